@@ -5,7 +5,27 @@ use seed::{
     *,
     prelude::*,
 };
-use shared::person::{ListPersons, Person};
+use seed_server_client::{ApiClient, FetchTransport};
+use shared::{ListPersons, Person, PersonEvent};
+
+///
+/// l'API est servie à la même origine que la page
+///
+fn api_url() -> String {
+    window().location().origin().unwrap_or_default()
+}
+
+///
+/// le WebSocket aussi, en wss:// quand la page est en https://
+///
+fn ws_url() -> String {
+    let location = window().location();
+    let scheme = match location.protocol() {
+        Ok(ref protocol) if protocol == "https:" => "wss",
+        _ => "ws",
+    };
+    format!("{}://{}/ws", scheme, location.host().unwrap_or_default())
+}
 
 struct Model {
     pub data: ListPersons,
@@ -13,6 +33,8 @@ struct Model {
     pub new_person: Person,
     pub person_lastname: String,
     pub person_firstname: String,
+    pub web_socket: Option<WebSocket>,
+    pub web_socket_reconnector: Option<StreamHandle>,
 }
 
 impl Default for Model {
@@ -29,6 +51,8 @@ impl Default for Model {
             new_person,
             person_lastname,
             person_firstname,
+            web_socket: None,
+            web_socket_reconnector: None,
         }
     }
 }
@@ -44,6 +68,11 @@ enum Msg {
     DeletePerson,
     NewFirstName(String),
     NewLastName(String),
+    WebSocketOpened,
+    EventReceived(PersonEvent),
+    WebSocketClosed(CloseEvent),
+    WebSocketFailed,
+    ReconnectWebSocket(usize),
 }
 
 
@@ -54,20 +83,15 @@ fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
             orders.skip();
             orders.perform_cmd(
                 async {
-                    let url = "https://localhost:8000/json_list";
-
-                    let response = fetch(url)
-                        .await
-                        .expect("HTTP reqwest failed !");
-
-                    let list_persons = response
-                        .check_status()
-                        .expect("status check failed !")
-                        .json::<ListPersons>()
-                        .await
-                        .expect("deserialisation failed !");
-
-                    Msg::Fetched(list_persons)
+                    let api = ApiClient::new(api_url(), FetchTransport);
+
+                    match api.list_persons_wrapped().await {
+                        Ok(list_persons) => Some(Msg::Fetched(list_persons)),
+                        Err(e) => {
+                            log!("impossible de charger la liste : ", e.to_string());
+                            None
+                        }
+                    }
                 });
         }
 
@@ -75,6 +99,49 @@ fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
             model.data = data;
         }
 
+        // la connexion WebSocket est (r)ouverte
+        // on recharge la liste pour ne pas rater les changements
+        // faits pendant la déconnexion
+        //
+        Msg::WebSocketOpened => {
+            model.web_socket_reconnector = None;
+            orders.send_msg(Msg::FetchData);
+            log!("WebSocket ouvert");
+        }
+
+        // un autre client a ajouté, modifié ou effacé une personne
+        // on applique le changement à la liste sans tout recharger
+        //
+        Msg::EventReceived(event) => {
+            event.apply_to(&mut model.data);
+        }
+
+        // la connexion est perdue : on essaie de se reconnecter
+        // avec des délais de plus en plus longs
+        //
+        Msg::WebSocketClosed(close_event) => {
+            log!("WebSocket fermé : ", close_event.code(), close_event.reason());
+            if model.web_socket_reconnector.is_none() {
+                model.web_socket_reconnector = Some(
+                    orders.stream_with_handle(streams::backoff(None, Msg::ReconnectWebSocket)),
+                );
+            }
+        }
+
+        Msg::WebSocketFailed => {
+            log!("WebSocket en erreur");
+            if model.web_socket_reconnector.is_none() {
+                model.web_socket_reconnector = Some(
+                    orders.stream_with_handle(streams::backoff(None, Msg::ReconnectWebSocket)),
+                );
+            }
+        }
+
+        Msg::ReconnectWebSocket(retries) => {
+            log!("Reconnexion WebSocket, essai : ", retries);
+            model.web_socket = create_websocket(orders);
+        }
+
         //lorsqu'on clique sur une rangée de la table, les données de la Person
         // affichées dans la rangée sont placées dans la variable "person"
         // du modèle.
@@ -175,6 +242,36 @@ fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
     }
 }
 
+///
+/// ouvre la connexion WebSocket avec le serveur
+/// les messages reçus sont des PersonEvent en JSON
+///
+fn create_websocket(orders: &mut impl Orders<Msg>) -> Option<WebSocket> {
+    let web_socket = WebSocket::builder(ws_url(), orders)
+        .on_open(|| Msg::WebSocketOpened)
+        .on_message(|message: WebSocketMessage| {
+            match message.json::<PersonEvent>() {
+                Ok(event) => Some(Msg::EventReceived(event)),
+                Err(e) => {
+                    log!("message WebSocket invalide : ", e);
+                    None
+                }
+            }
+        })
+        .on_close(Msg::WebSocketClosed)
+        .on_error(|| Msg::WebSocketFailed)
+        .build_and_open();
+
+    match web_socket {
+        Ok(web_socket) => Some(web_socket),
+        Err(e) => {
+            log!("impossible d'ouvrir le WebSocket : ", e);
+            orders.send_msg(Msg::WebSocketFailed);
+            None
+        }
+    }
+}
+
 ///
 /// Montre la liste des Personnes
 /// sur base du Vec<Person> compris dans la structure Data
@@ -343,6 +440,8 @@ fn after_mount(_url: Url, orders: &mut impl Orders<Msg>) -> AfterMount<Model> {
         new_person: Person::default(),
         person_lastname: "".to_string(),
         person_firstname: "".to_string(),
+        web_socket: None,
+        web_socket_reconnector: None,
     };
 /*
     // s'il y a des données dans le local_store
@@ -364,6 +463,7 @@ fn after_mount(_url: Url, orders: &mut impl Orders<Msg>) -> AfterMount<Model> {
  */
 
     orders.send_msg(Msg::FetchData);
+    model.web_socket = create_websocket(orders);
 
     log!(model.data.list_persons);
     AfterMount::new(model)
@@ -0,0 +1,52 @@
+// seed-server-client/src/fetch.rs
+
+use async_trait::async_trait;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{RequestInit, RequestMode};
+
+use crate::transport::{Request, Response, Transport};
+use crate::ApiError;
+
+///
+/// le transport du navigateur, avec l'API fetch de web-sys
+///
+#[derive(Debug, Clone, Default)]
+pub struct FetchTransport;
+
+fn js_error(value: wasm_bindgen::JsValue) -> ApiError {
+    ApiError::Transport(format!("{:?}", value))
+}
+
+#[async_trait(?Send)]
+impl Transport for FetchTransport {
+    async fn send(&self, request: Request) -> Result<Response, ApiError> {
+        let mut init = RequestInit::new();
+        init.method(request.method.as_str());
+        init.mode(RequestMode::Cors);
+        if let Some(body) = &request.body {
+            init.body(Some(&wasm_bindgen::JsValue::from_str(body)));
+        }
+
+        let web_request =
+            web_sys::Request::new_with_str_and_init(&request.url, &init).map_err(js_error)?;
+        for (name, value) in &request.headers {
+            web_request.headers().set(name, value).map_err(js_error)?;
+        }
+
+        let window = web_sys::window().ok_or_else(|| ApiError::Transport("no window".into()))?;
+        let response = JsFuture::from(window.fetch_with_request(&web_request))
+            .await
+            .map_err(js_error)?;
+        let response: web_sys::Response = response.dyn_into().map_err(js_error)?;
+
+        let text = JsFuture::from(response.text().map_err(js_error)?)
+            .await
+            .map_err(js_error)?;
+
+        Ok(Response {
+            status: response.status(),
+            body: text.as_string().unwrap_or_default(),
+        })
+    }
+}
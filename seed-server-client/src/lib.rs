@@ -0,0 +1,527 @@
+// seed-server-client/src/lib.rs
+
+//!
+//! le client typé de l'API seed-server
+//! partagé par le client Seed (transport fetch, feature "wasm")
+//! et les outils en ligne de commande (transport reqwest, feature "native")
+//! les webhooks (feature "mongo") ne sont pas compilés pour le client wasm
+//!
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use thiserror::Error;
+
+use shared::{
+    ApiKey, BatchRequest, BatchResponse, ComplianceEntry, ErasureReport, ErrorEnvelope,
+    IssuedApiKey, ListPersons, NewApiKey, Person,
+};
+#[cfg(feature = "mongo")]
+use shared::{Delivery, NewWebhook, Webhook};
+
+pub mod transport;
+
+#[cfg(feature = "wasm")]
+mod fetch;
+#[cfg(feature = "native")]
+mod native;
+
+#[cfg(feature = "wasm")]
+pub use crate::fetch::FetchTransport;
+#[cfg(feature = "native")]
+pub use crate::native::NativeTransport;
+pub use crate::transport::{Method, Request, Response, Transport};
+
+#[derive(Error, Debug)]
+pub enum ApiError {
+    #[error("transport error: {0}")]
+    Transport(String),
+
+    #[error("HTTP {status}: {}", .envelope.message)]
+    Api {
+        status: u16,
+        envelope: ErrorEnvelope,
+    },
+
+    #[error("invalid response: {0}")]
+    Decode(String),
+
+    #[error("invalid request: {0}")]
+    Encode(String),
+}
+
+impl ApiError {
+    ///
+    /// le code HTTP si le serveur a répondu par une erreur
+    ///
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            ApiError::Api { status, .. } => Some(*status),
+            _ => None,
+        }
+    }
+}
+
+///
+/// le client : une méthode par route de l'API
+///
+pub struct ApiClient<T: Transport> {
+    base_url: String,
+    transport: T,
+    admin_token: Option<String>,
+    api_key: Option<String>,
+    // les nouvelles tentatives d'une requête avec Idempotency-Key, après une erreur de transport
+    retries: u32,
+}
+
+///
+/// une nouvelle clé pour l'en-tête Idempotency-Key
+///
+pub fn new_idempotency_key() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+impl<T: Transport> ApiClient<T> {
+    pub fn new(base_url: impl Into<String>, transport: T) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_owned(),
+            transport,
+            admin_token: None,
+            api_key: None,
+            retries: 2,
+        }
+    }
+
+    ///
+    /// le jeton envoyé dans X-Admin-Token pour les routes d'administration
+    ///
+    pub fn with_admin_token(mut self, token: impl Into<String>) -> Self {
+        self.admin_token = Some(token.into());
+        self
+    }
+
+    ///
+    /// la clé d'API envoyée dans Authorization: Bearer
+    ///
+    pub fn with_api_key(mut self, key: impl Into<String>) -> Self {
+        self.api_key = Some(key.into());
+        self
+    }
+
+    ///
+    /// le nombre de nouvelles tentatives des requêtes qui créent (2 par défaut)
+    /// elles gardent leur Idempotency-Key : le serveur ne crée rien deux fois
+    ///
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    // ---- personnes ----
+
+    /// GET /json
+    pub async fn list_persons(&self) -> Result<Vec<Person>, ApiError> {
+        self.call(Method::Get, "/json", None::<&()>).await
+    }
+
+    /// GET /json_list
+    pub async fn list_persons_wrapped(&self) -> Result<ListPersons, ApiError> {
+        self.call(Method::Get, "/json_list", None::<&()>).await
+    }
+
+    /// GET /string
+    pub async fn list_persons_text(&self) -> Result<String, ApiError> {
+        let response = self.send(Method::Get, "/string", None, None).await?;
+        Ok(response.body)
+    }
+
+    /// GET /json/{id}
+    pub async fn get_person(&self, id: &str) -> Result<Option<Person>, ApiError> {
+        self.call(Method::Get, &format!("/json/{}", id), None::<&()>)
+            .await
+    }
+
+    /// POST /json
+    /// avec une nouvelle clé Idempotency-Key, gardée pour les nouvelles tentatives
+    pub async fn add_person(&self, person: &Person) -> Result<Person, ApiError> {
+        self.add_person_with_key(person, &new_idempotency_key())
+            .await
+    }
+
+    /// POST /json avec la clé Idempotency-Key de l'appelant
+    /// la même clé et la même personne rendent la personne déjà ajoutée
+    pub async fn add_person_with_key(
+        &self,
+        person: &Person,
+        key: &str,
+    ) -> Result<Person, ApiError> {
+        self.call_idempotent(Method::Post, "/json", person, key)
+            .await
+    }
+
+    /// PUT /json/{id}
+    /// renvoie la personne avant la modification, None si elle n'existe pas
+    pub async fn modify_person(
+        &self,
+        id: &str,
+        person: &Person,
+    ) -> Result<Option<Person>, ApiError> {
+        self.call(Method::Put, &format!("/json/{}", id), Some(person))
+            .await
+    }
+
+    /// DELETE /json/{id}
+    pub async fn delete_person(&self, person: &Person) -> Result<Option<Person>, ApiError> {
+        let id = person
+            .id
+            .as_ref()
+            .map(|id| id.to_string())
+            .unwrap_or_default();
+        self.call(Method::Delete, &format!("/json/{}", id), Some(person))
+            .await
+    }
+
+    /// POST /persons/batch
+    /// avec une nouvelle clé Idempotency-Key, comme add_person
+    pub async fn batch_persons(&self, batch: &BatchRequest) -> Result<BatchResponse, ApiError> {
+        self.call_idempotent(
+            Method::Post,
+            "/persons/batch",
+            batch,
+            &new_idempotency_key(),
+        )
+        .await
+    }
+
+    // ---- webhooks (administration) ----
+
+    /// GET /webhooks
+    #[cfg(feature = "mongo")]
+    pub async fn list_webhooks(&self) -> Result<Vec<Webhook>, ApiError> {
+        self.call(Method::Get, "/webhooks", None::<&()>).await
+    }
+
+    /// POST /webhooks
+    #[cfg(feature = "mongo")]
+    pub async fn add_webhook(&self, new_hook: &NewWebhook) -> Result<Webhook, ApiError> {
+        self.call(Method::Post, "/webhooks", Some(new_hook)).await
+    }
+
+    /// DELETE /webhooks/{id}
+    #[cfg(feature = "mongo")]
+    pub async fn delete_webhook(&self, id: &str) -> Result<Webhook, ApiError> {
+        self.call(Method::Delete, &format!("/webhooks/{}", id), None::<&()>)
+            .await
+    }
+
+    /// GET /webhooks/{id}/deliveries
+    #[cfg(feature = "mongo")]
+    pub async fn list_deliveries(&self, id: &str) -> Result<Vec<Delivery>, ApiError> {
+        self.call(
+            Method::Get,
+            &format!("/webhooks/{}/deliveries", id),
+            None::<&()>,
+        )
+        .await
+    }
+
+    // ---- clés d'API (administration) ----
+
+    /// GET /admin/keys
+    pub async fn list_api_keys(&self) -> Result<Vec<ApiKey>, ApiError> {
+        self.call(Method::Get, "/admin/keys", None::<&()>).await
+    }
+
+    /// POST /admin/keys
+    /// le secret de la clé n'est donné qu'ici
+    pub async fn create_api_key(&self, new_key: &NewApiKey) -> Result<IssuedApiKey, ApiError> {
+        self.call(Method::Post, "/admin/keys", Some(new_key)).await
+    }
+
+    /// DELETE /admin/keys/{id}
+    pub async fn revoke_api_key(&self, id: &str) -> Result<ApiKey, ApiError> {
+        self.call(Method::Delete, &format!("/admin/keys/{}", id), None::<&()>)
+            .await
+    }
+
+    /// POST /admin/keys/{id}/rotate
+    pub async fn rotate_api_key(&self, id: &str) -> Result<IssuedApiKey, ApiError> {
+        self.call(
+            Method::Post,
+            &format!("/admin/keys/{}/rotate", id),
+            None::<&()>,
+        )
+        .await
+    }
+
+    // ---- RGPD (administration) ----
+
+    /// GET /persons/{id}/gdpr-export
+    /// tout ce que le serveur garde sur la personne ; le format est décrit par /openapi.json
+    pub async fn gdpr_export(&self, id: &str) -> Result<serde_json::Value, ApiError> {
+        self.call(
+            Method::Get,
+            &format!("/persons/{}/gdpr-export", id),
+            None::<&()>,
+        )
+        .await
+    }
+
+    /// POST /persons/{id}/erase
+    /// irréversible : la personne et son historique sont effacés
+    pub async fn erase_person(&self, id: &str) -> Result<ErasureReport, ApiError> {
+        self.call(Method::Post, &format!("/persons/{}/erase", id), None::<&()>)
+            .await
+    }
+
+    /// GET /admin/compliance
+    pub async fn compliance_log(
+        &self,
+        person_id: Option<&str>,
+    ) -> Result<Vec<ComplianceEntry>, ApiError> {
+        let path = match person_id {
+            Some(id) => format!("/admin/compliance?person={}", id),
+            None => "/admin/compliance".to_owned(),
+        };
+        self.call(Method::Get, &path, None::<&()>).await
+    }
+
+    // ---- documentation ----
+
+    /// GET /openapi.json
+    pub async fn openapi(&self) -> Result<serde_json::Value, ApiError> {
+        self.call(Method::Get, "/openapi.json", None::<&()>).await
+    }
+
+    // ---- envoi ----
+
+    async fn call<B: Serialize + ?Sized, R: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&B>,
+    ) -> Result<R, ApiError> {
+        let body = match body {
+            Some(body) => Some(encode(body)?),
+            None => None,
+        };
+        let response = self.send(method, path, body, None).await?;
+        decode(&response)
+    }
+
+    // la requête est renvoyée avec la même clé tant que le transport échoue
+    async fn call_idempotent<B: Serialize + ?Sized, R: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        body: &B,
+        key: &str,
+    ) -> Result<R, ApiError> {
+        let body = encode(body)?;
+        let mut attempt = 0;
+        loop {
+            match self.send(method, path, Some(body.clone()), Some(key)).await {
+                Ok(response) => return decode(&response),
+                Err(ApiError::Transport(_)) if attempt < self.retries => attempt += 1,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    async fn send(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<String>,
+        idempotency_key: Option<&str>,
+    ) -> Result<Response, ApiError> {
+        let mut headers = vec![("Accept".to_owned(), "application/json".to_owned())];
+        if body.is_some() {
+            headers.push(("Content-Type".to_owned(), "application/json".to_owned()));
+        }
+        if let Some(token) = &self.admin_token {
+            headers.push(("X-Admin-Token".to_owned(), token.clone()));
+        }
+        if let Some(key) = &self.api_key {
+            headers.push(("Authorization".to_owned(), format!("Bearer {}", key)));
+        }
+        if let Some(key) = idempotency_key {
+            headers.push(("Idempotency-Key".to_owned(), key.to_owned()));
+        }
+
+        let response = self
+            .transport
+            .send(Request {
+                method,
+                url: format!("{}{}", self.base_url, path),
+                headers,
+                body,
+            })
+            .await?;
+
+        if response.status >= 200 && response.status < 300 {
+            Ok(response)
+        } else {
+            // le serveur répond avec ErrorEnvelope, sauf erreurs très précoces
+            let envelope = serde_json::from_str::<ErrorEnvelope>(&response.body)
+                .unwrap_or_else(|_| ErrorEnvelope::new("http_error", response.body.clone()));
+            Err(ApiError::Api {
+                status: response.status,
+                envelope,
+            })
+        }
+    }
+}
+
+fn encode<B: Serialize + ?Sized>(body: &B) -> Result<String, ApiError> {
+    serde_json::to_string(body).map_err(|e| ApiError::Encode(e.to_string()))
+}
+
+fn decode<R: DeserializeOwned>(response: &Response) -> Result<R, ApiError> {
+    serde_json::from_str(&response.body).map_err(|e| ApiError::Decode(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use futures::executor::block_on;
+    use std::cell::{Cell, RefCell};
+
+    // un faux transport qui garde les requêtes et renvoie une réponse fixe
+    // après `failures` erreurs de transport
+    struct MockTransport {
+        response: Response,
+        requests: RefCell<Vec<Request>>,
+        failures: Cell<u32>,
+    }
+
+    #[async_trait(?Send)]
+    impl Transport for MockTransport {
+        async fn send(&self, request: Request) -> Result<Response, ApiError> {
+            self.requests.borrow_mut().push(request);
+            if self.failures.get() > 0 {
+                self.failures.set(self.failures.get() - 1);
+                return Err(ApiError::Transport("connection reset".to_owned()));
+            }
+            Ok(self.response.clone())
+        }
+    }
+
+    fn header<'a>(request: &'a Request, name: &str) -> Option<&'a str> {
+        request
+            .headers
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn client(status: u16, body: &str) -> ApiClient<MockTransport> {
+        ApiClient::new(
+            "http://localhost:8000/",
+            MockTransport {
+                response: Response {
+                    status,
+                    body: body.to_owned(),
+                },
+                requests: RefCell::new(Vec::new()),
+                failures: Cell::new(0),
+            },
+        )
+    }
+
+    #[test]
+    fn test_add_person_request() {
+        let api = client(200, r#"{"id":null,"nom":"BERLIOZ","prenom":"Hector"}"#);
+        let pers = Person {
+            id: None,
+            nom: "BERLIOZ".to_owned(),
+            prenom: "Hector".to_owned(),
+        };
+
+        let added = block_on(api.add_person(&pers)).unwrap();
+        assert_eq!(added, pers);
+
+        let requests = api.transport.requests.borrow();
+        assert_eq!(requests[0].method, Method::Post);
+        assert_eq!(requests[0].url, "http://localhost:8000/json");
+        assert!(requests[0].body.as_ref().unwrap().contains("BERLIOZ"));
+        assert!(header(&requests[0], "Idempotency-Key").is_some());
+    }
+
+    #[test]
+    fn test_add_person_retries_with_same_key() {
+        let api = client(200, r#"{"id":null,"nom":"BERLIOZ","prenom":"Hector"}"#);
+        let pers = Person {
+            id: None,
+            nom: "BERLIOZ".to_owned(),
+            prenom: "Hector".to_owned(),
+        };
+
+        // deux coupures puis la réponse : trois envois avec la même clé
+        api.transport.failures.set(2);
+        assert_eq!(block_on(api.add_person(&pers)).unwrap(), pers);
+        {
+            let requests = api.transport.requests.borrow();
+            assert_eq!(requests.len(), 3);
+            let key = header(&requests[0], "Idempotency-Key").unwrap();
+            assert!(requests
+                .iter()
+                .all(|r| header(r, "Idempotency-Key") == Some(key)));
+        }
+
+        // ou la clé choisie par l'appelant
+        block_on(api.add_person_with_key(&pers, "cle-choisie")).unwrap();
+        let requests = api.transport.requests.borrow();
+        assert_eq!(header(&requests[3], "Idempotency-Key"), Some("cle-choisie"));
+        assert_ne!(header(&requests[0], "Idempotency-Key"), Some("cle-choisie"));
+    }
+
+    #[test]
+    fn test_add_person_gives_up_after_retries() {
+        let api = client(200, "{}").with_retries(1);
+        let pers = Person {
+            id: None,
+            nom: "BERLIOZ".to_owned(),
+            prenom: "Hector".to_owned(),
+        };
+
+        api.transport.failures.set(2);
+        let err = block_on(api.add_person(&pers)).unwrap_err();
+        assert!(matches!(err, ApiError::Transport(_)));
+        assert_eq!(api.transport.requests.borrow().len(), 2);
+    }
+
+    #[test]
+    fn test_api_key_header() {
+        let api = client(200, "[]").with_api_key("seed_0123abcd_secret");
+
+        let keys = block_on(api.list_api_keys()).unwrap();
+        assert!(keys.is_empty());
+
+        let requests = api.transport.requests.borrow();
+        assert_eq!(requests[0].url, "http://localhost:8000/admin/keys");
+        assert!(requests[0].headers.contains(&(
+            "Authorization".to_owned(),
+            "Bearer seed_0123abcd_secret".to_owned()
+        )));
+    }
+
+    #[test]
+    fn test_error_envelope() {
+        let api = client(
+            400,
+            r#"{"error":"invalid_id","message":"Invalid document id"}"#,
+        );
+
+        let err = block_on(api.get_person("pas-un-id")).unwrap_err();
+        assert_eq!(err.status(), Some(400));
+        match err {
+            ApiError::Api { envelope, .. } => assert_eq!(envelope.error, "invalid_id"),
+            other => panic!("unexpected error {:?}", other),
+        }
+    }
+}
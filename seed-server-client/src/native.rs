@@ -0,0 +1,50 @@
+// seed-server-client/src/native.rs
+
+use async_trait::async_trait;
+
+use crate::transport::{Method, Request, Response, Transport};
+use crate::ApiError;
+
+///
+/// le transport des outils natifs, avec reqwest
+///
+#[derive(Debug, Clone, Default)]
+pub struct NativeTransport {
+    client: reqwest::Client,
+}
+
+impl NativeTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait(?Send)]
+impl Transport for NativeTransport {
+    async fn send(&self, request: Request) -> Result<Response, ApiError> {
+        let mut builder = match request.method {
+            Method::Get => self.client.get(&request.url),
+            Method::Post => self.client.post(&request.url),
+            Method::Put => self.client.put(&request.url),
+            Method::Delete => self.client.delete(&request.url),
+        };
+        for (name, value) in &request.headers {
+            builder = builder.header(name.as_str(), value.as_str());
+        }
+        if let Some(body) = request.body {
+            builder = builder.body(body);
+        }
+
+        let response = builder
+            .send()
+            .await
+            .map_err(|e| ApiError::Transport(e.to_string()))?;
+        let status = response.status().as_u16();
+        let body = response
+            .text()
+            .await
+            .map_err(|e| ApiError::Transport(e.to_string()))?;
+
+        Ok(Response { status, body })
+    }
+}
@@ -0,0 +1,55 @@
+// seed-server-client/src/transport.rs
+
+use async_trait::async_trait;
+
+use crate::ApiError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    Get,
+    Post,
+    Put,
+    Delete,
+}
+
+impl Method {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Method::Get => "GET",
+            Method::Post => "POST",
+            Method::Put => "PUT",
+            Method::Delete => "DELETE",
+        }
+    }
+}
+
+///
+/// une requête HTTP prête à envoyer
+/// url est complète, body est du JSON
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Request {
+    pub method: Method,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<String>,
+}
+
+///
+/// la réponse brute : le code HTTP et le corps en texte
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub body: String,
+}
+
+///
+/// le transport envoie les requêtes
+/// fetch dans le navigateur, reqwest dans les outils natifs,
+/// ou un faux transport dans les tests
+///
+#[async_trait(?Send)]
+pub trait Transport {
+    async fn send(&self, request: Request) -> Result<Response, ApiError>;
+}
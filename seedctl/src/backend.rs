@@ -0,0 +1,166 @@
+// seedctl/src/backend.rs
+
+use async_trait::async_trait;
+
+use seed_server_client::{ApiClient, ApiError, NativeTransport};
+use server::api_keys::{self, KeyStore};
+use server::store::{self, PersonStore};
+use server::tenancy::Tenant;
+use shared::{ApiKey, IssuedApiKey, NewApiKey, Person};
+
+///
+/// là où seedctl lit et écrit les personnes :
+/// l'API HTTP du serveur, ou directement la base (MongoDB ou SQLite, selon SEED_STORAGE)
+///
+#[async_trait(?Send)]
+pub trait Backend {
+    async fn list(&self) -> Result<Vec<Person>, String>;
+    async fn get(&self, id: &str) -> Result<Option<Person>, String>;
+    async fn add(&self, person: Person) -> Result<Person, String>;
+    async fn modify(&self, id: &str, person: Person) -> Result<Option<Person>, String>;
+    async fn delete(&self, id: &str) -> Result<Option<Person>, String>;
+
+    async fn list_keys(&self) -> Result<Vec<ApiKey>, String>;
+    async fn create_key(&self, new_key: NewApiKey) -> Result<IssuedApiKey, String>;
+    async fn revoke_key(&self, id: &str) -> Result<Option<ApiKey>, String>;
+    async fn rotate_key(&self, id: &str) -> Result<Option<IssuedApiKey>, String>;
+}
+
+// 404 : la clé n'existe pas
+fn not_found<T>(result: Result<T, ApiError>) -> Result<Option<T>, String> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(e) if e.status() == Some(404) => Ok(None),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+///
+/// passe par l'API HTTP, avec le client typé
+/// la clé d'API (--api-key) est envoyée dans Authorization: Bearer
+///
+pub struct HttpBackend {
+    api: ApiClient<NativeTransport>,
+}
+
+impl HttpBackend {
+    pub fn new(url: &str, api_key: Option<String>) -> Self {
+        let api = ApiClient::new(url, NativeTransport::new());
+        Self {
+            api: match api_key {
+                Some(key) => api.with_api_key(key),
+                None => api,
+            },
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Backend for HttpBackend {
+    async fn list(&self) -> Result<Vec<Person>, String> {
+        self.api.list_persons().await.map_err(|e| e.to_string())
+    }
+
+    async fn get(&self, id: &str) -> Result<Option<Person>, String> {
+        self.api.get_person(id).await.map_err(|e| e.to_string())
+    }
+
+    async fn add(&self, person: Person) -> Result<Person, String> {
+        self.api
+            .add_person(&person)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    async fn modify(&self, id: &str, person: Person) -> Result<Option<Person>, String> {
+        self.api
+            .modify_person(id, &person)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    async fn delete(&self, id: &str) -> Result<Option<Person>, String> {
+        match self.get(id).await? {
+            Some(person) => self
+                .api
+                .delete_person(&person)
+                .await
+                .map_err(|e| e.to_string()),
+            None => Ok(None),
+        }
+    }
+
+    async fn list_keys(&self) -> Result<Vec<ApiKey>, String> {
+        self.api.list_api_keys().await.map_err(|e| e.to_string())
+    }
+
+    async fn create_key(&self, new_key: NewApiKey) -> Result<IssuedApiKey, String> {
+        self.api
+            .create_api_key(&new_key)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    async fn revoke_key(&self, id: &str) -> Result<Option<ApiKey>, String> {
+        not_found(self.api.revoke_api_key(id).await)
+    }
+
+    async fn rotate_key(&self, id: &str) -> Result<Option<IssuedApiKey>, String> {
+        not_found(self.api.rotate_api_key(id).await)
+    }
+}
+
+///
+/// travaille directement sur la base, sans serveur
+/// attention : les clients connectés ne sont pas prévenus des changements
+///
+pub struct DirectBackend;
+
+impl DirectBackend {
+    fn store(&self) -> Result<Box<dyn PersonStore>, String> {
+        store::open_store(&Tenant::default()).map_err(|e| e.to_string())
+    }
+
+    fn keys(&self) -> Result<Box<dyn KeyStore>, String> {
+        api_keys::open_key_store().map_err(|e| e.to_string())
+    }
+}
+
+#[async_trait(?Send)]
+impl Backend for DirectBackend {
+    async fn list(&self) -> Result<Vec<Person>, String> {
+        self.store()?.list().map_err(|e| e.to_string())
+    }
+
+    async fn get(&self, id: &str) -> Result<Option<Person>, String> {
+        self.store()?.get(id).map_err(|e| e.to_string())
+    }
+
+    async fn add(&self, person: Person) -> Result<Person, String> {
+        self.store()?.add(person).map_err(|e| e.to_string())
+    }
+
+    async fn modify(&self, id: &str, person: Person) -> Result<Option<Person>, String> {
+        self.store()?.modify(id, person).map_err(|e| e.to_string())
+    }
+
+    async fn delete(&self, id: &str) -> Result<Option<Person>, String> {
+        self.store()?.delete(id).map_err(|e| e.to_string())
+    }
+
+    async fn list_keys(&self) -> Result<Vec<ApiKey>, String> {
+        self.keys()?.list().map_err(|e| e.to_string())
+    }
+
+    async fn create_key(&self, new_key: NewApiKey) -> Result<IssuedApiKey, String> {
+        api_keys::create_key(&*self.keys()?, new_key, None).map_err(|e| e.to_string())
+    }
+
+    async fn revoke_key(&self, id: &str) -> Result<Option<ApiKey>, String> {
+        api_keys::revoke_key(&*self.keys()?, id).map_err(|e| e.to_string())
+    }
+
+    async fn rotate_key(&self, id: &str) -> Result<Option<IssuedApiKey>, String> {
+        api_keys::rotate_key(&*self.keys()?, id).map_err(|e| e.to_string())
+    }
+}
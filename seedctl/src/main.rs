@@ -0,0 +1,464 @@
+// seedctl/src/main.rs
+
+//!
+//! seedctl : l'outil d'administration de la base des personnes
+//! passe par l'API HTTP du serveur (par défaut) ou directement par la base (--direct)
+//! en cas d'erreur, le code de sortie est 1
+//!
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use structopt::StructOpt;
+
+use server::backup::{self, RestoreMode};
+use server::compliance;
+use server::config::Config;
+use server::db_postgres::PostgresIdMap;
+use server::event_log::EventLog;
+use server::migrations;
+use server::store::{self, Backend as Storage};
+use server::store_migration;
+use server::tenancy::{Tenant, TenantMode};
+use server::{db_mongo, db_postgres, db_sqlite};
+use shared::{IssuedApiKey, NewApiKey, Person, Scope};
+
+mod backend;
+mod output;
+
+use crate::backend::{Backend, DirectBackend, HttpBackend};
+use crate::output::{person_id, read_persons, write_keys, write_persons, Format};
+
+#[derive(StructOpt)]
+#[structopt(name = "seedctl", about = "Administration de la base des personnes")]
+struct Opt {
+    /// URL du serveur
+    #[structopt(long, env = "SEEDCTL_URL", default_value = "http://127.0.0.1:8000")]
+    url: String,
+
+    /// Clé d'API envoyée au serveur (Authorization: Bearer)
+    #[structopt(long, env = "SEEDCTL_API_KEY", hide_env_values = true)]
+    api_key: Option<String>,
+
+    /// Travaille directement sur la base (SEED_STORAGE), sans passer par le serveur
+    #[structopt(long)]
+    direct: bool,
+
+    /// Format de sortie : table, json ou csv
+    #[structopt(long, short, default_value = "table")]
+    format: Format,
+
+    #[structopt(subcommand)]
+    cmd: Command,
+}
+
+#[derive(StructOpt)]
+enum Command {
+    /// Liste toutes les personnes
+    List,
+    /// Affiche une personne
+    Get { id: String },
+    /// Ajoute une personne
+    Add {
+        #[structopt(long)]
+        nom: String,
+        #[structopt(long)]
+        prenom: String,
+    },
+    /// Modifie le nom et/ou le prénom d'une personne
+    Edit {
+        id: String,
+        #[structopt(long)]
+        nom: Option<String>,
+        #[structopt(long)]
+        prenom: Option<String>,
+    },
+    /// Efface une personne
+    Delete { id: String },
+    /// Importe des personnes depuis un fichier .json ou .csv
+    Import { file: PathBuf },
+    /// Exporte toutes les personnes, dans un fichier ou sur la sortie standard
+    Export {
+        #[structopt(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Cherche les personnes dont le nom ou le prénom contient le texte
+    Search { text: String },
+    /// Trouve les doublons (même nom et prénom) ; --apply efface les copies
+    Dedupe {
+        #[structopt(long)]
+        apply: bool,
+    },
+    /// Sauvegarde les personnes et le journal dans une archive .tar.gz (toujours directement sur la base)
+    Backup { output: PathBuf },
+    /// Vérifie puis restaure une archive .tar.gz (toujours directement sur la base)
+    Restore {
+        file: PathBuf,
+        /// merge ou replace
+        #[structopt(long, default_value = "merge")]
+        mode: RestoreMode,
+    },
+    /// Applique les migrations de la base (toujours directement sur la base)
+    Migrate {
+        /// Montre seulement ce qui serait changé
+        #[structopt(long)]
+        dry_run: bool,
+    },
+    /// Copie les personnes d'un stockage vers PostgreSQL, puis vérifie la copie
+    /// (toujours directement sur les bases ; peut être relancée après une coupure)
+    MigrateStore {
+        /// mongo ou sqlite
+        #[structopt(long)]
+        from: Storage,
+        /// postgres
+        #[structopt(long)]
+        to: Storage,
+    },
+    /// Gère les clés d'API (la première clé admin se crée avec --direct)
+    Key(KeyCommand),
+}
+
+#[derive(StructOpt)]
+enum KeyCommand {
+    /// Liste les clés, révoquées comprises
+    List,
+    /// Crée une clé ; son secret n'est affiché qu'une fois
+    Create {
+        #[structopt(long)]
+        name: String,
+        /// read, write ou admin ; peut être répété
+        #[structopt(long = "scope", required = true)]
+        scopes: Vec<Scope>,
+        /// Limite la clé à un locataire
+        #[structopt(long)]
+        tenant: Option<String>,
+        /// La clé expire après ce nombre de jours
+        #[structopt(long)]
+        expires_in_days: Option<u32>,
+    },
+    /// Révoque une clé
+    Revoke { id: String },
+    /// Remplace le secret d'une clé ; l'ancien est refusé aussitôt
+    Rotate { id: String },
+}
+
+#[tokio::main]
+async fn main() {
+    let opt = Opt::from_args();
+    // même base que le serveur : SEED_MONGO_URI et SEED_DATABASE
+    let config = Config::from_env();
+    store::configure(config.storage);
+    db_mongo::configure(&config.mongo_uri, &config.database);
+    db_sqlite::configure(&config.sqlite_path);
+    db_postgres::configure(&config.postgres_url);
+
+    let backend: Box<dyn Backend> = if opt.direct {
+        Box::new(DirectBackend)
+    } else {
+        Box::new(HttpBackend::new(&opt.url, opt.api_key.clone()))
+    };
+
+    if let Err(e) = run(backend.as_ref(), opt.format, config.tenant_mode, opt.cmd).await {
+        eprintln!("seedctl: {}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn run(
+    backend: &dyn Backend,
+    format: Format,
+    tenant_mode: TenantMode,
+    cmd: Command,
+) -> Result<(), String> {
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+
+    match cmd {
+        Command::List => {
+            let persons = backend.list().await?;
+            write_persons(&mut out, &persons, format)
+        }
+        Command::Get { id } => match backend.get(&id).await? {
+            Some(person) => write_persons(&mut out, &[person], format),
+            None => Err(format!("person {} not found", id)),
+        },
+        Command::Add { nom, prenom } => {
+            let added = backend
+                .add(Person {
+                    id: None,
+                    nom,
+                    prenom,
+                })
+                .await?;
+            write_persons(&mut out, &[added], format)
+        }
+        Command::Edit { id, nom, prenom } => {
+            let current = backend
+                .get(&id)
+                .await?
+                .ok_or_else(|| format!("person {} not found", id))?;
+            let modified = Person {
+                id: current.id.clone(),
+                nom: nom.unwrap_or(current.nom),
+                prenom: prenom.unwrap_or(current.prenom),
+            };
+            match backend.modify(&id, modified.clone()).await? {
+                Some(_) => write_persons(&mut out, &[modified], format),
+                None => Err(format!("person {} not found", id)),
+            }
+        }
+        Command::Delete { id } => match backend.delete(&id).await? {
+            Some(deleted) => write_persons(&mut out, &[deleted], format),
+            None => Err(format!("person {} not found", id)),
+        },
+        Command::Import { file } => {
+            let file_format = format_from_extension(&file)?;
+            let mut input = File::open(&file).map_err(|e| format!("{}: {}", file.display(), e))?;
+            let persons = read_persons(&mut input, file_format)?;
+            let mut added = Vec::with_capacity(persons.len());
+            for person in persons {
+                added.push(backend.add(person).await?);
+            }
+            write_persons(&mut out, &added, format)
+        }
+        Command::Export { output } => {
+            let persons = backend.list().await?;
+            match output {
+                Some(path) => {
+                    let file_format = format_from_extension(&path)?;
+                    let mut file =
+                        File::create(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+                    write_persons(&mut file, &persons, file_format)?;
+                    writeln!(
+                        out,
+                        "{} persons exported to {}",
+                        persons.len(),
+                        path.display()
+                    )
+                    .map_err(|e| e.to_string())
+                }
+                None => write_persons(&mut out, &persons, format),
+            }
+        }
+        Command::Search { text } => {
+            let text = text.to_lowercase();
+            let found: Vec<Person> = backend
+                .list()
+                .await?
+                .into_iter()
+                .filter(|p| {
+                    p.nom.to_lowercase().contains(&text) || p.prenom.to_lowercase().contains(&text)
+                })
+                .collect();
+            write_persons(&mut out, &found, format)
+        }
+        Command::Dedupe { apply } => {
+            let duplicates = find_duplicates(backend.list().await?);
+            if apply {
+                for person in &duplicates {
+                    backend.delete(&person_id(person)).await?;
+                }
+            }
+            write_persons(&mut out, &duplicates, format)
+        }
+        Command::Backup { output } => {
+            // le journal des événements n'existe qu'avec MongoDB
+            let audit = match store::backend() {
+                Storage::Mongo => EventLog::new().since(0).map_err(|e| e.to_string())?,
+                Storage::Sqlite | Storage::Postgres => Vec::new(),
+            };
+            let store = store::open_store(&Tenant::default()).map_err(|e| e.to_string())?;
+            let archive = backup::create_backup(&*store, &audit).map_err(|e| e.to_string())?;
+            std::fs::write(&output, &archive)
+                .map_err(|e| format!("{}: {}", output.display(), e))?;
+            writeln!(out, "backup written to {}", output.display()).map_err(|e| e.to_string())
+        }
+        Command::Restore { file, mode } => {
+            let archive = std::fs::read(&file).map_err(|e| format!("{}: {}", file.display(), e))?;
+            let store = store::open_store(&Tenant::default()).map_err(|e| e.to_string())?;
+            // les personnes effacées (droit à l'effacement) ne sont pas restaurées
+            let log = compliance::open_compliance_log().map_err(|e| e.to_string())?;
+            let erased = compliance::erased_ids(&*log, None).map_err(|e| e.to_string())?;
+            let report = backup::restore_backup(&*store, &archive, mode, &erased)
+                .map_err(|e| e.to_string())?;
+            writeln!(
+                out,
+                "{} persons restored ({}), {} erased persons skipped",
+                report.restored, report.mode, report.skipped_erased
+            )
+            .map_err(|e| e.to_string())
+        }
+        Command::Migrate { .. } if store::backend() == Storage::Sqlite => {
+            // le schéma SQLite est migré dès l'ouverture du fichier, même avec --dry-run
+            let pool = db_sqlite::pool(db_sqlite::path()).map_err(|e| e.to_string())?;
+            let mut conn = pool.get().map_err(|e| e.to_string())?;
+            let version = db_sqlite::migrate(&mut conn).map_err(|e| e.to_string())?;
+            writeln!(out, "sqlite schema version {}", version).map_err(|e| e.to_string())
+        }
+        Command::Migrate { .. } if store::backend() == Storage::Postgres => {
+            // comme SQLite : le pool applique les migrations à l'ouverture
+            let pool = db_postgres::pool().map_err(|e| e.to_string())?;
+            let mut conn = pool.get().map_err(|e| e.to_string())?;
+            let version = db_postgres::migrate(&mut conn).map_err(|e| e.to_string())?;
+            writeln!(out, "postgres schema version {}", version).map_err(|e| e.to_string())
+        }
+        Command::Migrate { dry_run } => {
+            let reports =
+                migrations::run_migrations(dry_run, tenant_mode).map_err(|e| e.to_string())?;
+            if reports.is_empty() {
+                return writeln!(out, "database is up to date").map_err(|e| e.to_string());
+            }
+            for report in reports {
+                writeln!(
+                    out,
+                    "{} {:20} {:>4} {:30} {} documents",
+                    if report.applied {
+                        "applied "
+                    } else {
+                        "pending "
+                    },
+                    report.database,
+                    report.version,
+                    report.name,
+                    report.documents
+                )
+                .map_err(|e| e.to_string())?;
+            }
+            Ok(())
+        }
+        Command::MigrateStore { from, to } => {
+            if to != Storage::Postgres || from == Storage::Postgres {
+                return Err("migrate-store copies from mongo or sqlite to postgres".to_owned());
+            }
+            let tenant = Tenant::default();
+            let source = store::open_store_with(from, &tenant).map_err(|e| e.to_string())?;
+            let target = store::open_store_with(to, &tenant).map_err(|e| e.to_string())?;
+            let map = PostgresIdMap::new(from.name(), None);
+            let report = store_migration::copy_persons(&*source, &*target, &map)
+                .map_err(|e| e.to_string())?;
+            writeln!(
+                out,
+                "{} persons copied, {} already copied\n{} {:>6} {}\n{} {:>6} {}",
+                report.copied,
+                report.skipped,
+                from.name(),
+                report.source_count,
+                report.source_checksum,
+                to.name(),
+                report.target_count,
+                report.target_checksum
+            )
+            .map_err(|e| e.to_string())?;
+            if report.verified() {
+                Ok(())
+            } else {
+                Err("the copy does not match the source, run migrate-store again".to_owned())
+            }
+        }
+        Command::Key(KeyCommand::List) => write_keys(&mut out, &backend.list_keys().await?, format),
+        Command::Key(KeyCommand::Create {
+            name,
+            scopes,
+            tenant,
+            expires_in_days,
+        }) => {
+            let expires_at = expires_in_days.map(|days| {
+                chrono::Utc::now().timestamp_millis() + i64::from(days) * 24 * 3600 * 1000
+            });
+            let issued = backend
+                .create_key(NewApiKey {
+                    name,
+                    scopes,
+                    tenant,
+                    expires_at,
+                })
+                .await?;
+            write_issued(&mut out, &issued, format)
+        }
+        Command::Key(KeyCommand::Revoke { id }) => match backend.revoke_key(&id).await? {
+            Some(key) => write_keys(&mut out, &[key], format),
+            None => Err(format!("API key {} not found", id)),
+        },
+        Command::Key(KeyCommand::Rotate { id }) => match backend.rotate_key(&id).await? {
+            Some(issued) => write_issued(&mut out, &issued, format),
+            None => Err(format!("API key {} not found", id)),
+        },
+    }
+}
+
+///
+/// une clé créée ou renouvelée : le secret suit la clé, il ne sera plus affiché
+///
+fn write_issued(out: &mut dyn Write, issued: &IssuedApiKey, format: Format) -> Result<(), String> {
+    if format == Format::Json {
+        serde_json::to_writer_pretty(&mut *out, issued).map_err(|e| e.to_string())?;
+        return writeln!(out).map_err(|e| e.to_string());
+    }
+    write_keys(out, &[issued.key.clone()], format)?;
+    writeln!(out, "\nsecret (shown only once): {}", issued.secret).map_err(|e| e.to_string())
+}
+
+fn format_from_extension(path: &Path) -> Result<Format, String> {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => Ok(Format::Json),
+        Some("csv") => Ok(Format::Csv),
+        _ => Err(format!("{}: expected a .json or .csv file", path.display())),
+    }
+}
+
+///
+/// les copies en trop : même nom et prénom, sans tenir compte de la casse ni des espaces
+/// on garde la première personne de chaque groupe
+///
+fn find_duplicates(persons: Vec<Person>) -> Vec<Person> {
+    let mut groups: BTreeMap<(String, String), Vec<Person>> = BTreeMap::new();
+    for person in persons {
+        let key = (
+            person.nom.trim().to_lowercase(),
+            person.prenom.trim().to_lowercase(),
+        );
+        groups.entry(key).or_insert_with(Vec::new).push(person);
+    }
+    groups
+        .into_iter()
+        .flat_map(|(_, group)| group.into_iter().skip(1))
+        .collect()
+}
+
+///
+/// les tests
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn person(nom: &str, prenom: &str) -> Person {
+        Person {
+            id: None,
+            nom: nom.to_owned(),
+            prenom: prenom.to_owned(),
+        }
+    }
+
+    ///
+    /// Test doublons : on garde la première personne de chaque groupe
+    ///
+    #[test]
+    fn test_find_duplicates() {
+        let persons = vec![
+            person("FAURE", "Gabriel"),
+            person("Faure ", "gabriel"),
+            person("FRANCK", "César"),
+            person("FAURE", "Gabriel"),
+        ];
+
+        let duplicates = find_duplicates(persons);
+
+        assert_eq!(
+            duplicates,
+            vec![person("Faure ", "gabriel"), person("FAURE", "Gabriel")]
+        );
+    }
+}
@@ -0,0 +1,219 @@
+// seedctl/src/output.rs
+
+use std::io::{Read, Write};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use shared::{ApiKey, Person};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Table,
+    Json,
+    Csv,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "table" => Ok(Format::Table),
+            "json" => Ok(Format::Json),
+            "csv" => Ok(Format::Csv),
+            other => Err(format!("unknown format '{}' (table, json or csv)", other)),
+        }
+    }
+}
+
+///
+/// une ligne CSV : id, nom, prenom
+///
+#[derive(Serialize, Deserialize)]
+struct CsvRow {
+    #[serde(default)]
+    id: String,
+    nom: String,
+    prenom: String,
+}
+
+pub fn person_id(person: &Person) -> String {
+    person
+        .id
+        .as_ref()
+        .map(|id| id.to_string())
+        .unwrap_or_default()
+}
+
+///
+/// écrit les personnes dans le format demandé
+///
+pub fn write_persons(
+    out: &mut dyn Write,
+    persons: &[Person],
+    format: Format,
+) -> Result<(), String> {
+    match format {
+        Format::Table => write_table(out, persons).map_err(|e| e.to_string()),
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut *out, persons).map_err(|e| e.to_string())?;
+            writeln!(out).map_err(|e| e.to_string())
+        }
+        Format::Csv => {
+            let mut writer = csv::Writer::from_writer(out);
+            for person in persons {
+                writer
+                    .serialize(CsvRow {
+                        id: person_id(person),
+                        nom: person.nom.clone(),
+                        prenom: person.prenom.clone(),
+                    })
+                    .map_err(|e| e.to_string())?;
+            }
+            writer.flush().map_err(|e| e.to_string())
+        }
+    }
+}
+
+fn write_table(out: &mut dyn Write, persons: &[Person]) -> std::io::Result<()> {
+    let rows: Vec<[String; 3]> = persons
+        .iter()
+        .map(|p| [person_id(p), p.nom.clone(), p.prenom.clone()])
+        .collect();
+    let headers = ["ID", "NOM", "PRENOM"];
+    let mut widths = [0; 3];
+    for (i, header) in headers.iter().enumerate() {
+        widths[i] = rows
+            .iter()
+            .map(|row| row[i].chars().count())
+            .chain(std::iter::once(header.len()))
+            .max()
+            .unwrap_or(0);
+    }
+
+    writeln!(
+        out,
+        "{:w0$}  {:w1$}  {}",
+        headers[0],
+        headers[1],
+        headers[2],
+        w0 = widths[0],
+        w1 = widths[1]
+    )?;
+    for row in rows {
+        writeln!(
+            out,
+            "{:w0$}  {:w1$}  {}",
+            row[0],
+            row[1],
+            row[2],
+            w0 = widths[0],
+            w1 = widths[1]
+        )?;
+    }
+    Ok(())
+}
+
+///
+/// une ligne de la liste des clés : jamais le secret
+///
+#[derive(Serialize)]
+struct KeyRow {
+    id: String,
+    prefix: String,
+    name: String,
+    scopes: String,
+    tenant: String,
+    status: &'static str,
+}
+
+impl From<&ApiKey> for KeyRow {
+    fn from(key: &ApiKey) -> Self {
+        Self {
+            id: key.id.clone(),
+            prefix: key.prefix.clone(),
+            name: key.name.clone(),
+            scopes: key
+                .scopes
+                .iter()
+                .map(|scope| scope.name())
+                .collect::<Vec<_>>()
+                .join(","),
+            tenant: key.tenant.clone().unwrap_or_default(),
+            status: if key.revoked_at.is_some() {
+                "revoked"
+            } else {
+                "active"
+            },
+        }
+    }
+}
+
+///
+/// écrit les clés d'API dans le format demandé
+///
+pub fn write_keys(out: &mut dyn Write, keys: &[ApiKey], format: Format) -> Result<(), String> {
+    match format {
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut *out, keys).map_err(|e| e.to_string())?;
+            writeln!(out).map_err(|e| e.to_string())
+        }
+        Format::Csv => {
+            let mut writer = csv::Writer::from_writer(out);
+            for key in keys {
+                writer
+                    .serialize(KeyRow::from(key))
+                    .map_err(|e| e.to_string())?;
+            }
+            writer.flush().map_err(|e| e.to_string())
+        }
+        Format::Table => {
+            writeln!(
+                out,
+                "{:36}  {:8}  {:7}  {:17}  {:10}  NAME",
+                "ID", "PREFIX", "STATUS", "SCOPES", "TENANT"
+            )
+            .map_err(|e| e.to_string())?;
+            for row in keys.iter().map(KeyRow::from) {
+                writeln!(
+                    out,
+                    "{:36}  {:8}  {:7}  {:17}  {:10}  {}",
+                    row.id, row.prefix, row.status, row.scopes, row.tenant, row.name
+                )
+                .map_err(|e| e.to_string())?;
+            }
+            Ok(())
+        }
+    }
+}
+
+///
+/// lit des personnes en JSON (tableau de Person) ou en CSV (colonnes nom, prenom)
+/// les id lus sont ignorés : les personnes importées reçoivent un nouvel id
+///
+pub fn read_persons(input: &mut dyn Read, format: Format) -> Result<Vec<Person>, String> {
+    let persons = match format {
+        Format::Json => {
+            serde_json::from_reader::<_, Vec<Person>>(input).map_err(|e| e.to_string())?
+        }
+        Format::Csv => {
+            let mut reader = csv::Reader::from_reader(input);
+            let mut persons = Vec::new();
+            for row in reader.deserialize::<CsvRow>() {
+                let row = row.map_err(|e| e.to_string())?;
+                persons.push(Person {
+                    id: None,
+                    nom: row.nom,
+                    prenom: row.prenom,
+                });
+            }
+            persons
+        }
+        Format::Table => return Err("cannot import the table format".to_owned()),
+    };
+    Ok(persons
+        .into_iter()
+        .map(|p| Person { id: None, ..p })
+        .collect())
+}
@@ -0,0 +1,35 @@
+// server/build.rs
+
+// le hash git et l'heure de la compilation, pour /version
+// sans dépôt git (archive, image docker), le hash vaut "unknown"
+// SOURCE_DATE_EPOCH fixe l'heure pour les compilations reproductibles
+
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn main() {
+    let git_hash = Command::new("git")
+        .args(&["rev-parse", "--short=12", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_owned())
+        .unwrap_or_else(|| "unknown".to_owned());
+
+    let build_time = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse::<u64>().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0)
+        });
+
+    println!("cargo:rustc-env=SEED_GIT_HASH={}", git_hash);
+    println!("cargo:rustc-env=SEED_BUILD_TIME={}", build_time);
+    // recompilé à chaque commit ou changement de branche
+    println!("cargo:rerun-if-changed=../.git/HEAD");
+    println!("cargo:rerun-if-changed=../.git/index");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+}
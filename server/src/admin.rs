@@ -0,0 +1,81 @@
+// server/src/admin.rs
+
+use actix_web::{HttpRequest, HttpResponse};
+use sha2::{Digest, Sha256};
+
+use crate::auth::Principal;
+use crate::config::Config;
+use shared::{ErrorEnvelope, Scope};
+
+///
+/// vérifie que la requête vient d'un administrateur :
+/// une clé d'API avec le droit admin, ou le jeton X-Admin-Token
+/// renvoie la réponse d'erreur à envoyer sinon
+///
+pub fn check_admin(req: &HttpRequest, config: &Config) -> Result<(), HttpResponse> {
+    // le middleware Authentication a déjà vérifié la clé et son droit
+    if let Some(principal) = Principal::from_request(req) {
+        if principal.allows(Scope::Admin) {
+            return Ok(());
+        }
+    }
+    let expected = match &config.admin_token {
+        Some(token) => token,
+        None => {
+            return Err(HttpResponse::Forbidden()
+                .json(ErrorEnvelope::new("forbidden", "administration disabled")))
+        }
+    };
+    let given = req
+        .headers()
+        .get("X-Admin-Token")
+        .and_then(|value| value.to_str().ok());
+    match given {
+        Some(token) if same_token(token, expected) => Ok(()),
+        Some(_) => {
+            Err(HttpResponse::Forbidden()
+                .json(ErrorEnvelope::new("forbidden", "invalid admin token")))
+        }
+        None => Err(HttpResponse::Unauthorized()
+            .json(ErrorEnvelope::new("unauthorized", "missing admin token"))),
+    }
+}
+
+///
+/// comme check_admin, pour ce qui n'appartient à aucun locataire : clés d'API et webhooks
+/// l'administrateur d'un locataire est refusé
+///
+pub fn check_global_admin(req: &HttpRequest, config: &Config) -> Result<(), HttpResponse> {
+    check_admin(req, config)?;
+    match Principal::from_request(req) {
+        Some(principal) if principal.tenant.is_some() => {
+            Err(HttpResponse::Forbidden().json(ErrorEnvelope::new(
+                "forbidden",
+                "a tenant administrator cannot manage global resources",
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
+// compare les empreintes SHA-256 sans s'arrêter à la première différence :
+// elles ont la même longueur, le temps ne dépend pas du jeton donné
+fn same_token(given: &str, expected: &str) -> bool {
+    let expected = Sha256::digest(expected.as_bytes());
+    Sha256::digest(given.as_bytes())
+        .iter()
+        .zip(expected.iter())
+        .fold(0, |diff, (a, b)| diff | (a ^ b))
+        == 0
+}
+
+///
+/// qui fait la demande d'administration, pour les journaux :
+/// le sujet de la clé ou de l'utilisateur admin, sinon admin-token
+///
+pub fn admin_actor(req: &HttpRequest) -> String {
+    match Principal::from_request(req) {
+        Some(principal) if principal.allows(Scope::Admin) => principal.subject,
+        _ => "admin-token".to_owned(),
+    }
+}
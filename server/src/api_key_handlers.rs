@@ -0,0 +1,79 @@
+// src/api_key_handlers.rs
+
+use actix_web::{web, HttpRequest, HttpResponse, Responder, ResponseError};
+
+use crate::admin::check_global_admin;
+use crate::api_keys::{self, NewApiKey, SharedKeyStore};
+use crate::auth::Principal;
+use crate::config::Config;
+use shared::ErrorEnvelope;
+
+fn key_not_found() -> HttpResponse {
+    HttpResponse::NotFound().json(ErrorEnvelope::new("not_found", "API key not found"))
+}
+
+pub async fn list_keys_hdl(
+    req: HttpRequest,
+    config: web::Data<Config>,
+    keys: web::Data<SharedKeyStore>,
+) -> impl Responder {
+    if let Err(resp) = check_global_admin(&req, &config) {
+        return resp;
+    }
+    match keys.list() {
+        Ok(list) => HttpResponse::Ok().json(list),
+        Err(e) => e.error_response(),
+    }
+}
+
+///
+/// crée une clé ; la réponse est le seul moment où la clé complète est visible
+/// la clé n'a pas plus de droits que celui qui la crée
+///
+pub async fn create_key_hdl(
+    req: HttpRequest,
+    config: web::Data<Config>,
+    keys: web::Data<SharedKeyStore>,
+    new_key: web::Json<NewApiKey>,
+) -> impl Responder {
+    if let Err(resp) = check_global_admin(&req, &config) {
+        return resp;
+    }
+    let issuer = Principal::from_request(&req);
+    match api_keys::create_key(&**keys, new_key.into_inner(), issuer.as_ref()) {
+        Ok(issued) => HttpResponse::Ok().json(issued),
+        Err(e) => e.error_response(),
+    }
+}
+
+pub async fn revoke_key_hdl(
+    req: HttpRequest,
+    config: web::Data<Config>,
+    keys: web::Data<SharedKeyStore>,
+    id: web::Path<String>,
+) -> impl Responder {
+    if let Err(resp) = check_global_admin(&req, &config) {
+        return resp;
+    }
+    match api_keys::revoke_key(&**keys, &id.into_inner()) {
+        Ok(Some(key)) => HttpResponse::Ok().json(key),
+        Ok(None) => key_not_found(),
+        Err(e) => e.error_response(),
+    }
+}
+
+pub async fn rotate_key_hdl(
+    req: HttpRequest,
+    config: web::Data<Config>,
+    keys: web::Data<SharedKeyStore>,
+    id: web::Path<String>,
+) -> impl Responder {
+    if let Err(resp) = check_global_admin(&req, &config) {
+        return resp;
+    }
+    match api_keys::rotate_key(&**keys, &id.into_inner()) {
+        Ok(Some(issued)) => HttpResponse::Ok().json(issued),
+        Ok(None) => key_not_found(),
+        Err(e) => e.error_response(),
+    }
+}
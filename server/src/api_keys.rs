@@ -0,0 +1,422 @@
+// server/src/api_keys.rs
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use bson::{doc, from_bson, Bson, Document};
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+
+use crate::auth::Principal;
+use crate::db_mongo;
+use crate::db_postgres::PostgresApiKeys;
+use crate::db_sqlite::{self, SqliteApiKeys};
+use crate::errors::MyError;
+use crate::store::{self, Backend};
+use crate::tenancy::validate_tenant_id;
+pub use shared::{ApiKey, IssuedApiKey, NewApiKey, Scope};
+
+///
+/// le début de chaque clé : seed_<prefix>_<secret>
+/// prefix (8 caractères) identifie la clé, le secret n'est gardé que haché
+///
+pub const KEY_START: &str = "seed_";
+
+const PREFIX_LEN: usize = 8;
+
+// last_used_at n'est pas réécrit à chaque requête
+const TOUCH_INTERVAL_MS: i64 = 60 * 1000;
+
+///
+/// là où les clés sont gardées : le stockage des personnes (SEED_STORAGE)
+/// les clés valent pour toute l'application, elles ne dépendent pas du locataire
+///
+pub trait KeyStore: Send + Sync {
+    fn insert(&self, key: &ApiKey) -> Result<(), MyError>;
+    fn list(&self) -> Result<Vec<ApiKey>, MyError>;
+    fn get(&self, id: &str) -> Result<Option<ApiKey>, MyError>;
+    fn find_by_prefix(&self, prefix: &str) -> Result<Option<ApiKey>, MyError>;
+    // renvoie false si la clé n'existe pas
+    fn update(&self, key: &ApiKey) -> Result<bool, MyError>;
+    fn touch(&self, id: &str, at: i64) -> Result<(), MyError>;
+}
+
+///
+/// le stockage partagé par le middleware et les routes (web::Data<SharedKeyStore>)
+///
+pub type SharedKeyStore = Arc<dyn KeyStore>;
+
+///
+/// le stockage des clés avec le backend configuré
+///
+pub fn open_key_store() -> Result<Box<dyn KeyStore>, MyError> {
+    Ok(match store::backend() {
+        Backend::Mongo => Box::new(MongoApiKeys),
+        Backend::Sqlite => Box::new(SqliteApiKeys::new(db_sqlite::path())),
+        Backend::Postgres => Box::new(PostgresApiKeys),
+    })
+}
+
+// "read,write" dans SQLite
+pub(crate) fn join_scopes(scopes: &[Scope]) -> String {
+    scopes
+        .iter()
+        .map(|scope| scope.name())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+pub(crate) fn parse_scopes(text: &str) -> Vec<Scope> {
+    text.split(',')
+        .filter_map(|scope| scope.trim().parse().ok())
+        .collect()
+}
+
+///
+/// la collection ApiKeys de MongoDB
+///
+pub struct MongoApiKeys;
+
+impl MongoApiKeys {
+    fn to_document(key: &ApiKey) -> Result<Document, MyError> {
+        let mut document = match bson::to_bson(key)? {
+            Bson::Document(document) => document,
+            _ => Document::new(),
+        };
+        // l'id de la clé sert d'_id ; hash n'est pas sérialisé par serde
+        document.remove("id");
+        document.insert("_id", key.id.clone());
+        document.insert("hash", key.hash.clone());
+        Ok(document)
+    }
+
+    fn find_one(&self, filter: Document) -> Result<Option<ApiKey>, MyError> {
+        let coll = db_mongo::get_named_collection("ApiKeys")?;
+        match coll.find_one(Some(filter), None)? {
+            Some(document) => Ok(Some(from_bson(Bson::Document(document))?)),
+            None => Ok(None),
+        }
+    }
+}
+
+impl KeyStore for MongoApiKeys {
+    fn insert(&self, key: &ApiKey) -> Result<(), MyError> {
+        let coll = db_mongo::get_named_collection("ApiKeys")?;
+        coll.insert_one(Self::to_document(key)?, None)?;
+        Ok(())
+    }
+
+    fn list(&self) -> Result<Vec<ApiKey>, MyError> {
+        let cursor = db_mongo::get_named_collection("ApiKeys")?.find(None, None)?;
+        let res: Result<Vec<_>, _> = cursor
+            .map(|row| row.and_then(|item| Ok(from_bson::<ApiKey>(Bson::Document(item))?)))
+            .collect();
+        Ok(res?)
+    }
+
+    fn get(&self, id: &str) -> Result<Option<ApiKey>, MyError> {
+        self.find_one(doc! {"_id": id})
+    }
+
+    fn find_by_prefix(&self, prefix: &str) -> Result<Option<ApiKey>, MyError> {
+        self.find_one(doc! {"prefix": prefix})
+    }
+
+    fn update(&self, key: &ApiKey) -> Result<bool, MyError> {
+        let coll = db_mongo::get_named_collection("ApiKeys")?;
+        let result =
+            coll.replace_one(doc! {"_id": key.id.clone()}, Self::to_document(key)?, None)?;
+        Ok(result.matched_count > 0)
+    }
+
+    fn touch(&self, id: &str, at: i64) -> Result<(), MyError> {
+        let coll = db_mongo::get_named_collection("ApiKeys")?;
+        coll.update_one(doc! {"_id": id}, doc! {"$set": {"last_used_at": at}}, None)?;
+        Ok(())
+    }
+}
+
+impl KeyStore for SqliteApiKeys {
+    fn insert(&self, key: &ApiKey) -> Result<(), MyError> {
+        SqliteApiKeys::insert(self, key)
+    }
+
+    fn list(&self) -> Result<Vec<ApiKey>, MyError> {
+        SqliteApiKeys::list(self)
+    }
+
+    fn get(&self, id: &str) -> Result<Option<ApiKey>, MyError> {
+        SqliteApiKeys::get(self, id)
+    }
+
+    fn find_by_prefix(&self, prefix: &str) -> Result<Option<ApiKey>, MyError> {
+        SqliteApiKeys::find_by_prefix(self, prefix)
+    }
+
+    fn update(&self, key: &ApiKey) -> Result<bool, MyError> {
+        SqliteApiKeys::update(self, key)
+    }
+
+    fn touch(&self, id: &str, at: i64) -> Result<(), MyError> {
+        SqliteApiKeys::touch(self, id, at)
+    }
+}
+
+impl KeyStore for PostgresApiKeys {
+    fn insert(&self, key: &ApiKey) -> Result<(), MyError> {
+        PostgresApiKeys::insert(self, key)
+    }
+
+    fn list(&self) -> Result<Vec<ApiKey>, MyError> {
+        PostgresApiKeys::list(self)
+    }
+
+    fn get(&self, id: &str) -> Result<Option<ApiKey>, MyError> {
+        PostgresApiKeys::get(self, id)
+    }
+
+    fn find_by_prefix(&self, prefix: &str) -> Result<Option<ApiKey>, MyError> {
+        PostgresApiKeys::find_by_prefix(self, prefix)
+    }
+
+    fn update(&self, key: &ApiKey) -> Result<bool, MyError> {
+        PostgresApiKeys::update(self, key)
+    }
+
+    fn touch(&self, id: &str, at: i64) -> Result<(), MyError> {
+        PostgresApiKeys::touch(self, id, at)
+    }
+}
+
+///
+/// les clés en mémoire (tests)
+///
+#[derive(Default)]
+pub struct MemoryKeyStore {
+    keys: Mutex<BTreeMap<String, ApiKey>>,
+}
+
+impl KeyStore for MemoryKeyStore {
+    fn insert(&self, key: &ApiKey) -> Result<(), MyError> {
+        self.keys
+            .lock()
+            .unwrap()
+            .insert(key.id.clone(), key.clone());
+        Ok(())
+    }
+
+    fn list(&self) -> Result<Vec<ApiKey>, MyError> {
+        Ok(self.keys.lock().unwrap().values().cloned().collect())
+    }
+
+    fn get(&self, id: &str) -> Result<Option<ApiKey>, MyError> {
+        Ok(self.keys.lock().unwrap().get(id).cloned())
+    }
+
+    fn find_by_prefix(&self, prefix: &str) -> Result<Option<ApiKey>, MyError> {
+        Ok(self
+            .keys
+            .lock()
+            .unwrap()
+            .values()
+            .find(|key| key.prefix == prefix)
+            .cloned())
+    }
+
+    fn update(&self, key: &ApiKey) -> Result<bool, MyError> {
+        match self.keys.lock().unwrap().get_mut(&key.id) {
+            Some(stored) => {
+                *stored = key.clone();
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn touch(&self, id: &str, at: i64) -> Result<(), MyError> {
+        if let Some(key) = self.keys.lock().unwrap().get_mut(id) {
+            key.last_used_at = Some(at);
+        }
+        Ok(())
+    }
+}
+
+///
+/// l'empreinte SHA-256 d'une clé complète
+///
+pub fn hash_secret(secret: &str) -> String {
+    hex::encode(Sha256::digest(secret.as_bytes()))
+}
+
+// compare sans s'arrêter à la première différence
+fn same_hash(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0u8, |diff, (x, y)| diff | (x ^ y))
+            == 0
+}
+
+// un nouveau préfixe, libre, et la clé complète
+fn generate(store: &dyn KeyStore) -> Result<(String, String), MyError> {
+    loop {
+        let prefix = uuid::Uuid::new_v4().to_simple().to_string()[..PREFIX_LEN].to_owned();
+        if store.find_by_prefix(&prefix)?.is_some() {
+            continue;
+        }
+        let secret = format!(
+            "{}{}_{}{}",
+            KEY_START,
+            prefix,
+            uuid::Uuid::new_v4().to_simple(),
+            uuid::Uuid::new_v4().to_simple()
+        );
+        return Ok((prefix, secret));
+    }
+}
+
+///
+/// le préfixe d'une clé complète, si elle a la bonne forme
+///
+pub fn key_prefix(secret: &str) -> Option<&str> {
+    if !secret.starts_with(KEY_START) {
+        return None;
+    }
+    let rest = &secret[KEY_START.len()..];
+    let prefix = rest.get(..PREFIX_LEN)?;
+    let tail = &rest[PREFIX_LEN..];
+    if tail.len() > 1 && tail.starts_with('_') {
+        Some(prefix)
+    } else {
+        None
+    }
+}
+
+///
+/// crée une clé ; le secret n'est renvoyé qu'ici
+/// issuer : qui la demande par l'API (None pour le jeton admin ou seedctl en direct) ;
+/// la clé ne peut avoir ni un droit qu'il n'a pas, ni un autre locataire que le sien
+///
+pub fn create_key(
+    store: &dyn KeyStore,
+    new_key: NewApiKey,
+    issuer: Option<&Principal>,
+) -> Result<IssuedApiKey, MyError> {
+    let name = new_key.name.trim().to_owned();
+    if name.is_empty() {
+        return Err(MyError::InvalidApiKey("the key needs a name".to_owned()));
+    }
+    if new_key.scopes.is_empty() {
+        return Err(MyError::InvalidApiKey(
+            "the key needs at least one scope".to_owned(),
+        ));
+    }
+    let tenant = match new_key.tenant {
+        Some(tenant) => {
+            Some(validate_tenant_id(&tenant).map_err(|e| MyError::InvalidApiKey(e.to_string()))?)
+        }
+        None => None,
+    };
+    if let Some(issuer) = issuer {
+        if let Some(scope) = new_key.scopes.iter().find(|scope| !issuer.allows(**scope)) {
+            return Err(MyError::Forbidden(format!(
+                "the {} scope is broader than the caller's",
+                scope.name()
+            )));
+        }
+        if issuer.tenant.is_some() && tenant != issuer.tenant {
+            return Err(MyError::Forbidden(
+                "the key must belong to the caller's tenant".to_owned(),
+            ));
+        }
+    }
+    let now = Utc::now().timestamp_millis();
+    if new_key.expires_at.map_or(false, |at| at <= now) {
+        return Err(MyError::InvalidApiKey(
+            "the expiry date is in the past".to_owned(),
+        ));
+    }
+
+    let (prefix, secret) = generate(store)?;
+    let mut scopes = new_key.scopes;
+    scopes.sort();
+    scopes.dedup();
+    let key = ApiKey {
+        id: uuid::Uuid::new_v4().to_string(),
+        name,
+        prefix,
+        hash: hash_secret(&secret),
+        scopes,
+        tenant,
+        created_at: now,
+        expires_at: new_key.expires_at,
+        last_used_at: None,
+        revoked_at: None,
+    };
+    store.insert(&key)?;
+    Ok(IssuedApiKey { key, secret })
+}
+
+///
+/// révoque une clé ; elle reste dans la liste, mais n'est plus acceptée
+///
+pub fn revoke_key(store: &dyn KeyStore, id: &str) -> Result<Option<ApiKey>, MyError> {
+    let mut key = match store.get(id)? {
+        Some(key) => key,
+        None => return Ok(None),
+    };
+    if key.revoked_at.is_none() {
+        key.revoked_at = Some(Utc::now().timestamp_millis());
+        store.update(&key)?;
+    }
+    Ok(Some(key))
+}
+
+///
+/// renouvelle le secret d'une clé : mêmes droits, nouveau préfixe
+/// l'ancien secret est refusé dès maintenant
+///
+pub fn rotate_key(store: &dyn KeyStore, id: &str) -> Result<Option<IssuedApiKey>, MyError> {
+    let mut key = match store.get(id)? {
+        Some(key) => key,
+        None => return Ok(None),
+    };
+    if key.revoked_at.is_some() {
+        return Err(MyError::InvalidApiKey(
+            "a revoked key cannot be rotated".to_owned(),
+        ));
+    }
+    let (prefix, secret) = generate(store)?;
+    key.prefix = prefix;
+    key.hash = hash_secret(&secret);
+    store.update(&key)?;
+    Ok(Some(IssuedApiKey { key, secret }))
+}
+
+///
+/// la clé d'un en-tête Authorization: Bearer, si elle est valable
+/// refuse les clés inconnues, révoquées ou expirées ; met à jour last_used_at
+///
+pub fn authenticate(store: &dyn KeyStore, secret: &str) -> Result<ApiKey, MyError> {
+    let invalid = || MyError::Unauthorized("invalid API key".to_owned());
+    let prefix = key_prefix(secret).ok_or_else(invalid)?;
+    let mut key = store.find_by_prefix(prefix)?.ok_or_else(invalid)?;
+    if !same_hash(&key.hash, &hash_secret(secret)) {
+        return Err(invalid());
+    }
+    if key.revoked_at.is_some() {
+        return Err(MyError::Unauthorized("revoked API key".to_owned()));
+    }
+    let now = Utc::now().timestamp_millis();
+    if key.expires_at.map_or(false, |at| at <= now) {
+        return Err(MyError::Unauthorized("expired API key".to_owned()));
+    }
+    if key
+        .last_used_at
+        .map_or(true, |at| now - at >= TOUCH_INTERVAL_MS)
+    {
+        store.touch(&key.id, now)?;
+        key.last_used_at = Some(now);
+    }
+    Ok(key)
+}
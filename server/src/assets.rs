@@ -0,0 +1,297 @@
+// server/src/assets.rs
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use actix_web::http::{header, Method};
+use actix_web::{web, HttpRequest, HttpResponse};
+use once_cell::sync::Lazy;
+use sha2::{Digest, Sha256};
+
+use crate::config::Config;
+use shared::ErrorEnvelope;
+
+///
+/// avec la feature embed-client, index.html et client/pkg sont dans le binaire
+/// il faut compiler le client (cargo make build_client_release) avant le serveur
+///
+#[cfg(feature = "embed-client")]
+#[derive(rust_embed::RustEmbed)]
+#[folder = "../client/pkg/"]
+struct EmbeddedPkg;
+
+#[cfg(feature = "embed-client")]
+const EMBEDDED_INDEX: &[u8] = include_bytes!("../../client/index.html");
+
+// un an : les fichiers demandés avec le bon ?v= ne changent jamais
+const IMMUTABLE: &str = "public, max-age=31536000, immutable";
+// le navigateur revalide à chaque fois, avec If-None-Match
+const REVALIDATE: &str = "no-cache";
+
+// les empreintes déjà calculées, par fichier (date de modification, taille)
+type Fingerprint = (Option<SystemTime>, u64);
+static HASHES: Lazy<Mutex<HashMap<(PathBuf, String), (Fingerprint, String)>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+///
+/// le type MIME d'après l'extension ; application/wasm pour le module du client
+///
+pub fn content_type(path: &str) -> &'static str {
+    match path.rsplit('.').next().unwrap_or("") {
+        "html" => "text/html; charset=utf-8",
+        "js" | "mjs" => "application/javascript",
+        "wasm" => "application/wasm",
+        "css" => "text/css",
+        "json" | "map" => "application/json",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "ico" => "image/x-icon",
+        "txt" => "text/plain; charset=utf-8",
+        _ => "application/octet-stream",
+    }
+}
+
+// un chemin relatif sans .. ni racine : on ne sort pas du dossier du client
+fn is_safe(rel: &str) -> bool {
+    !rel.is_empty()
+        && !rel.contains('\\')
+        && Path::new(rel)
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+}
+
+///
+/// les fichiers du client : index.html et pkg/, sur le disque ou dans le binaire
+///
+pub struct ClientAssets {
+    dir: PathBuf,
+}
+
+impl ClientAssets {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    ///
+    /// le dossier de la configuration (SEED_CLIENT_DIR), client/ sans configuration
+    ///
+    pub fn from_request(req: &HttpRequest) -> Self {
+        match req.app_data::<web::Data<Config>>() {
+            Some(config) => Self::new(&config.client_dir),
+            None => Self::new(Config::default().client_dir),
+        }
+    }
+
+    #[cfg(not(feature = "embed-client"))]
+    fn read(&self, rel: &str) -> Option<Cow<'static, [u8]>> {
+        std::fs::read(self.dir.join(rel)).ok().map(Cow::Owned)
+    }
+
+    #[cfg(feature = "embed-client")]
+    fn read(&self, rel: &str) -> Option<Cow<'static, [u8]>> {
+        if rel == "index.html" {
+            Some(Cow::Borrowed(EMBEDDED_INDEX))
+        } else if rel.starts_with("pkg/") {
+            EmbeddedPkg::get(&rel["pkg/".len()..])
+        } else {
+            None
+        }
+    }
+
+    // sur le disque, la date et la taille ; dans le binaire, le contenu ne change pas
+    #[cfg(not(feature = "embed-client"))]
+    fn fingerprint(&self, rel: &str) -> Option<Fingerprint> {
+        let metadata = std::fs::metadata(self.dir.join(rel)).ok()?;
+        Some((metadata.modified().ok(), metadata.len()))
+    }
+
+    #[cfg(feature = "embed-client")]
+    fn fingerprint(&self, rel: &str) -> Option<Fingerprint> {
+        self.read(rel).map(|bytes| (None, bytes.len() as u64))
+    }
+
+    ///
+    /// l'empreinte SHA-256 d'un fichier (16 caractères)
+    /// elle n'est recalculée que si le fichier a changé
+    ///
+    pub fn hash(&self, rel: &str) -> Option<String> {
+        if !is_safe(rel) {
+            return None;
+        }
+        let fingerprint = self.fingerprint(rel)?;
+        let key = (self.dir.clone(), rel.to_owned());
+        if let Some((known, hash)) = HASHES.lock().unwrap().get(&key) {
+            if *known == fingerprint {
+                return Some(hash.clone());
+            }
+        }
+        let bytes = self.read(rel)?;
+        let hash = hex::encode(Sha256::digest(&bytes))[..16].to_owned();
+        HASHES
+            .lock()
+            .unwrap()
+            .insert(key, (fingerprint, hash.clone()));
+        Some(hash)
+    }
+
+    ///
+    /// le contenu d'un fichier et son empreinte
+    ///
+    pub fn load(&self, rel: &str) -> Option<(Cow<'static, [u8]>, String)> {
+        let hash = self.hash(rel)?;
+        Some((self.read(rel)?, hash))
+    }
+
+    ///
+    /// index.html, avec ?v=<empreinte> ajouté aux liens vers /pkg/
+    /// les fichiers du client peuvent alors être gardés en cache sans limite
+    ///
+    pub fn index(&self) -> Option<(Vec<u8>, String)> {
+        let (bytes, _) = self.load("index.html")?;
+        let html = String::from_utf8_lossy(&bytes);
+        let mut out = String::with_capacity(html.len() + 64);
+        let mut rest: &str = &html;
+        while let Some(start) = rest.find("/pkg/") {
+            let after = &rest[start + 1..];
+            let end = after
+                .find(|c| c == '\'' || c == '"' || c == '?' || c == ' ')
+                .unwrap_or(after.len());
+            let rel = &after[..end];
+            out.push_str(&rest[..start + 1 + end]);
+            if let Some(hash) = self.hash(rel) {
+                out.push_str("?v=");
+                out.push_str(&hash);
+            }
+            rest = &after[end..];
+        }
+        out.push_str(rest);
+        let hash = hex::encode(Sha256::digest(out.as_bytes()))[..16].to_owned();
+        Some((out.into_bytes(), hash))
+    }
+}
+
+// l'encodage accepté par le client, de préférence brotli
+fn accepted_encodings(req: &HttpRequest) -> Vec<(&'static str, &'static str)> {
+    let accept = req
+        .headers()
+        .get(header::ACCEPT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("");
+    let mut encodings = Vec::new();
+    if accept.contains("br") {
+        encodings.push(("br", ".br"));
+    }
+    if accept.contains("gzip") {
+        encodings.push(("gzip", ".gz"));
+    }
+    encodings
+}
+
+fn respond(
+    req: &HttpRequest,
+    body: Cow<'static, [u8]>,
+    content_type: &str,
+    hash: &str,
+    cache_control: &str,
+    encoding: Option<&str>,
+) -> HttpResponse {
+    let etag = format!("\"{}\"", hash);
+    let not_modified = req
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .map_or(false, |given| given.split(',').any(|t| t.trim() == etag));
+
+    let mut builder = if not_modified {
+        HttpResponse::NotModified()
+    } else {
+        HttpResponse::Ok()
+    };
+    builder
+        .header(header::ETAG, etag.as_str())
+        .header(header::CACHE_CONTROL, cache_control)
+        .header(header::VARY, "Accept-Encoding");
+    if not_modified {
+        return builder.finish();
+    }
+    if let Some(encoding) = encoding {
+        builder.header(header::CONTENT_ENCODING, encoding);
+    }
+    builder.content_type(content_type).body(body.into_owned())
+}
+
+///
+/// la page du client, si elle existe
+///
+pub fn client_index(req: &HttpRequest) -> Option<HttpResponse> {
+    let (html, hash) = ClientAssets::from_request(req).index()?;
+    Some(respond(
+        req,
+        Cow::Owned(html),
+        "text/html; charset=utf-8",
+        &hash,
+        REVALIDATE,
+        None,
+    ))
+}
+
+fn not_found(req: &HttpRequest) -> HttpResponse {
+    HttpResponse::NotFound().json(ErrorEnvelope::new(
+        "not_found",
+        format!("no route for {} {}", req.method(), req.path()),
+    ))
+}
+
+///
+/// GET /pkg/... : le module wasm et le JavaScript du client
+/// une version .br ou .gz à côté du fichier est envoyée si le navigateur l'accepte
+///
+pub async fn pkg_hdl(req: HttpRequest) -> HttpResponse {
+    let rel = format!("pkg/{}", req.match_info().query("file"));
+    let assets = ClientAssets::from_request(&req);
+    let (body, hash) = match assets.load(&rel) {
+        Some(asset) => asset,
+        None => return not_found(&req),
+    };
+
+    // ?v= est l'empreinte mise par index() : le contenu ne changera plus
+    let versioned = req.query_string() == format!("v={}", hash);
+    let cache_control = if versioned { IMMUTABLE } else { REVALIDATE };
+
+    for (encoding, suffix) in accepted_encodings(&req) {
+        if let Some((body, compressed_hash)) = assets.load(&format!("{}{}", rel, suffix)) {
+            return respond(
+                &req,
+                body,
+                content_type(&rel),
+                &compressed_hash,
+                cache_control,
+                Some(encoding),
+            );
+        }
+    }
+    respond(&req, body, content_type(&rel), &hash, cache_control, None)
+}
+
+///
+/// les chemins sans route : index.html pour la navigation du client (SPA),
+/// une erreur 404 pour le reste (API, fichiers)
+///
+pub async fn spa_fallback_hdl(req: HttpRequest) -> HttpResponse {
+    let navigation = (req.method() == Method::GET || req.method() == Method::HEAD)
+        && req
+            .headers()
+            .get(header::ACCEPT)
+            .and_then(|v| v.to_str().ok())
+            .map_or(false, |accept| accept.contains("text/html"))
+        && !req.path().rsplit('/').next().unwrap_or("").contains('.');
+    if navigation {
+        if let Some(response) = client_index(&req) {
+            return response;
+        }
+    }
+    not_found(&req)
+}
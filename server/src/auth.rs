@@ -0,0 +1,218 @@
+// server/src/auth.rs
+
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{HeaderMap, AUTHORIZATION};
+use actix_web::http::Method;
+use actix_web::{Error, HttpMessage, HttpRequest, ResponseError};
+use futures::future::{ok, Either, Ready};
+use serde::Serialize;
+
+use crate::api_keys::{self, KeyStore};
+use crate::errors::MyError;
+use crate::sessions::{SharedSessions, SESSION_COOKIE};
+use shared::{ApiKey, Scope, User};
+
+///
+/// qui fait la requête : une clé d'API ou un utilisateur connecté
+/// le middleware Authentication le met dans les extensions de la requête
+///
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Principal {
+    // key:<id> pour une clé d'API, user:<id> pour un utilisateur
+    pub subject: String,
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    // le locataire de la clé ou de l'utilisateur ; il passe avant l'en-tête du locataire
+    pub tenant: Option<String>,
+}
+
+impl Principal {
+    pub fn allows(&self, scope: Scope) -> bool {
+        self.scopes.iter().any(|s| *s >= scope)
+    }
+
+    ///
+    /// le Principal authentifié de la requête, s'il y en a un
+    ///
+    pub fn from_request(req: &HttpRequest) -> Option<Principal> {
+        req.extensions().get::<Principal>().cloned()
+    }
+}
+
+impl From<&ApiKey> for Principal {
+    fn from(key: &ApiKey) -> Self {
+        Self {
+            subject: format!("key:{}", key.id),
+            name: key.name.clone(),
+            scopes: key.scopes.clone(),
+            tenant: key.tenant.clone(),
+        }
+    }
+}
+
+impl From<&User> for Principal {
+    fn from(user: &User) -> Self {
+        Self {
+            subject: format!("user:{}", user.id),
+            name: user.name.clone(),
+            scopes: user.scopes.clone(),
+            tenant: user.tenant.clone(),
+        }
+    }
+}
+
+///
+/// le droit demandé par une route ; None pour les routes publiques :
+/// sondes, client, documentation et /metrics
+///
+pub fn required_scope(method: &Method, path: &str) -> Option<Scope> {
+    let first = path.trim_start_matches('/').split('/').next().unwrap_or("");
+    match first {
+        "admin" | "webhooks" => Some(Scope::Admin),
+        // l'export et l'effacement RGPD sont réservés aux administrateurs
+        "persons" if path.ends_with("/gdpr-export") || path.ends_with("/erase") => {
+            Some(Scope::Admin)
+        }
+        // les mutations GraphQL vérifient elles-mêmes le droit write
+        "graphql" | "graphiql" => Some(Scope::Read),
+        "string" | "json" | "json_list" | "persons" | "ws" | "events" => {
+            if method == Method::GET || method == Method::HEAD {
+                Some(Scope::Read)
+            } else {
+                Some(Scope::Write)
+            }
+        }
+        _ => None,
+    }
+}
+
+///
+/// la clé de l'en-tête Authorization: Bearer
+///
+pub fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| value.starts_with("Bearer "))
+        .map(|value| value["Bearer ".len()..].trim())
+}
+
+///
+/// le middleware d'authentification
+/// une clé présentée doit être valable et avoir le droit demandé par la route (401, 403)
+/// sans clé, le cookie de session est essayé ; une session expirée est ignorée
+/// sans l'un ni l'autre, la requête passe, sauf sur les routes protégées avec SEED_AUTH_REQUIRED
+/// la clé ou l'utilisateur est noté dans le champ user du span de la requête
+///
+pub struct Authentication {
+    keys: Arc<dyn KeyStore>,
+    sessions: Option<SharedSessions>,
+    required: bool,
+}
+
+impl Authentication {
+    pub fn new(keys: Arc<dyn KeyStore>, required: bool) -> Self {
+        Self {
+            keys,
+            sessions: None,
+            required,
+        }
+    }
+
+    ///
+    /// accepte aussi les sessions ouvertes par /auth/callback
+    ///
+    pub fn with_sessions(mut self, sessions: SharedSessions) -> Self {
+        self.sessions = Some(sessions);
+        self
+    }
+}
+
+impl<S, B> Transform<S> for Authentication
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = AuthenticationMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(AuthenticationMiddleware {
+            service,
+            keys: self.keys.clone(),
+            sessions: self.sessions.clone(),
+            required: self.required,
+        })
+    }
+}
+
+pub struct AuthenticationMiddleware<S> {
+    service: S,
+    keys: Arc<dyn KeyStore>,
+    sessions: Option<SharedSessions>,
+    required: bool,
+}
+
+impl<S> AuthenticationMiddleware<S> {
+    fn principal(&self, req: &ServiceRequest) -> Result<Option<Principal>, MyError> {
+        let principal = match bearer_token(req.headers()) {
+            Some(secret) => Some(Principal::from(&api_keys::authenticate(
+                &*self.keys,
+                secret,
+            )?)),
+            None => match (&self.sessions, req.cookie(SESSION_COOKIE)) {
+                (Some(sessions), Some(cookie)) => sessions.get(cookie.value()),
+                _ => None,
+            },
+        };
+        match (required_scope(req.method(), req.path()), &principal) {
+            (Some(scope), Some(principal)) if !principal.allows(scope) => Err(MyError::Forbidden(
+                format!("the {} scope is needed", scope.name()),
+            )),
+            (Some(_), None) if self.required => Err(MyError::Unauthorized(
+                "missing API key or session".to_owned(),
+            )),
+            _ => Ok(principal),
+        }
+    }
+}
+
+impl<S, B> Service for AuthenticationMiddleware<S>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Either<S::Future, Ready<Result<Self::Response, Self::Error>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: ServiceRequest) -> Self::Future {
+        match self.principal(&req) {
+            Ok(Some(principal)) => {
+                tracing::Span::current().record("user", &principal.subject.as_str());
+                req.extensions_mut().insert(principal);
+            }
+            Ok(None) => {}
+            Err(e) => {
+                tracing::warn!(error = %e, "authentication refused");
+                let response = e.error_response().into_body();
+                return Either::Right(ok(req.into_response(response)));
+            }
+        }
+        Either::Left(self.service.call(req))
+    }
+}
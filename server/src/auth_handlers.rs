@@ -0,0 +1,172 @@
+// src/auth_handlers.rs
+
+use actix_web::cookie::{Cookie, SameSite};
+use actix_web::http::header;
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, ResponseError};
+use serde::Deserialize;
+
+use crate::auth::Principal;
+use crate::config::Config;
+use crate::errors::MyError;
+use crate::oidc::{state_hash, OidcClient, LOGIN_TIMEOUT};
+use crate::sessions::{session_cookie, SharedSessions, SESSION_COOKIE};
+use crate::users::{self, SharedUserStore};
+use shared::ErrorEnvelope;
+
+///
+/// le cookie qui lie le retour du fournisseur au navigateur qui a commencé la connexion
+///
+pub const STATE_COOKIE: &str = "seed_oidc_state";
+
+#[derive(Deserialize)]
+pub struct LoginQuery {
+    pub return_to: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct CallbackQuery {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    // le fournisseur renvoie error au lieu de code quand il refuse
+    pub error: Option<String>,
+    pub error_description: Option<String>,
+}
+
+fn oidc_disabled() -> HttpResponse {
+    HttpResponse::NotFound().json(ErrorEnvelope::new(
+        "oidc_disabled",
+        "single sign-on is not configured",
+    ))
+}
+
+// le cookie n'est Secure que si le serveur est en HTTPS
+fn secure_cookies(config: &Config) -> bool {
+    config.tls_files().is_some() || config.dev_tls
+}
+
+// l'empreinte du state, le temps de revenir du fournisseur
+fn state_cookie(state: &str, secure: bool) -> Cookie<'static> {
+    Cookie::build(STATE_COOKIE, state_hash(state))
+        .path("/auth")
+        .http_only(true)
+        .secure(secure)
+        .same_site(SameSite::Lax)
+        .max_age(time::Duration::seconds(LOGIN_TIMEOUT.as_secs() as i64))
+        .finish()
+}
+
+// efface le cookie du state une fois la connexion faite
+fn clear_state_cookie() -> Cookie<'static> {
+    Cookie::build(STATE_COOKIE, "")
+        .path("/auth")
+        .max_age(time::Duration::zero())
+        .finish()
+}
+
+///
+/// commence la connexion : redirige vers le fournisseur d'identité
+///
+pub async fn login_hdl(
+    oidc: Option<web::Data<OidcClient>>,
+    config: web::Data<Config>,
+    query: web::Query<LoginQuery>,
+) -> HttpResponse {
+    let oidc = match oidc {
+        Some(oidc) => oidc,
+        None => return oidc_disabled(),
+    };
+    match oidc.authorize_url(query.return_to.as_deref()).await {
+        Ok((url, state)) => HttpResponse::Found()
+            .header(header::LOCATION, url)
+            .cookie(state_cookie(&state, secure_cookies(&config)))
+            .finish(),
+        Err(e) => e.error_response(),
+    }
+}
+
+///
+/// le retour du fournisseur : valide l'ID token, crée ou met à jour l'utilisateur,
+/// ouvre la session et renvoie le navigateur là où il allait
+/// le state doit correspondre au cookie posé par login_hdl dans ce navigateur
+///
+pub async fn callback_hdl(
+    req: HttpRequest,
+    oidc: Option<web::Data<OidcClient>>,
+    config: web::Data<Config>,
+    users: web::Data<SharedUserStore>,
+    sessions: web::Data<SharedSessions>,
+    query: web::Query<CallbackQuery>,
+) -> HttpResponse {
+    let oidc = match oidc {
+        Some(oidc) => oidc,
+        None => return oidc_disabled(),
+    };
+    let query = query.into_inner();
+    let (code, state) = match (query.code, query.state, query.error) {
+        (Some(code), Some(state), None) => (code, state),
+        (_, _, Some(error)) => {
+            let description = query.error_description.unwrap_or_default();
+            return MyError::Unauthorized(format!("{} {}", error, description).trim().to_owned())
+                .error_response();
+        }
+        _ => {
+            return MyError::Unauthorized("missing code or state".to_owned()).error_response();
+        }
+    };
+    let bound = req
+        .cookie(STATE_COOKIE)
+        .map_or(false, |cookie| cookie.value() == state_hash(&state));
+    if !bound {
+        tracing::warn!("single sign-on callback without the browser state cookie");
+        return MyError::Unauthorized("login not started from this browser".to_owned())
+            .error_response();
+    }
+
+    let (identity, return_to) = match oidc.callback(&code, &state).await {
+        Ok(result) => result,
+        Err(e) => {
+            tracing::warn!(error = %e, "single sign-on refused");
+            return e.error_response();
+        }
+    };
+    let scopes = oidc.roles(&identity);
+    if scopes.is_empty() {
+        tracing::warn!(subject = %identity.subject, "no application role for the user groups");
+        return MyError::Forbidden("no application role for your groups".to_owned())
+            .error_response();
+    }
+    let user = match users::provision(&**users, identity, scopes) {
+        Ok(user) => user,
+        Err(e) => return e.error_response(),
+    };
+    tracing::info!(user = %user.id, "user logged in");
+
+    let token = sessions.create(Principal::from(&user));
+    HttpResponse::Found()
+        .header(header::LOCATION, return_to)
+        .cookie(session_cookie(&token, secure_cookies(&config)))
+        .cookie(clear_state_cookie())
+        .finish()
+}
+
+///
+/// ferme la session et efface le cookie
+///
+pub async fn logout_hdl(req: HttpRequest, sessions: web::Data<SharedSessions>) -> HttpResponse {
+    let mut response = HttpResponse::NoContent().finish();
+    if let Some(cookie) = req.cookie(SESSION_COOKIE) {
+        sessions.remove(cookie.value());
+        response.del_cookie(SESSION_COOKIE);
+    }
+    response
+}
+
+///
+/// qui est connecté : l'utilisateur de la session ou la clé d'API
+///
+pub async fn me_hdl(req: HttpRequest) -> HttpResponse {
+    match Principal::from_request(&req) {
+        Some(principal) => HttpResponse::Ok().json(principal),
+        None => MyError::Unauthorized("not logged in".to_owned()).error_response(),
+    }
+}
@@ -0,0 +1,262 @@
+// server/src/backup.rs
+
+use std::collections::{BTreeMap, HashSet};
+use std::io::Read;
+
+use chrono::Utc;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::errors::MyError;
+use crate::store::PersonStore;
+use shared::Person;
+
+// version du format de l'archive, vérifiée à la restauration
+pub const BACKUP_FORMAT_VERSION: u32 = 1;
+
+const MANIFEST_FILE: &str = "manifest.json";
+const PERSONS_FILE: &str = "persons.json";
+const AUDIT_FILE: &str = "audit.json";
+// la taille décompressée maximale d'une archive, tous fichiers confondus
+pub const MAX_BACKUP_SIZE: u64 = 512 * 1024 * 1024;
+
+///
+/// un fichier de l'archive avec sa somme SHA-256
+///
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ManifestEntry {
+    pub name: String,
+    pub sha256: String,
+    pub size: u64,
+    pub count: usize,
+}
+
+///
+/// le manifeste de l'archive : ce qu'elle contient et comment le vérifier
+///
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Manifest {
+    pub format_version: u32,
+    pub app_version: String,
+    pub created_at: String,
+    pub files: Vec<ManifestEntry>,
+}
+
+///
+/// merge : ajoute les personnes de l'archive, remplace celles qui ont le même id
+/// replace : efface toutes les personnes puis restaure l'archive
+///
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RestoreMode {
+    Merge,
+    Replace,
+}
+
+impl std::str::FromStr for RestoreMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "merge" => Ok(RestoreMode::Merge),
+            "replace" => Ok(RestoreMode::Replace),
+            other => Err(format!(
+                "unknown restore mode '{}' (merge or replace)",
+                other
+            )),
+        }
+    }
+}
+
+///
+/// le contenu vérifié d'une archive
+///
+#[derive(Debug, Clone)]
+pub struct BackupContents {
+    pub manifest: Manifest,
+    pub persons: Vec<Person>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct RestoreReport {
+    pub mode: String,
+    pub restored: usize,
+    // les personnes effacées (droit à l'effacement) ne reviennent pas
+    pub skipped_erased: usize,
+    pub audit_entries: usize,
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+///
+/// crée l'archive tar.gz : manifest.json, persons.json et audit.json
+/// audit est le journal des événements (vide si pas de journal)
+///
+pub fn create_backup<A: Serialize>(
+    store: &dyn PersonStore,
+    audit: &[A],
+) -> Result<Vec<u8>, MyError> {
+    let persons = store.list()?;
+    let persons_json = serde_json::to_vec_pretty(&persons)?;
+    let audit_json = serde_json::to_vec_pretty(audit)?;
+
+    let manifest = Manifest {
+        format_version: BACKUP_FORMAT_VERSION,
+        app_version: env!("CARGO_PKG_VERSION").to_owned(),
+        created_at: Utc::now().to_rfc3339(),
+        files: vec![
+            ManifestEntry {
+                name: PERSONS_FILE.to_owned(),
+                sha256: sha256_hex(&persons_json),
+                size: persons_json.len() as u64,
+                count: persons.len(),
+            },
+            ManifestEntry {
+                name: AUDIT_FILE.to_owned(),
+                sha256: sha256_hex(&audit_json),
+                size: audit_json.len() as u64,
+                count: audit.len(),
+            },
+        ],
+    };
+    let manifest_json = serde_json::to_vec_pretty(&manifest)?;
+
+    let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    for (name, data) in &[
+        (MANIFEST_FILE, &manifest_json),
+        (PERSONS_FILE, &persons_json),
+        (AUDIT_FILE, &audit_json),
+    ] {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(Utc::now().timestamp() as u64);
+        header.set_cksum();
+        builder.append_data(&mut header, name, data.as_slice())?;
+    }
+    Ok(builder.into_inner()?.finish()?)
+}
+
+///
+/// lit l'archive et vérifie le manifeste et les sommes SHA-256
+/// au-delà de MAX_BACKUP_SIZE décompressés, l'archive est refusée
+/// rien n'est écrit dans la base ici
+///
+pub fn read_backup(archive: &[u8]) -> Result<BackupContents, MyError> {
+    let mut files: BTreeMap<String, Vec<u8>> = BTreeMap::new();
+    let mut total: u64 = 0;
+    let mut tar = tar::Archive::new(GzDecoder::new(archive));
+    for entry in tar.entries()? {
+        let entry = entry?;
+        let name = entry.path()?.to_string_lossy().into_owned();
+        let mut data = Vec::new();
+        // on lit un octet de plus que la place restante pour voir le dépassement
+        entry
+            .take(MAX_BACKUP_SIZE - total + 1)
+            .read_to_end(&mut data)?;
+        total += data.len() as u64;
+        if total > MAX_BACKUP_SIZE {
+            return Err(MyError::Backup(format!(
+                "the archive is larger than {} bytes once decompressed",
+                MAX_BACKUP_SIZE
+            )));
+        }
+        files.insert(name, data);
+    }
+
+    let manifest: Manifest = match files.get(MANIFEST_FILE) {
+        Some(data) => serde_json::from_slice(data)?,
+        None => return Err(MyError::Backup("manifest.json is missing".to_owned())),
+    };
+    if manifest.format_version != BACKUP_FORMAT_VERSION {
+        return Err(MyError::Backup(format!(
+            "unsupported format version {}",
+            manifest.format_version
+        )));
+    }
+
+    for entry in &manifest.files {
+        let data = files
+            .get(&entry.name)
+            .ok_or_else(|| MyError::Backup(format!("{} is missing", entry.name)))?;
+        if data.len() as u64 != entry.size || sha256_hex(data) != entry.sha256 {
+            return Err(MyError::Backup(format!(
+                "checksum mismatch for {}",
+                entry.name
+            )));
+        }
+    }
+
+    let persons: Vec<Person> = match files.get(PERSONS_FILE) {
+        Some(data) => serde_json::from_slice(data)?,
+        None => return Err(MyError::Backup("persons.json is missing".to_owned())),
+    };
+    let expected = manifest
+        .files
+        .iter()
+        .find(|entry| entry.name == PERSONS_FILE)
+        .map(|entry| entry.count);
+    if expected != Some(persons.len()) {
+        return Err(MyError::Backup(
+            "person count does not match the manifest".to_owned(),
+        ));
+    }
+
+    Ok(BackupContents { manifest, persons })
+}
+
+///
+/// vérifie l'archive puis restaure les personnes, sauf celles dont l'id est dans erased
+/// le journal d'audit est gardé dans l'archive mais n'est pas réécrit
+///
+pub fn restore_backup(
+    store: &dyn PersonStore,
+    archive: &[u8],
+    mode: RestoreMode,
+    erased: &HashSet<String>,
+) -> Result<RestoreReport, MyError> {
+    let contents = read_backup(archive)?;
+
+    let persons: Vec<Person> = contents
+        .persons
+        .iter()
+        .filter(|person| {
+            person
+                .id
+                .as_ref()
+                .map_or(true, |id| !erased.contains(id.as_str()))
+        })
+        .cloned()
+        .collect();
+    let restored = persons.len();
+    match mode {
+        // tout ou rien : un échec laisse les personnes d'avant
+        RestoreMode::Replace => store.replace_all(persons)?,
+        RestoreMode::Merge => {
+            for person in persons {
+                store.upsert(person)?;
+            }
+        }
+    }
+
+    let audit_entries = contents
+        .manifest
+        .files
+        .iter()
+        .find(|entry| entry.name == AUDIT_FILE)
+        .map_or(0, |entry| entry.count);
+    Ok(RestoreReport {
+        mode: match mode {
+            RestoreMode::Merge => "merge".to_owned(),
+            RestoreMode::Replace => "replace".to_owned(),
+        },
+        restored,
+        skipped_erased: contents.persons.len() - restored,
+        audit_entries,
+    })
+}
@@ -0,0 +1,91 @@
+// src/backup_handlers.rs
+
+use actix_web::{web, HttpRequest, HttpResponse};
+use futures::StreamExt;
+use serde::Deserialize;
+
+use crate::admin::check_admin;
+use crate::backup::{self, RestoreMode};
+use crate::broadcast::Broadcaster;
+use crate::compliance::{self, SharedComplianceLog};
+use crate::config::Config;
+use crate::errors::MyError;
+use crate::tenancy::Tenant;
+use shared::ErrorEnvelope;
+
+#[derive(Deserialize)]
+pub struct RestoreQuery {
+    pub mode: Option<RestoreMode>,
+}
+
+///
+/// télécharge une sauvegarde tar.gz des personnes et du journal du locataire
+///
+pub async fn backup_hdl(
+    req: HttpRequest,
+    config: web::Data<Config>,
+    broadcaster: web::Data<Broadcaster>,
+    tenant: Tenant,
+) -> Result<HttpResponse, MyError> {
+    if let Err(resp) = check_admin(&req, &config) {
+        return Ok(resp);
+    }
+    let store = tenant.store()?;
+    // la lecture du journal l'attend : elle se fait hors du worker
+    let archive = web::block(move || {
+        let audit: Vec<_> = broadcaster
+            .events_since(0)
+            .into_iter()
+            .filter(|logged| logged.tenant == tenant.id)
+            .collect();
+        backup::create_backup(&*store, &audit)
+    })
+    .await?;
+    let file_name = format!(
+        "seed-backup-{}.tar.gz",
+        chrono::Utc::now().format("%Y%m%d-%H%M%S")
+    );
+    Ok(HttpResponse::Ok()
+        .content_type("application/gzip")
+        .header(
+            "Content-Disposition",
+            format!("attachment; filename=\"{}\"", file_name),
+        )
+        .body(archive))
+}
+
+///
+/// restaure une sauvegarde envoyée dans le corps de la requête
+/// l'archive est vérifiée entièrement avant de toucher à la base
+/// les personnes effacées depuis la sauvegarde ne sont pas restaurées
+///
+pub async fn restore_hdl(
+    req: HttpRequest,
+    config: web::Data<Config>,
+    log: web::Data<SharedComplianceLog>,
+    query: web::Query<RestoreQuery>,
+    tenant: Tenant,
+    mut payload: web::Payload,
+) -> Result<HttpResponse, MyError> {
+    if let Err(resp) = check_admin(&req, &config) {
+        return Ok(resp);
+    }
+
+    let mut archive = web::BytesMut::new();
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk.map_err(|e| MyError::Backup(e.to_string()))?;
+        // la taille maximale vient de SEED_IMPORT_LIMIT
+        if archive.len() + chunk.len() > config.import_limit {
+            return Ok(HttpResponse::PayloadTooLarge().json(ErrorEnvelope::new(
+                "payload_too_large",
+                "backup archive is too large",
+            )));
+        }
+        archive.extend_from_slice(&chunk);
+    }
+
+    let mode = query.mode.unwrap_or(RestoreMode::Merge);
+    let erased = compliance::erased_ids(&**log, tenant.id.as_deref())?;
+    let report = backup::restore_backup(&*tenant.store()?, &archive, mode, &erased)?;
+    Ok(HttpResponse::Ok().json(report))
+}
@@ -0,0 +1,384 @@
+// server/src/batch.rs
+
+use std::collections::HashMap;
+
+use actix_web::ResponseError;
+
+use crate::errors::MyError;
+use crate::store::{Direct, PersonStore, PersonWriter};
+use shared::{
+    BatchAction, BatchRequest, BatchResponse, BatchResult, ErrorEnvelope, Person, PersonEvent,
+    PersonId,
+};
+
+// le statut des opérations annulées ou pas exécutées (Failed Dependency)
+const FAILED_DEPENDENCY: u16 = 424;
+
+///
+/// vérifie le lot avant de l'exécuter : au moins une et au plus `limit` opérations,
+/// des ref uniques, et des ids "$<ref>" qui désignent un ajout placé avant
+///
+pub fn validate(request: &BatchRequest, limit: usize) -> Result<(), MyError> {
+    let count = request.operations.len();
+    if count == 0 {
+        return Err(MyError::Batch("the batch has no operation".to_owned()));
+    }
+    if count > limit {
+        return Err(MyError::Batch(format!(
+            "the batch has {} operations, the limit is {}",
+            count, limit
+        )));
+    }
+    // ref -> l'opération est un ajout
+    let mut references: HashMap<&str, bool> = HashMap::new();
+    for (index, operation) in request.operations.iter().enumerate() {
+        if let BatchAction::Modify { id, .. } | BatchAction::Delete { id } = &operation.action {
+            if id.starts_with('$') && references.get(&id[1..]) != Some(&true) {
+                return Err(MyError::Batch(format!(
+                    "operation {}: {} is not the ref of an earlier add",
+                    index, id
+                )));
+            }
+        }
+        if let Some(reference) = &operation.reference {
+            if reference.is_empty() || reference.starts_with('$') {
+                return Err(MyError::Batch(format!(
+                    "operation {}: a ref must not be empty or start with $",
+                    index
+                )));
+            }
+            let is_add = matches!(operation.action, BatchAction::Add { .. });
+            if references.insert(reference, is_add).is_some() {
+                return Err(MyError::Batch(format!(
+                    "operation {}: the ref {} is already used",
+                    index, reference
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+// ce qui défait une opération
+enum Undo {
+    // un ajout
+    Delete(String),
+    // une modification : la personne d'avant
+    Restore(String, Person),
+    // un effacement : la personne effacée, avec son id
+    Upsert(Person),
+}
+
+impl Undo {
+    fn revert(self, store: &dyn PersonStore) -> Result<(), MyError> {
+        match self {
+            Undo::Delete(id) => store.delete(&id).map(|_| ()),
+            Undo::Restore(id, previous) => store.modify(&id, previous).map(|_| ()),
+            Undo::Upsert(person) => store.upsert(person),
+        }
+    }
+}
+
+struct Applied {
+    person: Person,
+    event: PersonEvent,
+    undo: Undo,
+}
+
+// l'échec d'une opération : son statut et son erreur
+type OpError = (u16, ErrorEnvelope);
+
+fn op_error(e: MyError) -> OpError {
+    (
+        e.status_code().as_u16(),
+        ErrorEnvelope::new(e.code(), e.to_string()),
+    )
+}
+
+fn not_found(id: &str) -> OpError {
+    (
+        404,
+        ErrorEnvelope::new("not_found", format!("no person with the id {}", id)),
+    )
+}
+
+// l'id de l'opération ; "$<ref>" devient l'id de la personne ajoutée par l'opération ref
+fn resolve(id: String, created: &HashMap<String, String>) -> Result<String, OpError> {
+    if !id.starts_with('$') {
+        return Ok(id);
+    }
+    match created.get(&id[1..]) {
+        Some(created_id) => Ok(created_id.clone()),
+        None => Err((
+            FAILED_DEPENDENCY,
+            ErrorEnvelope::new(
+                "failed_dependency",
+                format!("the operation {} did not add a person", &id[1..]),
+            ),
+        )),
+    }
+}
+
+fn apply<W: PersonWriter + ?Sized>(
+    store: &W,
+    action: BatchAction,
+    created: &HashMap<String, String>,
+) -> Result<Applied, OpError> {
+    match action {
+        BatchAction::Add { person } => {
+            let added = store.add(person).map_err(op_error)?;
+            let id = added
+                .id
+                .as_ref()
+                .map(|id| id.to_string())
+                .unwrap_or_default();
+            Ok(Applied {
+                person: added.clone(),
+                event: PersonEvent::Created(added),
+                undo: Undo::Delete(id),
+            })
+        }
+        BatchAction::Modify { id, person } => {
+            let id = resolve(id, created)?;
+            match store.modify(&id, person.clone()).map_err(op_error)? {
+                Some(previous) => {
+                    let updated = Person {
+                        id: Some(PersonId::new(id.clone())),
+                        ..person
+                    };
+                    Ok(Applied {
+                        person: updated.clone(),
+                        event: PersonEvent::Updated(updated),
+                        undo: Undo::Restore(id, previous),
+                    })
+                }
+                None => Err(not_found(&id)),
+            }
+        }
+        BatchAction::Delete { id } => {
+            let id = resolve(id, created)?;
+            match store.delete(&id).map_err(op_error)? {
+                Some(deleted) => Ok(Applied {
+                    person: deleted.clone(),
+                    event: PersonEvent::Deleted(deleted.clone()),
+                    undo: Undo::Upsert(deleted),
+                }),
+                None => Err(not_found(&id)),
+            }
+        }
+    }
+}
+
+///
+/// le résultat d'un lot, et les événements à envoyer pour ce qui a été gardé
+///
+pub struct BatchOutcome {
+    pub response: BatchResponse,
+    pub events: Vec<PersonEvent>,
+}
+
+// ce que les opérations ont donné : les résultats, les opérations faites
+// (le rang du résultat, l'événement et ce qui la défait) et si le lot a échoué
+struct Run {
+    results: Vec<BatchResult>,
+    applied: Vec<(usize, PersonEvent, Undo)>,
+    failed: bool,
+}
+
+// exécute les opérations dans l'ordre, jusqu'à la première en échec sauf si partial
+fn run<W: PersonWriter + ?Sized>(store: &W, request: BatchRequest) -> Run {
+    let partial = request.partial;
+    let mut created: HashMap<String, String> = HashMap::new();
+    let mut results = Vec::with_capacity(request.operations.len());
+    let mut applied = Vec::new();
+    let mut failed = false;
+
+    for operation in request.operations {
+        let reference = operation.reference;
+        if failed {
+            results.push(BatchResult {
+                reference,
+                status: FAILED_DEPENDENCY,
+                person: None,
+                error: Some(ErrorEnvelope::new(
+                    "not_executed",
+                    "not executed because an earlier operation failed",
+                )),
+            });
+            continue;
+        }
+        match apply(store, operation.action, &created) {
+            Ok(done) => {
+                if let (Some(reference), PersonEvent::Created(person)) = (&reference, &done.event) {
+                    if let Some(id) = &person.id {
+                        created.insert(reference.clone(), id.to_string());
+                    }
+                }
+                applied.push((results.len(), done.event, done.undo));
+                results.push(BatchResult {
+                    reference,
+                    status: 200,
+                    person: Some(done.person),
+                    error: None,
+                });
+            }
+            Err((status, error)) => {
+                results.push(BatchResult {
+                    reference,
+                    status,
+                    person: None,
+                    error: Some(error),
+                });
+                failed = !partial;
+            }
+        }
+    }
+    Run {
+        results,
+        applied,
+        failed,
+    }
+}
+
+fn committed(run: Run) -> BatchOutcome {
+    BatchOutcome {
+        response: BatchResponse {
+            committed: true,
+            results: run.results,
+        },
+        events: run.applied.into_iter().map(|(_, event, _)| event).collect(),
+    }
+}
+
+fn rolled_back(result: &mut BatchResult) {
+    result.status = FAILED_DEPENDENCY;
+    result.person = None;
+    result.error = Some(ErrorEnvelope::new(
+        "rolled_back",
+        "undone because another operation failed",
+    ));
+}
+
+///
+/// exécute les opérations dans l'ordre (le lot doit être passé par validate)
+///
+/// avec partial, chaque opération est gardée ou non selon son propre résultat
+/// sinon, tout ou rien : à la première opération en échec, les suivantes ne sont pas
+/// exécutées et celles déjà faites sont défaites
+/// SQLite et PostgreSQL exécutent le lot dans une transaction, annulée en cas d'échec
+/// le pilote mongodb utilisé n'a pas de transactions multi-documents : avec MongoDB
+/// (et en mémoire) les opérations faites sont défaites en ordre inverse (compensation),
+/// et pendant le lot, les autres requêtes voient son état
+/// une compensation en échec laisse l'opération en place, avec le statut 500
+///
+pub fn execute(store: &dyn PersonStore, request: BatchRequest) -> BatchOutcome {
+    // partial garde ce qui a réussi : pas besoin de transaction
+    if request.partial {
+        return committed(run(&Direct(store), request));
+    }
+    let operations = request.operations.len();
+    let mut pending = Some(request);
+    let mut done = None;
+    let transaction = store.transaction(&mut |writer: &dyn PersonWriter| match pending.take() {
+        Some(request) => {
+            let ran = run(writer, request);
+            let commit = !ran.failed;
+            done = Some(ran);
+            commit
+        }
+        None => false,
+    });
+
+    match (transaction, done, pending) {
+        (None, _, Some(request)) => compensate(store, run(&Direct(store), request)),
+        (Some(Ok(_)), Some(mut ran), _) => {
+            if !ran.failed {
+                return committed(ran);
+            }
+            for (index, _, _) in &ran.applied {
+                rolled_back(&mut ran.results[*index]);
+            }
+            tracing::warn!(operations, "batch failed, transaction rolled back");
+            BatchOutcome {
+                response: BatchResponse {
+                    committed: false,
+                    results: ran.results,
+                },
+                events: Vec::new(),
+            }
+        }
+        // la transaction n'a pas pu commencer ou être validée : rien n'est écrit
+        (result, ran, _) => {
+            let error = match result {
+                Some(Err(e)) => e,
+                _ => MyError::Batch("the batch did not run".to_owned()),
+            };
+            tracing::error!(error = %error, "batch transaction failed");
+            let envelope = ErrorEnvelope::new("transaction_failed", error.to_string());
+            let results = match ran {
+                Some(ran) => ran
+                    .results
+                    .into_iter()
+                    .map(|result| BatchResult {
+                        status: 500,
+                        person: None,
+                        error: Some(envelope.clone()),
+                        ..result
+                    })
+                    .collect(),
+                None => (0..operations)
+                    .map(|_| BatchResult {
+                        reference: None,
+                        status: 500,
+                        person: None,
+                        error: Some(envelope.clone()),
+                    })
+                    .collect(),
+            };
+            BatchOutcome {
+                response: BatchResponse {
+                    committed: false,
+                    results,
+                },
+                events: Vec::new(),
+            }
+        }
+    }
+}
+
+// sans transaction : défait en ordre inverse ce qui a été fait
+fn compensate(store: &dyn PersonStore, run: Run) -> BatchOutcome {
+    if !run.failed {
+        return committed(run);
+    }
+
+    let mut results = run.results;
+    let mut kept = Vec::new();
+    for (index, event, undo) in run.applied.into_iter().rev() {
+        let result = &mut results[index];
+        match undo.revert(store) {
+            Ok(()) => rolled_back(result),
+            Err(e) => {
+                tracing::error!(error = %e, operation = index, "batch rollback failed");
+                result.status = 500;
+                result.error = Some(ErrorEnvelope::new(
+                    "rollback_failed",
+                    format!("the operation could not be undone: {}", e),
+                ));
+                kept.push(event);
+            }
+        }
+    }
+    kept.reverse();
+    tracing::warn!(
+        operations = results.len(),
+        "batch failed, operations rolled back"
+    );
+    BatchOutcome {
+        response: BatchResponse {
+            committed: false,
+            results,
+        },
+        events: kept,
+    }
+}
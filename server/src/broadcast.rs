@@ -0,0 +1,233 @@
+// server/src/broadcast.rs
+
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+
+use crate::errors::MyError;
+use crate::event_log::{EventLog, LoggedEvent};
+use shared::PersonEvent;
+
+// ce que reçoit le thread qui écrit le journal
+enum LogWrite {
+    Append(LoggedEvent),
+    // répond quand les écritures demandées avant sont faites
+    Flush(Sender<()>),
+}
+
+// le thread d'écriture : les événements dans l'ordre où ils arrivent
+fn write_log(log: &EventLog, writes: Receiver<LogWrite>) {
+    for write in writes {
+        match write {
+            LogWrite::Append(logged) => {
+                if let Err(e) = log.append(&logged) {
+                    tracing::error!(event_id = logged.id, error = %e, "failed to log event");
+                }
+            }
+            LogWrite::Flush(done) => {
+                let _ = done.send(());
+            }
+        }
+    }
+}
+
+// le journal et la file de son thread d'écriture
+struct LogWriter {
+    log: Arc<EventLog>,
+    queue: Mutex<Sender<LogWrite>>,
+}
+
+impl LogWriter {
+    ///
+    /// les écritures MongoDB se font sur un thread à part, dans l'ordre des numéros :
+    /// les workers actix n'attendent pas la base quand ils envoient un événement
+    ///
+    fn start(log: EventLog) -> Self {
+        let log = Arc::new(log);
+        let (queue, writes) = mpsc::channel();
+        let writer_log = log.clone();
+        thread::Builder::new()
+            .name("event-log".to_owned())
+            .spawn(move || write_log(&writer_log, writes))
+            .expect("failed to start the event log writer");
+        Self {
+            log,
+            queue: Mutex::new(queue),
+        }
+    }
+
+    fn append(&self, logged: LoggedEvent) {
+        if self
+            .queue
+            .lock()
+            .unwrap()
+            .send(LogWrite::Append(logged))
+            .is_err()
+        {
+            tracing::error!("the event log writer has stopped");
+        }
+    }
+
+    // attend que les événements déjà envoyés soient dans le journal
+    fn flush(&self) {
+        let (done, wait) = mpsc::channel();
+        if self
+            .queue
+            .lock()
+            .unwrap()
+            .send(LogWrite::Flush(done))
+            .is_ok()
+        {
+            let _ = wait.recv();
+        }
+    }
+}
+
+///
+/// le Broadcaster garde la liste des clients abonnés
+/// et leur envoie chaque événement sur les personnes
+///
+/// chaque événement reçoit un numéro croissant
+/// et est enregistré dans le journal s'il y en a un
+///
+pub struct Broadcaster {
+    clients: Mutex<Vec<UnboundedSender<LoggedEvent>>>,
+    last_id: Mutex<i64>,
+    log: Option<LogWriter>,
+}
+
+impl Broadcaster {
+    pub fn new() -> Self {
+        Self {
+            clients: Mutex::new(Vec::new()),
+            last_id: Mutex::new(0),
+            log: None,
+        }
+    }
+
+    ///
+    /// un Broadcaster qui enregistre les événements dans le journal
+    /// la numérotation reprend après le dernier événement enregistré
+    ///
+    pub fn with_log(log: EventLog) -> Self {
+        let last_id = match log.last_id() {
+            Ok(id) => id,
+            Err(e) => panic!("Error: failed to read the event log {}", e),
+        };
+        Self {
+            clients: Mutex::new(Vec::new()),
+            last_id: Mutex::new(last_id),
+            log: Some(LogWriter::start(log)),
+        }
+    }
+
+    ///
+    /// enregistre un nouveau client
+    /// et renvoie le flux des événements qui lui sont destinés
+    ///
+    pub fn new_client(&self) -> UnboundedReceiver<LoggedEvent> {
+        let (tx, rx) = unbounded();
+        self.clients.lock().unwrap().push(tx);
+        rx
+    }
+
+    ///
+    /// envoie un événement sans locataire (mode single)
+    ///
+    pub fn send(&self, event: PersonEvent) {
+        self.send_for(None, event)
+    }
+
+    ///
+    /// numérote l'événement, l'enregistre et l'envoie à tous les clients
+    /// les clients filtrent ensuite sur leur locataire
+    /// les clients déconnectés sont retirés de la liste
+    ///
+    pub fn send_for(&self, tenant: Option<String>, event: PersonEvent) {
+        // on garde le verrou jusqu'à la fin pour que les clients et le journal
+        // reçoivent les événements dans l'ordre des numéros
+        // l'écriture en base est faite ensuite par le thread du journal
+        let mut last_id = self.last_id.lock().unwrap();
+        *last_id += 1;
+        let logged = LoggedEvent {
+            id: *last_id,
+            tenant,
+            event,
+        };
+
+        if let Some(log) = &self.log {
+            log.append(logged.clone());
+        }
+
+        let mut clients = self.clients.lock().unwrap();
+        clients.retain(|client| client.unbounded_send(logged.clone()).is_ok());
+    }
+
+    ///
+    /// les événements enregistrés après l'id donné
+    /// pour rejouer ce qu'un client a manqué
+    /// attend le thread d'écriture et lit la base : à appeler dans web::block
+    ///
+    pub fn events_since(&self, last_id: i64) -> Vec<LoggedEvent> {
+        match &self.log {
+            Some(writer) => {
+                writer.flush();
+                writer.log.since(last_id).unwrap_or_else(|e| {
+                    tracing::error!(error = %e, "failed to read the event log");
+                    Vec::new()
+                })
+            }
+            None => Vec::new(),
+        }
+    }
+
+    ///
+    /// les événements d'une personne du locataire encore dans le journal
+    /// vide sans journal ; une erreur de lecture est renvoyée, pas ignorée
+    /// attend le thread d'écriture et lit la base : à appeler dans web::block
+    ///
+    pub fn person_events(
+        &self,
+        tenant: Option<&str>,
+        person_id: &str,
+    ) -> Result<Vec<LoggedEvent>, MyError> {
+        match &self.log {
+            Some(writer) => {
+                writer.flush();
+                writer.log.person_events(tenant, person_id)
+            }
+            None => Ok(Vec::new()),
+        }
+    }
+
+    ///
+    /// le nombre d'événements que garde le journal ; None sans journal
+    ///
+    pub fn log_capacity(&self) -> Option<i64> {
+        self.log.as_ref().map(|writer| writer.log.capacity())
+    }
+
+    ///
+    /// efface du journal les événements d'une personne et renvoie leurs numéros
+    /// les clients déjà servis ont reçu ces événements : ils ne sont pas rappelés
+    /// attend le thread d'écriture et écrit dans la base : à appeler dans web::block
+    ///
+    pub fn erase_person(&self, tenant: Option<&str>, person_id: &str) -> Result<Vec<i64>, MyError> {
+        match &self.log {
+            Some(writer) => {
+                // un événement encore en file serait écrit après l'effacement
+                writer.flush();
+                writer.log.erase_person(tenant, person_id)
+            }
+            None => Ok(Vec::new()),
+        }
+    }
+}
+
+impl Default for Broadcaster {
+    fn default() -> Self {
+        Self::new()
+    }
+}
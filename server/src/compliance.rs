@@ -0,0 +1,178 @@
+// server/src/compliance.rs
+
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+use bson::{doc, from_bson, to_bson, Bson, Document};
+use chrono::Utc;
+use mongodb::options::FindOptions;
+
+use crate::db_mongo;
+use crate::db_postgres::PostgresComplianceLog;
+use crate::db_sqlite::{self, SqliteComplianceLog};
+use crate::errors::MyError;
+use crate::store::{self, Backend};
+pub use shared::{ComplianceAction, ComplianceEntry};
+
+///
+/// le journal des demandes RGPD, pour le délégué à la protection des données
+/// il est gardé dans le stockage des personnes (SEED_STORAGE) et n'est jamais effacé
+///
+pub trait ComplianceLog: Send + Sync {
+    fn append(&self, entry: &ComplianceEntry) -> Result<(), MyError>;
+    // les entrées du locataire dans l'ordre ; celles d'une seule personne si person_id est donné
+    fn list(
+        &self,
+        tenant: Option<&str>,
+        person_id: Option<&str>,
+    ) -> Result<Vec<ComplianceEntry>, MyError>;
+}
+
+///
+/// le journal partagé par les routes RGPD (web::Data<SharedComplianceLog>)
+///
+pub type SharedComplianceLog = Arc<dyn ComplianceLog>;
+
+///
+/// le journal avec le backend configuré
+///
+pub fn open_compliance_log() -> Result<Box<dyn ComplianceLog>, MyError> {
+    Ok(match store::backend() {
+        Backend::Mongo => Box::new(MongoComplianceLog),
+        Backend::Sqlite => Box::new(SqliteComplianceLog::new(db_sqlite::path())),
+        Backend::Postgres => Box::new(PostgresComplianceLog),
+    })
+}
+
+///
+/// une nouvelle entrée datée de maintenant
+///
+pub fn entry(
+    tenant: Option<String>,
+    person_id: &str,
+    action: ComplianceAction,
+    actor: &str,
+    details: Option<String>,
+) -> ComplianceEntry {
+    ComplianceEntry {
+        id: uuid::Uuid::new_v4().to_string(),
+        tenant,
+        person_id: person_id.to_owned(),
+        action,
+        actor: actor.to_owned(),
+        at: Utc::now().timestamp_millis(),
+        details,
+    }
+}
+
+///
+/// les id des personnes du locataire dont l'effacement a été demandé
+/// une restauration ne doit pas les faire revenir
+///
+pub fn erased_ids(
+    log: &dyn ComplianceLog,
+    tenant: Option<&str>,
+) -> Result<HashSet<String>, MyError> {
+    Ok(log
+        .list(tenant, None)?
+        .into_iter()
+        .filter(|entry| entry.action != ComplianceAction::Export)
+        .map(|entry| entry.person_id)
+        .collect())
+}
+
+///
+/// la collection ComplianceLog de MongoDB
+///
+pub struct MongoComplianceLog;
+
+impl ComplianceLog for MongoComplianceLog {
+    fn append(&self, entry: &ComplianceEntry) -> Result<(), MyError> {
+        let mut document = match to_bson(entry)? {
+            Bson::Document(document) => document,
+            _ => Document::new(),
+        };
+        document.remove("id");
+        document.insert("_id", entry.id.clone());
+        db_mongo::get_named_collection("ComplianceLog")?.insert_one(document, None)?;
+        Ok(())
+    }
+
+    fn list(
+        &self,
+        tenant: Option<&str>,
+        person_id: Option<&str>,
+    ) -> Result<Vec<ComplianceEntry>, MyError> {
+        let mut filter = doc! {"tenant": to_bson(&tenant)?};
+        if let Some(person_id) = person_id {
+            filter.insert("person_id", person_id);
+        }
+        // l'ordre d'insertion : deux entrées peuvent avoir la même milliseconde
+        let options = FindOptions::builder().sort(doc! {"$natural": 1}).build();
+        let cursor =
+            db_mongo::get_named_collection("ComplianceLog")?.find(Some(filter), Some(options))?;
+        let res: Result<Vec<_>, _> = cursor
+            .map(|row| row.and_then(|item| Ok(from_bson::<ComplianceEntry>(Bson::Document(item))?)))
+            .collect();
+        Ok(res?)
+    }
+}
+
+impl ComplianceLog for SqliteComplianceLog {
+    fn append(&self, entry: &ComplianceEntry) -> Result<(), MyError> {
+        SqliteComplianceLog::append(self, entry)
+    }
+
+    fn list(
+        &self,
+        tenant: Option<&str>,
+        person_id: Option<&str>,
+    ) -> Result<Vec<ComplianceEntry>, MyError> {
+        SqliteComplianceLog::list(self, tenant, person_id)
+    }
+}
+
+impl ComplianceLog for PostgresComplianceLog {
+    fn append(&self, entry: &ComplianceEntry) -> Result<(), MyError> {
+        PostgresComplianceLog::append(self, entry)
+    }
+
+    fn list(
+        &self,
+        tenant: Option<&str>,
+        person_id: Option<&str>,
+    ) -> Result<Vec<ComplianceEntry>, MyError> {
+        PostgresComplianceLog::list(self, tenant, person_id)
+    }
+}
+
+///
+/// le journal en mémoire (tests)
+///
+#[derive(Default)]
+pub struct MemoryComplianceLog {
+    entries: Mutex<Vec<ComplianceEntry>>,
+}
+
+impl ComplianceLog for MemoryComplianceLog {
+    fn append(&self, entry: &ComplianceEntry) -> Result<(), MyError> {
+        self.entries.lock().unwrap().push(entry.clone());
+        Ok(())
+    }
+
+    fn list(
+        &self,
+        tenant: Option<&str>,
+        person_id: Option<&str>,
+    ) -> Result<Vec<ComplianceEntry>, MyError> {
+        Ok(self
+            .entries
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|entry| entry.tenant.as_deref() == tenant)
+            .filter(|entry| person_id.map_or(true, |id| entry.person_id == id))
+            .cloned()
+            .collect())
+    }
+}
@@ -0,0 +1,363 @@
+// server/src/config.rs
+
+use std::env;
+
+use actix_web::http::header::HeaderName;
+use actix_web::http::Method;
+
+use crate::cors::{CorsMode, DEFAULT_CORS_HEADERS, DEFAULT_CORS_METHODS};
+use crate::db_mongo::{DEFAULT_DATABASE, DEFAULT_MONGO_URI};
+use crate::db_postgres::DEFAULT_POSTGRES_URL;
+use crate::db_sqlite::DEFAULT_SQLITE_PATH;
+use crate::store::Backend;
+use crate::telemetry::LogFormat;
+use crate::tenancy::TenantMode;
+use crate::tls::TlsFiles;
+use shared::Scope;
+
+pub const DEFAULT_BIND: &str = "127.0.0.1:8000";
+
+// une heure
+pub const DEFAULT_CORS_MAX_AGE: usize = 3600;
+
+pub const DEFAULT_RATE_LIMIT_READS: u32 = 600;
+pub const DEFAULT_RATE_LIMIT_WRITES: u32 = 120;
+pub const DEFAULT_RATE_LIMIT_IMPORTS: u32 = 5;
+
+// 64 Kio : une personne tient en quelques dizaines d'octets
+pub const DEFAULT_JSON_LIMIT: usize = 64 * 1024;
+pub const DEFAULT_IMPORT_LIMIT: usize = 256 * 1024 * 1024;
+
+pub const DEFAULT_OIDC_REDIRECT_URL: &str = "http://127.0.0.1:8000/auth/callback";
+pub const DEFAULT_OIDC_SCOPES: &[&str] = &["openid", "profile", "email"];
+
+// huit heures, une journée de travail
+pub const DEFAULT_SESSION_TTL: u64 = 8 * 3600;
+
+// une journée : de quoi couvrir les nouvelles tentatives d'un client
+pub const DEFAULT_IDEMPOTENCY_TTL: u64 = 24 * 3600;
+
+pub const DEFAULT_BATCH_LIMIT: usize = 1000;
+
+///
+/// la configuration du serveur
+/// lue dans les variables d'environnement (ou le fichier .env)
+///
+#[derive(Clone, Debug)]
+pub struct Config {
+    // jeton demandé dans l'en-tête X-Admin-Token pour les routes d'administration
+    // sans jeton, les routes d'administration sont refusées
+    pub admin_token: Option<String>,
+    // demande une clé d'API (Authorization: Bearer) sur toutes les routes non publiques
+    // sinon, une requête sans clé passe comme avant
+    pub auth_required: bool,
+    // active l'interface GraphiQL sur /graphiql
+    pub graphiql: bool,
+    // applique les migrations de la base au démarrage
+    pub auto_migrate: bool,
+    // le stockage des personnes : mongo, sqlite ou postgres
+    pub storage: Backend,
+    // le fichier de la base SQLite
+    pub sqlite_path: String,
+    // l'adresse du serveur PostgreSQL
+    pub postgres_url: String,
+    // l'adresse du serveur MongoDB
+    pub mongo_uri: String,
+    // la base des personnes ; "local" est réservée par MongoDB et n'est pas répliquée
+    pub database: String,
+    // single, database (une base par locataire) ou collection (tenant_id dans Persons)
+    pub tenant_mode: TenantMode,
+    // l'en-tête qui porte l'identifiant du locataire
+    pub tenant_header: String,
+    // le locataire peut aussi venir du sous-domaine : acme.example.com -> acme
+    pub tenant_subdomain: bool,
+    // les journaux en json (par défaut) ou en texte ; le filtre reste RUST_LOG
+    pub log_format: LogFormat,
+    // le collecteur OpenTelemetry, par exemple http://localhost:4317 (feature otlp)
+    pub otlp_endpoint: Option<String>,
+    // le dossier du client : index.html et pkg/ (ignoré avec la feature embed-client)
+    pub client_dir: String,
+    // l'adresse du serveur ; en HTTPS quand un certificat est configuré
+    pub bind: String,
+    // le certificat et la clé au format PEM : le serveur passe en HTTPS
+    // ils sont rechargés sur SIGHUP ou quand les fichiers changent
+    pub tls_cert: Option<String>,
+    pub tls_key: Option<String>,
+    // un certificat auto-signé pour localhost (ou l'option --dev-tls), en développement
+    pub dev_tls: bool,
+    // une adresse HTTP en plus, qui redirige vers HTTPS, par exemple 127.0.0.1:8080
+    pub http_redirect_bind: Option<String>,
+    // strict (par défaut) ou dev, qui accepte toutes les origines
+    pub cors_mode: CorsMode,
+    // les origines acceptées en mode strict, par exemple https://tools.example.com
+    pub cors_origins: Vec<String>,
+    pub cors_methods: Vec<String>,
+    pub cors_headers: Vec<String>,
+    // Access-Control-Allow-Credentials en mode strict
+    pub cors_credentials: bool,
+    // la durée de cache de la réponse au preflight, en secondes
+    pub cors_max_age: usize,
+    // les quotas par client, en requêtes par minute (0 : pas de limite)
+    // lectures (GET), écritures (POST, PUT, DELETE) et imports (/admin/restore)
+    pub rate_limit_reads: u32,
+    pub rate_limit_writes: u32,
+    pub rate_limit_imports: u32,
+    // la taille maximale d'un corps JSON, en octets
+    pub json_limit: usize,
+    // la taille maximale d'un import (archive de /admin/restore), en octets
+    pub import_limit: usize,
+    // le fournisseur d'identité OpenID Connect, par exemple https://sso.example.com/realms/acme
+    // sans issuer, la connexion par /auth/login est désactivée
+    pub oidc_issuer: Option<String>,
+    pub oidc_client_id: String,
+    // absent pour un client public : PKCE suffit
+    pub oidc_client_secret: Option<String>,
+    // l'adresse de /auth/callback vue par le navigateur, déclarée chez le fournisseur
+    pub oidc_redirect_url: String,
+    pub oidc_scopes: Vec<String>,
+    // la revendication de l'ID token qui porte les groupes
+    pub oidc_groups_claim: String,
+    // la revendication qui porte le locataire de l'utilisateur, s'il y en a une
+    pub oidc_tenant_claim: Option<String>,
+    // groupe=droit, par exemple seed-admins=admin,seed-users=read
+    pub oidc_roles: Vec<(String, Scope)>,
+    // le droit des utilisateurs sans groupe reconnu ; sans droit, la connexion est refusée
+    pub oidc_default_role: Option<Scope>,
+    // la durée d'une session après la connexion, en secondes
+    pub session_ttl: u64,
+    // combien de temps une clé Idempotency-Key et sa réponse sont gardées, en secondes
+    pub idempotency_ttl: u64,
+    // le nombre maximal d'opérations d'un lot (POST /persons/batch)
+    pub batch_limit: usize,
+}
+
+impl Config {
+    pub fn from_env() -> Self {
+        dotenv::dotenv().ok();
+        Self {
+            admin_token: env::var("SEED_ADMIN_TOKEN").ok().filter(|t| !t.is_empty()),
+            auth_required: env_flag("SEED_AUTH_REQUIRED"),
+            graphiql: env_flag("SEED_GRAPHIQL"),
+            auto_migrate: env_flag_or("SEED_AUTO_MIGRATE", true),
+            storage: match env::var("SEED_STORAGE") {
+                Ok(storage) => storage
+                    .parse()
+                    .unwrap_or_else(|e| panic!("Error: SEED_STORAGE {}", e)),
+                Err(_) => Backend::Mongo,
+            },
+            sqlite_path: env_or("SEED_SQLITE_PATH", DEFAULT_SQLITE_PATH),
+            postgres_url: env_or("SEED_POSTGRES_URL", DEFAULT_POSTGRES_URL),
+            mongo_uri: env_or("SEED_MONGO_URI", DEFAULT_MONGO_URI),
+            database: env_or("SEED_DATABASE", DEFAULT_DATABASE),
+            tenant_mode: match env::var("SEED_TENANT_MODE") {
+                Ok(mode) => mode
+                    .parse()
+                    .unwrap_or_else(|e| panic!("Error: SEED_TENANT_MODE {}", e)),
+                Err(_) => TenantMode::Single,
+            },
+            tenant_header: env_or("SEED_TENANT_HEADER", "X-Tenant-Id"),
+            tenant_subdomain: env_flag("SEED_TENANT_SUBDOMAIN"),
+            log_format: match env::var("SEED_LOG_FORMAT") {
+                Ok(format) => format
+                    .parse()
+                    .unwrap_or_else(|e| panic!("Error: SEED_LOG_FORMAT {}", e)),
+                Err(_) => LogFormat::Json,
+            },
+            otlp_endpoint: env::var("SEED_OTLP_ENDPOINT")
+                .ok()
+                .filter(|e| !e.is_empty()),
+            client_dir: env_or("SEED_CLIENT_DIR", "client"),
+            bind: env_or("SEED_BIND", DEFAULT_BIND),
+            tls_cert: env::var("SEED_TLS_CERT").ok().filter(|p| !p.is_empty()),
+            tls_key: env::var("SEED_TLS_KEY").ok().filter(|p| !p.is_empty()),
+            dev_tls: env_flag("SEED_DEV_TLS"),
+            http_redirect_bind: env::var("SEED_HTTP_REDIRECT_BIND")
+                .ok()
+                .filter(|b| !b.is_empty()),
+            cors_mode: match env::var("SEED_CORS_MODE") {
+                Ok(mode) => mode
+                    .parse()
+                    .unwrap_or_else(|e| panic!("Error: SEED_CORS_MODE {}", e)),
+                Err(_) => CorsMode::Strict,
+            },
+            cors_origins: env_list("SEED_CORS_ORIGINS", &[]),
+            cors_methods: env_list("SEED_CORS_METHODS", DEFAULT_CORS_METHODS)
+                .into_iter()
+                .map(|method| {
+                    let method = method.to_uppercase();
+                    Method::from_bytes(method.as_bytes())
+                        .unwrap_or_else(|e| panic!("Error: SEED_CORS_METHODS {}", e));
+                    method
+                })
+                .collect(),
+            cors_headers: env_list("SEED_CORS_HEADERS", DEFAULT_CORS_HEADERS)
+                .into_iter()
+                .map(|name| {
+                    HeaderName::from_bytes(name.as_bytes())
+                        .unwrap_or_else(|e| panic!("Error: SEED_CORS_HEADERS {}", e));
+                    name
+                })
+                .collect(),
+            cors_credentials: env_flag("SEED_CORS_CREDENTIALS"),
+            cors_max_age: env_parse("SEED_CORS_MAX_AGE", DEFAULT_CORS_MAX_AGE),
+            rate_limit_reads: env_parse("SEED_RATE_LIMIT_READS", DEFAULT_RATE_LIMIT_READS),
+            rate_limit_writes: env_parse("SEED_RATE_LIMIT_WRITES", DEFAULT_RATE_LIMIT_WRITES),
+            rate_limit_imports: env_parse("SEED_RATE_LIMIT_IMPORTS", DEFAULT_RATE_LIMIT_IMPORTS),
+            json_limit: env_parse("SEED_JSON_LIMIT", DEFAULT_JSON_LIMIT),
+            import_limit: env_parse("SEED_IMPORT_LIMIT", DEFAULT_IMPORT_LIMIT),
+            oidc_issuer: env::var("SEED_OIDC_ISSUER").ok().filter(|i| !i.is_empty()),
+            oidc_client_id: env_or("SEED_OIDC_CLIENT_ID", "seed-server"),
+            oidc_client_secret: env::var("SEED_OIDC_CLIENT_SECRET")
+                .ok()
+                .filter(|s| !s.is_empty()),
+            oidc_redirect_url: env_or("SEED_OIDC_REDIRECT_URL", DEFAULT_OIDC_REDIRECT_URL),
+            oidc_scopes: env_list("SEED_OIDC_SCOPES", DEFAULT_OIDC_SCOPES),
+            oidc_groups_claim: env_or("SEED_OIDC_GROUPS_CLAIM", "groups"),
+            oidc_tenant_claim: env::var("SEED_OIDC_TENANT_CLAIM")
+                .ok()
+                .filter(|c| !c.is_empty()),
+            oidc_roles: env_list("SEED_OIDC_ROLES", &[])
+                .into_iter()
+                .map(|pair| {
+                    parse_role(&pair).unwrap_or_else(|e| panic!("Error: SEED_OIDC_ROLES {}", e))
+                })
+                .collect(),
+            oidc_default_role: env::var("SEED_OIDC_DEFAULT_ROLE")
+                .ok()
+                .filter(|r| !r.is_empty())
+                .map(|role| {
+                    role.parse()
+                        .unwrap_or_else(|e| panic!("Error: SEED_OIDC_DEFAULT_ROLE {}", e))
+                }),
+            session_ttl: env_parse("SEED_SESSION_TTL", DEFAULT_SESSION_TTL),
+            idempotency_ttl: env_parse("SEED_IDEMPOTENCY_TTL", DEFAULT_IDEMPOTENCY_TTL),
+            batch_limit: env_parse("SEED_BATCH_LIMIT", DEFAULT_BATCH_LIMIT),
+        }
+    }
+
+    ///
+    /// les fichiers du certificat, s'ils sont configurés tous les deux
+    ///
+    pub fn tls_files(&self) -> Option<TlsFiles> {
+        match (&self.tls_cert, &self.tls_key) {
+            (Some(cert), Some(key)) => Some(TlsFiles::new(cert, key)),
+            _ => None,
+        }
+    }
+}
+
+// groupe=droit
+fn parse_role(pair: &str) -> Result<(String, Scope), String> {
+    let mut parts = pair.splitn(2, '=');
+    match (parts.next(), parts.next()) {
+        (Some(group), Some(scope)) if !group.trim().is_empty() => {
+            Ok((group.trim().to_owned(), scope.trim().parse()?))
+        }
+        _ => Err(format!("'{}' is not group=scope", pair)),
+    }
+}
+
+// un nombre ; une valeur invalide arrête le serveur
+fn env_parse<T>(name: &str, default: T) -> T
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    match env::var(name) {
+        Ok(value) if !value.is_empty() => value
+            .parse()
+            .unwrap_or_else(|e| panic!("Error: {} {}", name, e)),
+        _ => default,
+    }
+}
+
+// une liste séparée par des virgules
+fn env_list(name: &str, default: &[&str]) -> Vec<String> {
+    match env::var(name) {
+        Ok(value) => value
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(str::to_owned)
+            .collect(),
+        Err(_) => default.iter().map(|item| (*item).to_owned()).collect(),
+    }
+}
+
+fn env_or(name: &str, default: &str) -> String {
+    env::var(name)
+        .ok()
+        .filter(|value| !value.is_empty())
+        .unwrap_or_else(|| default.to_owned())
+}
+
+// une variable d'environnement vraie si elle vaut 1, true, yes ou on
+fn env_flag(name: &str) -> bool {
+    env_flag_or(name, false)
+}
+
+fn env_flag_or(name: &str, default: bool) -> bool {
+    match env::var(name) {
+        Ok(value) => matches!(value.to_lowercase().as_str(), "1" | "true" | "yes" | "on"),
+        Err(_) => default,
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            admin_token: None,
+            auth_required: false,
+            graphiql: false,
+            auto_migrate: true,
+            storage: Backend::Mongo,
+            sqlite_path: DEFAULT_SQLITE_PATH.to_owned(),
+            postgres_url: DEFAULT_POSTGRES_URL.to_owned(),
+            mongo_uri: DEFAULT_MONGO_URI.to_owned(),
+            database: DEFAULT_DATABASE.to_owned(),
+            tenant_mode: TenantMode::Single,
+            tenant_header: "X-Tenant-Id".to_owned(),
+            tenant_subdomain: false,
+            log_format: LogFormat::Json,
+            otlp_endpoint: None,
+            client_dir: "client".to_owned(),
+            bind: DEFAULT_BIND.to_owned(),
+            tls_cert: None,
+            tls_key: None,
+            dev_tls: false,
+            http_redirect_bind: None,
+            cors_mode: CorsMode::Strict,
+            cors_origins: Vec::new(),
+            cors_methods: DEFAULT_CORS_METHODS
+                .iter()
+                .map(|m| (*m).to_owned())
+                .collect(),
+            cors_headers: DEFAULT_CORS_HEADERS
+                .iter()
+                .map(|h| (*h).to_owned())
+                .collect(),
+            cors_credentials: false,
+            cors_max_age: DEFAULT_CORS_MAX_AGE,
+            rate_limit_reads: DEFAULT_RATE_LIMIT_READS,
+            rate_limit_writes: DEFAULT_RATE_LIMIT_WRITES,
+            rate_limit_imports: DEFAULT_RATE_LIMIT_IMPORTS,
+            json_limit: DEFAULT_JSON_LIMIT,
+            import_limit: DEFAULT_IMPORT_LIMIT,
+            oidc_issuer: None,
+            oidc_client_id: "seed-server".to_owned(),
+            oidc_client_secret: None,
+            oidc_redirect_url: DEFAULT_OIDC_REDIRECT_URL.to_owned(),
+            oidc_scopes: DEFAULT_OIDC_SCOPES
+                .iter()
+                .map(|s| (*s).to_owned())
+                .collect(),
+            oidc_groups_claim: "groups".to_owned(),
+            oidc_tenant_claim: None,
+            oidc_roles: Vec::new(),
+            oidc_default_role: None,
+            session_ttl: DEFAULT_SESSION_TTL,
+            idempotency_ttl: DEFAULT_IDEMPOTENCY_TTL,
+            batch_limit: DEFAULT_BATCH_LIMIT,
+        }
+    }
+}
@@ -0,0 +1,78 @@
+// server/src/cors.rs
+
+use actix_cors::{Cors, CorsFactory};
+use actix_web::middleware::Condition;
+
+use crate::config::Config;
+
+///
+/// strict (par défaut) : seules les origines de SEED_CORS_ORIGINS sont acceptées,
+/// aucune sans liste ; dev : toutes les origines, avec les cookies
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CorsMode {
+    Strict,
+    Dev,
+}
+
+impl std::str::FromStr for CorsMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "strict" => Ok(CorsMode::Strict),
+            "dev" => Ok(CorsMode::Dev),
+            other => Err(format!("unknown CORS mode '{}' (strict or dev)", other)),
+        }
+    }
+}
+
+pub const DEFAULT_CORS_METHODS: &[&str] = &["GET", "POST", "PUT", "DELETE"];
+
+pub const DEFAULT_CORS_HEADERS: &[&str] = &[
+    "content-type",
+    "authorization",
+    "x-request-id",
+    "idempotency-key",
+];
+
+// les en-têtes de réponse lisibles par le JavaScript d'une autre origine
+const EXPOSED_HEADERS: &[&str] = &[
+    "x-request-id",
+    "ratelimit-limit",
+    "ratelimit-remaining",
+    "ratelimit-reset",
+    "retry-after",
+    "idempotency-replayed",
+];
+
+///
+/// le middleware CORS de la configuration
+/// en mode strict sans origine, il n'est pas installé : le navigateur bloque les autres origines
+///
+pub fn cors(config: &Config) -> Condition<CorsFactory> {
+    let enabled = config.cors_mode == CorsMode::Dev || !config.cors_origins.is_empty();
+    let mut cors = Cors::new()
+        .expose_headers(EXPOSED_HEADERS.iter().copied())
+        .max_age(config.cors_max_age);
+
+    match config.cors_mode {
+        // toutes les origines, tous les en-têtes : l'origine est renvoyée telle quelle
+        CorsMode::Dev => {
+            cors = cors.supports_credentials();
+        }
+        CorsMode::Strict => {
+            for origin in &config.cors_origins {
+                cors = cors.allowed_origin(origin);
+            }
+            cors = cors
+                .allowed_methods(config.cors_methods.iter().map(String::as_str))
+                .allowed_headers(config.cors_headers.iter().map(String::as_str))
+                .allowed_header(config.tenant_header.as_str());
+            if config.cors_credentials {
+                cors = cors.supports_credentials();
+            }
+        }
+    }
+    Condition::new(enabled, cors.finish())
+}
@@ -1,111 +1,344 @@
 // server/src/db_mongo.rs
 
 use bson::oid::ObjectId;
-use bson::{doc, from_bson, to_bson, Bson, Document};
+use bson::{doc, from_bson, Bson, Document};
 
 use crate::errors::MyError;
-use shared::{InsertablePers, Person};
+use crate::metrics;
+use crate::migrations::{upgrade_person_document, CURRENT_SCHEMA_VERSION};
+use shared::{InsertablePers, Person, PersonId};
 
-use mongodb::{Client, Collection};
+use mongodb::options::{ClientOptions, FindOptions};
+use mongodb::{Client, Collection, Database};
+use once_cell::sync::OnceCell;
 use r2d2::PooledConnection;
 use r2d2_mongodb::{ConnectionOptions, MongodbConnectionManager};
 
-pub(crate) type Pool = r2d2::Pool<MongodbConnectionManager>;
+pub type Pool = r2d2::Pool<MongodbConnectionManager>;
 pub struct Conn(pub PooledConnection<MongodbConnectionManager>);
 
+pub const DEFAULT_MONGO_URI: &str = "mongodb://localhost:27017/";
+pub const DEFAULT_DATABASE: &str = "seed";
+// le port d'un serveur donné sans port dans l'uri
+const DEFAULT_MONGO_PORT: u16 = 27017;
+
+///
+/// l'adresse du serveur MongoDB et le nom de la base
+/// fixés une fois au démarrage par configure()
+///
+#[derive(Debug, Clone)]
+pub struct MongoSettings {
+    pub uri: String,
+    pub database: String,
+}
+
+static SETTINGS: OnceCell<MongoSettings> = OnceCell::new();
+
+///
+/// choisit le serveur et la base utilisés par toutes les fonctions de ce module
+/// à appeler avant le premier accès ; les appels suivants sont ignorés
+///
+pub fn configure(uri: &str, database: &str) {
+    let _ = SETTINGS.set(MongoSettings {
+        uri: uri.to_owned(),
+        database: database.to_owned(),
+    });
+}
+
+pub fn settings() -> &'static MongoSettings {
+    SETTINGS.get_or_init(|| MongoSettings {
+        uri: DEFAULT_MONGO_URI.to_owned(),
+        database: DEFAULT_DATABASE.to_owned(),
+    })
+}
+
+///
+/// les options du pool : les serveurs et l'utilisateur de l'uri, la base configurée
+/// l'uri est lue par le driver, comme pour Client::with_uri_str
+///
+pub fn connection_options(uri: &str, database: &str) -> Result<ConnectionOptions, MyError> {
+    let parsed = ClientOptions::parse(uri)?;
+    let mut builder = ConnectionOptions::builder();
+    for host in &parsed.hosts {
+        builder.with_host(&host.hostname, host.port.unwrap_or(DEFAULT_MONGO_PORT));
+    }
+    if let Some(credential) = &parsed.credential {
+        if let (Some(username), Some(password)) = (&credential.username, &credential.password) {
+            builder.with_auth(username, password);
+        }
+    }
+    Ok(builder.with_db(database).build())
+}
+
 /*
     create a connection pool of mongodb connections to allow a lot of users to modify db at same time.
 */
 pub fn init_pool() -> Pool {
-    let manager = MongodbConnectionManager::new(
-        ConnectionOptions::builder()
-            .with_host("localhost", 27017)
-            .with_db("local")
-            .build(),
-    );
+    let options = match connection_options(&settings().uri, &settings().database) {
+        Ok(options) => options,
+        Err(e) => panic!("Error: invalid mongodb uri {}: {}", settings().uri, e),
+    };
+    let manager = MongodbConnectionManager::new(options);
     match Pool::builder().max_size(8).build(manager) {
         Ok(pool) => pool,
         Err(e) => panic!("Error: failed to create mongodb pool {}", e),
     }
 }
 
+static POOL: OnceCell<Pool> = OnceCell::new();
+
+///
+/// le pool du serveur, créé au premier appel
+/// /readyz regarde son état
+///
+pub fn pool() -> &'static Pool {
+    POOL.get_or_init(init_pool)
+}
+
+///
+/// le pool s'il est déjà ouvert, sans l'ouvrir (pour /metrics)
+///
+pub fn opened_pool() -> Option<&'static Pool> {
+    POOL.get()
+}
+
 pub fn open_pool_connection() -> Result<Conn, r2d2::Error> {
-    let pool = init_pool();
-    let db = pool.get();
+    let db = metrics::observe_pool_wait("mongo", || pool().get());
     Ok(Conn(db?))
 }
 
 pub fn get_collection() -> Result<Collection, MyError> {
-    let client = Client::with_uri_str("mongodb://localhost:27017/")?;
-    let db = client.database("local");
-    let collection = db.collection("Persons");
+    get_named_collection("Persons")
+}
+
+pub fn get_named_collection(name: &str) -> Result<Collection, MyError> {
+    let collection = get_database()?.collection(name);
     Ok(collection)
 }
 
-pub fn add_person(pers: Person) -> Result<Person, MyError> {
-    let coll = get_collection()?;
-    let insertable = InsertablePers::from_person(pers);
-    let ret_val = insertable.clone();
-    let value = doc! {"nom" : insertable.nom, "prenom" : insertable.prenom};
-    let result = coll.insert_one(value, None)?;
+pub fn get_database() -> Result<Database, MyError> {
+    get_database_named(&settings().database)
+}
+
+pub fn get_database_named(name: &str) -> Result<Database, MyError> {
+    let client = Client::with_uri_str(&settings().uri)?;
+    Ok(client.database(name))
+}
+
+///
+/// décode un document de la collection Persons
+/// les documents d'un ancien schéma sont mis à jour en mémoire avant
+/// le champ _id devient l'id de la personne, la forme hexadécimale de l'ObjectId
+///
+pub fn person_from_document(doc: Document) -> Result<Person, MyError> {
+    let mut doc = upgrade_person_document(doc);
+    match doc.remove("_id") {
+        Some(Bson::ObjectId(oid)) => {
+            doc.insert("id", oid.to_hex());
+        }
+        Some(other) => {
+            doc.insert("id", other);
+        }
+        None => {}
+    }
+    Ok(from_bson::<Person>(Bson::Document(doc))?)
+}
 
-    let res = bson::from_bson(result.inserted_id);
+///
+/// l'ObjectId MongoDB d'un PersonId
+///
+pub fn object_id(id: &PersonId) -> Result<ObjectId, MyError> {
+    Ok(id.to_object_id()?)
+}
+
+// échappe les caractères spéciaux pour chercher un texte dans une regex Mongo
+fn escape_regex(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "\\^$.|?*+()[]{}".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+///
+/// filtre Mongo : les champs contiennent le texte, sans tenir compte de la casse
+///
+pub fn person_filter(nom: Option<&str>, prenom: Option<&str>) -> Document {
+    let mut filter = Document::new();
+    if let Some(nom) = nom {
+        filter.insert("nom", doc! {"$regex": escape_regex(nom), "$options": "i"});
+    }
+    if let Some(prenom) = prenom {
+        filter.insert(
+            "prenom",
+            doc! {"$regex": escape_regex(prenom), "$options": "i"},
+        );
+    }
+    filter
+}
+
+///
+/// la collection Persons vue par un locataire (tenant)
+/// avec tenant_id, chaque requête est limitée aux documents de ce locataire
+/// c'est ici que l'isolation entre locataires est garantie
+///
+pub struct PersonsScope {
+    pub collection: Collection,
+    pub tenant_id: Option<String>,
+}
+
+impl PersonsScope {
+    ///
+    /// la collection Persons de la base configurée, sans locataire
+    ///
+    pub fn default_scope() -> Result<Self, MyError> {
+        Ok(Self {
+            collection: get_collection()?,
+            tenant_id: None,
+        })
+    }
+
+    ///
+    /// ajoute le tenant_id au filtre ou au document
+    ///
+    pub fn scoped(&self, mut doc: Document) -> Document {
+        if let Some(tenant_id) = &self.tenant_id {
+            doc.insert("tenant_id", tenant_id.clone());
+        }
+        doc
+    }
+
+    pub fn add_person(&self, pers: Person) -> Result<Person, MyError> {
+        metrics::observe_db("mongo", "add_person", || {
+            let insertable = InsertablePers::from_person(pers);
+            let ret_val = insertable.clone();
+            let value = self.scoped(doc! {"nom" : insertable.nom, "prenom" : insertable.prenom,
+            "schema_version": CURRENT_SCHEMA_VERSION});
+            let result = self.collection.insert_one(value, None)?;
 
-    match res {
-        Ok(res) => {
             let added_person = Person {
-                id: res,
+                id: result
+                    .inserted_id
+                    .as_object_id()
+                    .map(|oid| PersonId::from(oid.clone())),
                 nom: ret_val.nom,
                 prenom: ret_val.prenom,
             };
             Ok(added_person)
-        }
-        Err(err) => Err(err.into()),
+        })
+    }
+
+    pub fn get_list_persons(&self) -> Result<Vec<Person>, MyError> {
+        metrics::observe_db("mongo", "get_list_persons", || {
+            let cursor = self.collection.find(Some(self.scoped(doc! {})), None)?;
+            let res: Result<Vec<_>, MyError> =
+                cursor.map(|row| person_from_document(row?)).collect();
+            res
+        })
+    }
+
+    ///
+    /// cherche des personnes avec un filtre, par pages
+    /// renvoie la page demandée et le nombre total de personnes trouvées
+    ///
+    pub fn find_persons(
+        &self,
+        filter: Document,
+        skip: i64,
+        limit: i64,
+    ) -> Result<(Vec<Person>, i64), MyError> {
+        metrics::observe_db("mongo", "find_persons", || {
+            let filter = self.scoped(filter);
+            let total = self
+                .collection
+                .count_documents(Some(filter.clone()), None)?;
+            let options = FindOptions::builder()
+                .sort(doc! {"nom": 1, "prenom": 1})
+                .skip(skip)
+                .limit(limit)
+                .build();
+            let cursor = self.collection.find(Some(filter), Some(options))?;
+            let res: Result<Vec<_>, MyError> =
+                cursor.map(|row| person_from_document(row?)).collect();
+            Ok((res?, total))
+        })
+    }
+
+    pub fn get_person_by_id(&self, pers_id: &str) -> Result<Option<Person>, MyError> {
+        metrics::observe_db("mongo", "get_person_by_id", || {
+            let cursor: Option<Document> = self.collection.find_one(
+                Some(self.scoped(doc! { "_id": ObjectId::with_string(pers_id)?})),
+                None,
+            )?;
+            cursor
+                .map(person_from_document)
+                .map_or(Ok(None), |v| v.map(Some))
+        })
+    }
+
+    pub fn modify_person_by_id(
+        &self,
+        pers_id: &str,
+        modifyed_person: Person,
+    ) -> Result<Option<Person>, MyError> {
+        metrics::observe_db("mongo", "modify_person_by_id", || {
+            let cursor: Option<Document> = self.collection.find_one_and_replace(
+                self.scoped(doc! {"_id": ObjectId::with_string(pers_id)?}),
+                self.scoped(doc! {"_id": ObjectId::with_string(pers_id)?,
+                "nom" : modifyed_person.nom,
+                "prenom" : modifyed_person.prenom,
+                "schema_version": CURRENT_SCHEMA_VERSION }),
+                Some(Default::default()),
+            )?;
+            cursor
+                .map(person_from_document)
+                .map_or(Ok(None), |v| v.map(Some))
+        })
+    }
+
+    pub fn delete_person(&self, pers_id: &str) -> Result<Option<Person>, MyError> {
+        metrics::observe_db("mongo", "delete_person", || {
+            let cursor: Option<Document> = self.collection.find_one_and_delete(
+                self.scoped(doc! {"_id": ObjectId::with_string(pers_id)?}),
+                Some(Default::default()),
+            )?;
+            cursor
+                .map(person_from_document)
+                .map_or(Ok(None), |v| v.map(Some))
+        })
     }
 }
 
+pub fn add_person(pers: Person) -> Result<Person, MyError> {
+    PersonsScope::default_scope()?.add_person(pers)
+}
+
 pub fn get_list_persons() -> Result<Vec<Person>, MyError> {
-    let cursor = get_collection()?.find(None, None)?;
-    let res: Result<Vec<_>, _> = cursor
-        .map(|row| row.and_then(|item| Ok(from_bson::<Person>(bson::Bson::Document(item))?)))
-        .collect();
-    Ok(res?)
+    PersonsScope::default_scope()?.get_list_persons()
+}
+
+pub fn find_persons(
+    filter: Document,
+    skip: i64,
+    limit: i64,
+) -> Result<(Vec<Person>, i64), MyError> {
+    PersonsScope::default_scope()?.find_persons(filter, skip, limit)
 }
 
 pub fn get_person_by_id(pers_id: &str) -> Result<Option<Person>, MyError> {
-    let coll = get_collection()?;
-    let cursor: Option<Document> =
-        coll.find_one(Some(doc! { "_id": ObjectId::with_string(pers_id)?}), None)?;
-    cursor
-        .map(|doc| Ok(bson::from_bson::<Person>(bson::Bson::Document(doc))?))
-        .map_or(Ok(None), |v| v.map(Some))
+    PersonsScope::default_scope()?.get_person_by_id(pers_id)
 }
 
 pub fn modify_person_by_id(
     pers_id: &str,
     modifyed_person: Person,
 ) -> Result<Option<Person>, MyError> {
-    let coll = get_collection()?;
-    let cursor: Option<Document> = coll.find_one_and_replace(
-        doc! {"_id": ObjectId::with_string(pers_id)?},
-        doc! {"_id": ObjectId::with_string(pers_id)?,
-        "nom" : modifyed_person.nom,
-        "prenom" : modifyed_person.prenom },
-        Some(Default::default()),
-    )?;
-    cursor
-        .map(|doc| Ok(bson::from_bson::<Person>(bson::Bson::Document(doc))?))
-        .map_or(Ok(None), |v| v.map(Some))
-}
-
-pub fn delete_person(pers: Person) -> Result<Option<Person>, MyError> {
-    let coll = get_collection()?;
-    if let Bson::Document(doc) = to_bson(&pers)? {
-        let cursor: Option<Document> = coll.find_one_and_delete(doc, Some(Default::default()))?;
-        cursor
-            .map(|doc| Ok(bson::from_bson::<Person>(bson::Bson::Document(doc))?))
-            .map_or(Ok(None), |v| v.map(Some))
-    } else {
-        Ok(None)
-    }
+    PersonsScope::default_scope()?.modify_person_by_id(pers_id, modifyed_person)
+}
+
+pub fn delete_person(pers_id: &str) -> Result<Option<Person>, MyError> {
+    PersonsScope::default_scope()?.delete_person(pers_id)
 }
@@ -0,0 +1,711 @@
+// server/src/db_postgres.rs
+
+use std::cell::RefCell;
+
+use bson::oid::ObjectId;
+use once_cell::sync::OnceCell;
+use postgres::{GenericClient, NoTls, Row, Transaction};
+use r2d2_postgres::PostgresConnectionManager;
+
+use crate::errors::MyError;
+use crate::metrics;
+use shared::{ApiKey, ComplianceEntry, Person, PersonId, Scope, User};
+
+pub type PostgresPool = r2d2::Pool<PostgresConnectionManager<NoTls>>;
+type PostgresConn = r2d2::PooledConnection<PostgresConnectionManager<NoTls>>;
+
+pub const DEFAULT_POSTGRES_URL: &str = "postgres://postgres@localhost/seed";
+
+static URL: OnceCell<String> = OnceCell::new();
+static POOL: OnceCell<PostgresPool> = OnceCell::new();
+
+///
+/// les migrations du schéma PostgreSQL, dans l'ordre
+/// les versions appliquées sont gardées dans schema_migrations
+///
+const MIGRATIONS: &[&str] = &[
+    // 1 : la table des personnes ; tenant_id vaut '' sans locataire
+    "CREATE TABLE persons (
+        tenant_id TEXT NOT NULL DEFAULT '',
+        id TEXT NOT NULL,
+        nom TEXT NOT NULL,
+        prenom TEXT NOT NULL,
+        PRIMARY KEY (tenant_id, id)
+    );
+    CREATE INDEX persons_nom_prenom ON persons (tenant_id, nom, prenom);",
+    // 2 : la correspondance des id pour seedctl migrate-store
+    "CREATE TABLE person_id_map (
+        source TEXT NOT NULL,
+        tenant_id TEXT NOT NULL DEFAULT '',
+        source_id TEXT NOT NULL,
+        target_id TEXT NOT NULL,
+        migrated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+        PRIMARY KEY (source, tenant_id, source_id)
+    );",
+    // 3 : les clés d'API ; les dates sont en millisecondes depuis epoch, comme avec MongoDB
+    "CREATE TABLE api_keys (
+        id TEXT PRIMARY KEY,
+        name TEXT NOT NULL,
+        prefix TEXT NOT NULL UNIQUE,
+        hash TEXT NOT NULL,
+        scopes TEXT[] NOT NULL,
+        tenant_id TEXT,
+        created_at BIGINT NOT NULL,
+        expires_at BIGINT,
+        last_used_at BIGINT,
+        revoked_at BIGINT
+    );",
+    // 4 : les utilisateurs OpenID Connect
+    "CREATE TABLE users (
+        id TEXT PRIMARY KEY,
+        subject TEXT NOT NULL UNIQUE,
+        name TEXT NOT NULL,
+        email TEXT,
+        groups TEXT[] NOT NULL,
+        scopes TEXT[] NOT NULL,
+        tenant_id TEXT,
+        created_at BIGINT NOT NULL,
+        last_login_at BIGINT NOT NULL
+    );",
+    // 5 : le journal des demandes RGPD ; seq garde l'ordre des entrées d'une même milliseconde
+    "CREATE TABLE compliance_log (
+        seq BIGSERIAL PRIMARY KEY,
+        id TEXT NOT NULL UNIQUE,
+        tenant_id TEXT NOT NULL DEFAULT '',
+        person_id TEXT NOT NULL,
+        action TEXT NOT NULL
+            CHECK (action IN ('export', 'erasure_started', 'erased', 'erasure_failed')),
+        actor TEXT NOT NULL,
+        at BIGINT NOT NULL,
+        details TEXT
+    );
+    CREATE INDEX compliance_log_person ON compliance_log (tenant_id, person_id);",
+];
+
+///
+/// la version du schéma attendue par cette version du serveur
+///
+pub const SCHEMA_VERSION: i32 = MIGRATIONS.len() as i32;
+
+///
+/// choisit le serveur PostgreSQL
+/// à appeler avant le premier accès ; les appels suivants sont ignorés
+///
+pub fn configure(url: &str) {
+    let _ = URL.set(url.to_owned());
+}
+
+pub fn url() -> &'static str {
+    URL.get_or_init(|| DEFAULT_POSTGRES_URL.to_owned())
+}
+
+///
+/// applique les migrations qui manquent
+/// renvoie la version du schéma après coup
+///
+pub fn migrate(conn: &mut postgres::Client) -> Result<i32, MyError> {
+    conn.batch_execute(
+        "CREATE TABLE IF NOT EXISTS schema_migrations (
+            version INTEGER PRIMARY KEY,
+            applied_at TIMESTAMPTZ NOT NULL DEFAULT now()
+        )",
+    )?;
+    let current: i32 = conn
+        .query_one(
+            "SELECT COALESCE(MAX(version), 0) FROM schema_migrations",
+            &[],
+        )?
+        .get(0);
+    for (index, sql) in MIGRATIONS.iter().enumerate().skip(current.max(0) as usize) {
+        let version = index as i32 + 1;
+        let mut tx = conn.transaction()?;
+        tx.batch_execute(sql)?;
+        tx.execute(
+            "INSERT INTO schema_migrations (version) VALUES ($1)",
+            &[&version],
+        )?;
+        tx.commit()?;
+    }
+    Ok(MIGRATIONS.len() as i32)
+}
+
+///
+/// la version du schéma d'une base déjà ouverte
+///
+pub fn schema_version(conn: &mut postgres::Client) -> Result<i32, MyError> {
+    Ok(conn
+        .query_one(
+            "SELECT COALESCE(MAX(version), 0) FROM schema_migrations",
+            &[],
+        )?
+        .get(0))
+}
+
+///
+/// le pool de connexions, ouvert et migré au premier appel
+///
+pub fn pool() -> Result<&'static PostgresPool, MyError> {
+    POOL.get_or_try_init(|| {
+        let config = url()
+            .parse::<postgres::Config>()
+            .map_err(MyError::Postgres)?;
+        let pool = r2d2::Pool::builder()
+            .max_size(8)
+            .build(PostgresConnectionManager::new(config, NoTls))?;
+        migrate(&mut pool.get()?)?;
+        Ok(pool)
+    })
+}
+
+///
+/// le pool s'il est déjà ouvert, sans l'ouvrir (pour /metrics)
+///
+pub fn opened_pool() -> Option<&'static PostgresPool> {
+    POOL.get()
+}
+
+fn conn() -> Result<PostgresConn, MyError> {
+    let pool = pool()?;
+    Ok(metrics::observe_pool_wait("postgres", || pool.get())?)
+}
+
+fn person_from_row(row: &Row) -> Person {
+    Person {
+        id: Some(PersonId::new(row.get::<_, String>(0))),
+        nom: row.get(1),
+        prenom: row.get(2),
+    }
+}
+
+// échappe % et _ pour chercher un texte avec ILIKE ... ESCAPE '\'
+fn like_pattern(text: &str) -> String {
+    let mut pattern = String::with_capacity(text.len() + 2);
+    pattern.push('%');
+    for c in text.chars() {
+        if c == '%' || c == '_' || c == '\\' {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern.push('%');
+    pattern
+}
+
+///
+/// les personnes d'un locataire dans PostgreSQL
+/// les deux modes multi-locataires utilisent la colonne tenant_id
+///
+pub struct PostgresPersons {
+    pub tenant_id: String,
+}
+
+impl PostgresPersons {
+    pub fn new(tenant_id: Option<String>) -> Self {
+        Self {
+            tenant_id: tenant_id.unwrap_or_default(),
+        }
+    }
+
+    pub fn list(&self) -> Result<Vec<Person>, MyError> {
+        let rows = conn()?.query(
+            "SELECT id, nom, prenom FROM persons WHERE tenant_id = $1 ORDER BY nom, prenom",
+            &[&self.tenant_id],
+        )?;
+        Ok(rows.iter().map(person_from_row).collect())
+    }
+
+    ///
+    /// les personnes dont le nom et le prénom contiennent le texte, par pages
+    /// renvoie la page et le nombre total de personnes trouvées
+    ///
+    pub fn search(
+        &self,
+        nom: Option<&str>,
+        prenom: Option<&str>,
+        skip: i64,
+        limit: i64,
+    ) -> Result<(Vec<Person>, i64), MyError> {
+        let mut conn = conn()?;
+        let nom = like_pattern(nom.unwrap_or(""));
+        let prenom = like_pattern(prenom.unwrap_or(""));
+        let filter =
+            "WHERE tenant_id = $1 AND nom ILIKE $2 ESCAPE '\\' AND prenom ILIKE $3 ESCAPE '\\'";
+
+        let total: i64 = conn
+            .query_one(
+                format!("SELECT COUNT(*) FROM persons {}", filter).as_str(),
+                &[&self.tenant_id, &nom, &prenom],
+            )?
+            .get(0);
+        let rows = conn.query(
+            format!(
+                "SELECT id, nom, prenom FROM persons {} ORDER BY nom, prenom LIMIT $4 OFFSET $5",
+                filter
+            )
+            .as_str(),
+            &[&self.tenant_id, &nom, &prenom, &limit, &skip.max(0)],
+        )?;
+        Ok((rows.iter().map(person_from_row).collect(), total))
+    }
+
+    pub fn get(&self, id: &str) -> Result<Option<Person>, MyError> {
+        let rows = conn()?.query(
+            "SELECT id, nom, prenom FROM persons WHERE tenant_id = $1 AND id = $2",
+            &[&self.tenant_id, &id],
+        )?;
+        Ok(rows.first().map(person_from_row))
+    }
+
+    fn writer<'a, C: GenericClient>(&'a self, client: &'a mut C) -> PostgresWriter<'a, C> {
+        PostgresWriter {
+            client: RefCell::new(client),
+            tenant_id: &self.tenant_id,
+        }
+    }
+
+    ///
+    /// ajoute la personne avec un nouvel id, de la même forme que ceux de MongoDB
+    ///
+    pub fn add(&self, person: Person) -> Result<Person, MyError> {
+        self.writer(&mut *conn()?).add(person)
+    }
+
+    ///
+    /// remplace la personne ; renvoie l'ancienne, comme db_mongo
+    ///
+    pub fn modify(&self, id: &str, person: Person) -> Result<Option<Person>, MyError> {
+        let mut conn = conn()?;
+        let mut tx = conn.transaction()?;
+        let previous = self.writer(&mut tx).modify(id, person)?;
+        tx.commit()?;
+        Ok(previous)
+    }
+
+    pub fn delete(&self, id: &str) -> Result<Option<Person>, MyError> {
+        self.writer(&mut *conn()?).delete(id)
+    }
+
+    ///
+    /// appelle run avec les écritures d'une transaction, validée si run renvoie true
+    /// sinon, ou en cas d'erreur, rien n'est écrit
+    ///
+    pub fn transaction(
+        &self,
+        run: impl FnOnce(&PostgresWriter<Transaction>) -> bool,
+    ) -> Result<bool, MyError> {
+        let mut conn = conn()?;
+        let mut tx = conn.transaction()?;
+        let commit = run(&self.writer(&mut tx));
+        if commit {
+            tx.commit()?;
+        }
+        Ok(commit)
+    }
+
+    pub fn upsert(&self, person: Person) -> Result<(), MyError> {
+        let id = match &person.id {
+            Some(id) => id.clone(),
+            None => return self.add(person).map(|_| ()),
+        };
+        conn()?.execute(
+            "INSERT INTO persons (tenant_id, id, nom, prenom) VALUES ($1, $2, $3, $4)
+             ON CONFLICT (tenant_id, id) DO UPDATE SET nom = excluded.nom, prenom = excluded.prenom",
+            &[&self.tenant_id, &id.as_str(), &person.nom, &person.prenom],
+        )?;
+        Ok(())
+    }
+
+    pub fn clear(&self) -> Result<(), MyError> {
+        conn()?.execute(
+            "DELETE FROM persons WHERE tenant_id = $1",
+            &[&self.tenant_id],
+        )?;
+        Ok(())
+    }
+
+    ///
+    /// efface les personnes du locataire et écrit celles données, dans une transaction
+    /// les personnes sans id sont ignorées : PersonStore::replace_all leur en donne un
+    ///
+    pub fn replace_all(&self, persons: Vec<Person>) -> Result<(), MyError> {
+        let mut conn = conn()?;
+        let mut tx = conn.transaction()?;
+        tx.execute(
+            "DELETE FROM persons WHERE tenant_id = $1",
+            &[&self.tenant_id],
+        )?;
+        for person in &persons {
+            let id = match &person.id {
+                Some(id) => id,
+                None => continue,
+            };
+            tx.execute(
+                "INSERT INTO persons (tenant_id, id, nom, prenom) VALUES ($1, $2, $3, $4)
+                 ON CONFLICT (tenant_id, id) DO UPDATE SET nom = excluded.nom, prenom = excluded.prenom",
+                &[&self.tenant_id, &id.as_str(), &person.nom, &person.prenom],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+///
+/// les écritures sur les personnes d'un locataire, avec une connexion ou une transaction
+/// PostgresPersons::transaction les fait dans une transaction (lots d'opérations)
+///
+pub struct PostgresWriter<'a, C> {
+    client: RefCell<&'a mut C>,
+    tenant_id: &'a str,
+}
+
+impl<C: GenericClient> PostgresWriter<'_, C> {
+    pub fn add(&self, person: Person) -> Result<Person, MyError> {
+        let id = PersonId::from(ObjectId::new()?);
+        self.client.borrow_mut().execute(
+            "INSERT INTO persons (tenant_id, id, nom, prenom) VALUES ($1, $2, $3, $4)",
+            &[&self.tenant_id, &id.as_str(), &person.nom, &person.prenom],
+        )?;
+        Ok(Person {
+            id: Some(id),
+            ..person
+        })
+    }
+
+    // à appeler dans une transaction : la ligne reste verrouillée jusqu'à sa fin
+    pub fn modify(&self, id: &str, person: Person) -> Result<Option<Person>, MyError> {
+        let mut client = self.client.borrow_mut();
+        let previous = client
+            .query(
+                "SELECT id, nom, prenom FROM persons WHERE tenant_id = $1 AND id = $2 FOR UPDATE",
+                &[&self.tenant_id, &id],
+            )?
+            .first()
+            .map(person_from_row);
+        if previous.is_some() {
+            client.execute(
+                "UPDATE persons SET nom = $3, prenom = $4 WHERE tenant_id = $1 AND id = $2",
+                &[&self.tenant_id, &id, &person.nom, &person.prenom],
+            )?;
+        }
+        Ok(previous)
+    }
+
+    pub fn delete(&self, id: &str) -> Result<Option<Person>, MyError> {
+        let rows = self.client.borrow_mut().query(
+            "DELETE FROM persons WHERE tenant_id = $1 AND id = $2 RETURNING id, nom, prenom",
+            &[&self.tenant_id, &id],
+        )?;
+        Ok(rows.first().map(person_from_row))
+    }
+}
+
+///
+/// la table person_id_map : l'id d'origine de chaque personne copiée
+/// source est le nom du stockage d'origine (mongo, sqlite)
+///
+pub struct PostgresIdMap {
+    pub source: String,
+    pub tenant_id: String,
+}
+
+impl PostgresIdMap {
+    pub fn new(source: &str, tenant_id: Option<String>) -> Self {
+        Self {
+            source: source.to_owned(),
+            tenant_id: tenant_id.unwrap_or_default(),
+        }
+    }
+
+    pub fn get(&self, source_id: &str) -> Result<Option<String>, MyError> {
+        let rows = conn()?.query(
+            "SELECT target_id FROM person_id_map
+             WHERE source = $1 AND tenant_id = $2 AND source_id = $3",
+            &[&self.source, &self.tenant_id, &source_id],
+        )?;
+        Ok(rows.first().map(|row| row.get(0)))
+    }
+
+    pub fn insert(&self, source_id: &str, target_id: &str) -> Result<(), MyError> {
+        conn()?.execute(
+            "INSERT INTO person_id_map (source, tenant_id, source_id, target_id)
+             VALUES ($1, $2, $3, $4)
+             ON CONFLICT (source, tenant_id, source_id) DO UPDATE SET target_id = excluded.target_id",
+            &[&self.source, &self.tenant_id, &source_id, &target_id],
+        )?;
+        Ok(())
+    }
+
+    pub fn all(&self) -> Result<Vec<(String, String)>, MyError> {
+        let rows = conn()?.query(
+            "SELECT source_id, target_id FROM person_id_map
+             WHERE source = $1 AND tenant_id = $2 ORDER BY source_id",
+            &[&self.source, &self.tenant_id],
+        )?;
+        Ok(rows.iter().map(|row| (row.get(0), row.get(1))).collect())
+    }
+}
+
+const API_KEY_COLUMNS: &str = "id, name, prefix, hash, scopes, tenant_id, \
+                               created_at, expires_at, last_used_at, revoked_at";
+
+fn api_key_from_row(row: &Row) -> ApiKey {
+    ApiKey {
+        id: row.get(0),
+        name: row.get(1),
+        prefix: row.get(2),
+        hash: row.get(3),
+        scopes: row
+            .get::<_, Vec<String>>(4)
+            .iter()
+            .filter_map(|scope| scope.parse().ok())
+            .collect(),
+        tenant: row.get(5),
+        created_at: row.get(6),
+        expires_at: row.get(7),
+        last_used_at: row.get(8),
+        revoked_at: row.get(9),
+    }
+}
+
+fn scope_names(scopes: &[Scope]) -> Vec<&'static str> {
+    scopes.iter().map(|scope| scope.name()).collect()
+}
+
+///
+/// la table api_keys
+///
+pub struct PostgresApiKeys;
+
+impl PostgresApiKeys {
+    pub fn insert(&self, key: &ApiKey) -> Result<(), MyError> {
+        conn()?.execute(
+            format!(
+                "INSERT INTO api_keys ({}) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)",
+                API_KEY_COLUMNS
+            )
+            .as_str(),
+            &[
+                &key.id,
+                &key.name,
+                &key.prefix,
+                &key.hash,
+                &scope_names(&key.scopes),
+                &key.tenant,
+                &key.created_at,
+                &key.expires_at,
+                &key.last_used_at,
+                &key.revoked_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn list(&self) -> Result<Vec<ApiKey>, MyError> {
+        let rows = conn()?.query(
+            format!(
+                "SELECT {} FROM api_keys ORDER BY created_at",
+                API_KEY_COLUMNS
+            )
+            .as_str(),
+            &[],
+        )?;
+        Ok(rows.iter().map(api_key_from_row).collect())
+    }
+
+    fn find(&self, column: &str, value: &str) -> Result<Option<ApiKey>, MyError> {
+        let rows = conn()?.query(
+            format!(
+                "SELECT {} FROM api_keys WHERE {} = $1",
+                API_KEY_COLUMNS, column
+            )
+            .as_str(),
+            &[&value],
+        )?;
+        Ok(rows.first().map(api_key_from_row))
+    }
+
+    pub fn get(&self, id: &str) -> Result<Option<ApiKey>, MyError> {
+        self.find("id", id)
+    }
+
+    pub fn find_by_prefix(&self, prefix: &str) -> Result<Option<ApiKey>, MyError> {
+        self.find("prefix", prefix)
+    }
+
+    pub fn update(&self, key: &ApiKey) -> Result<bool, MyError> {
+        let changed = conn()?.execute(
+            "UPDATE api_keys SET name = $2, prefix = $3, hash = $4, scopes = $5, tenant_id = $6,
+             expires_at = $7, last_used_at = $8, revoked_at = $9 WHERE id = $1",
+            &[
+                &key.id,
+                &key.name,
+                &key.prefix,
+                &key.hash,
+                &scope_names(&key.scopes),
+                &key.tenant,
+                &key.expires_at,
+                &key.last_used_at,
+                &key.revoked_at,
+            ],
+        )?;
+        Ok(changed > 0)
+    }
+
+    pub fn touch(&self, id: &str, at: i64) -> Result<(), MyError> {
+        conn()?.execute(
+            "UPDATE api_keys SET last_used_at = $2 WHERE id = $1",
+            &[&id, &at],
+        )?;
+        Ok(())
+    }
+}
+
+const USER_COLUMNS: &str =
+    "id, subject, name, email, groups, scopes, tenant_id, created_at, last_login_at";
+
+fn user_from_row(row: &Row) -> User {
+    User {
+        id: row.get(0),
+        subject: row.get(1),
+        name: row.get(2),
+        email: row.get(3),
+        groups: row.get(4),
+        scopes: row
+            .get::<_, Vec<String>>(5)
+            .iter()
+            .filter_map(|scope| scope.parse().ok())
+            .collect(),
+        tenant: row.get(6),
+        created_at: row.get(7),
+        last_login_at: row.get(8),
+    }
+}
+
+///
+/// la table users
+///
+pub struct PostgresUsers;
+
+impl PostgresUsers {
+    fn find(&self, column: &str, value: &str) -> Result<Option<User>, MyError> {
+        let rows = conn()?.query(
+            format!("SELECT {} FROM users WHERE {} = $1", USER_COLUMNS, column).as_str(),
+            &[&value],
+        )?;
+        Ok(rows.first().map(user_from_row))
+    }
+
+    pub fn get(&self, id: &str) -> Result<Option<User>, MyError> {
+        self.find("id", id)
+    }
+
+    pub fn find_by_subject(&self, subject: &str) -> Result<Option<User>, MyError> {
+        self.find("subject", subject)
+    }
+
+    pub fn insert(&self, user: &User) -> Result<(), MyError> {
+        conn()?.execute(
+            format!(
+                "INSERT INTO users ({}) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+                USER_COLUMNS
+            )
+            .as_str(),
+            &[
+                &user.id,
+                &user.subject,
+                &user.name,
+                &user.email,
+                &user.groups,
+                &scope_names(&user.scopes),
+                &user.tenant,
+                &user.created_at,
+                &user.last_login_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn update(&self, user: &User) -> Result<bool, MyError> {
+        let changed = conn()?.execute(
+            "UPDATE users SET name = $2, email = $3, groups = $4, scopes = $5, tenant_id = $6,
+             last_login_at = $7 WHERE id = $1",
+            &[
+                &user.id,
+                &user.name,
+                &user.email,
+                &user.groups,
+                &scope_names(&user.scopes),
+                &user.tenant,
+                &user.last_login_at,
+            ],
+        )?;
+        Ok(changed > 0)
+    }
+}
+
+const COMPLIANCE_COLUMNS: &str = "id, tenant_id, person_id, action, actor, at, details";
+
+fn compliance_from_row(row: &Row) -> Result<ComplianceEntry, MyError> {
+    let tenant: String = row.get(1);
+    Ok(ComplianceEntry {
+        id: row.get(0),
+        tenant: Some(tenant).filter(|tenant| !tenant.is_empty()),
+        person_id: row.get(2),
+        // une action inconnue vient d'un serveur plus récent
+        action: row
+            .get::<_, String>(3)
+            .parse()
+            .map_err(|e: String| MyError::Migration(format!("compliance_log: {}", e)))?,
+        actor: row.get(4),
+        at: row.get(5),
+        details: row.get(6),
+    })
+}
+
+///
+/// la table compliance_log
+///
+pub struct PostgresComplianceLog;
+
+impl PostgresComplianceLog {
+    pub fn append(&self, entry: &ComplianceEntry) -> Result<(), MyError> {
+        conn()?.execute(
+            format!(
+                "INSERT INTO compliance_log ({}) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+                COMPLIANCE_COLUMNS
+            )
+            .as_str(),
+            &[
+                &entry.id,
+                &entry.tenant.as_deref().unwrap_or(""),
+                &entry.person_id,
+                &entry.action.name(),
+                &entry.actor,
+                &entry.at,
+                &entry.details,
+            ],
+        )?;
+        Ok(())
+    }
+
+    ///
+    /// les entrées du locataire, dans l'ordre ; seulement celles de la personne si elle est donnée
+    ///
+    pub fn list(
+        &self,
+        tenant: Option<&str>,
+        person_id: Option<&str>,
+    ) -> Result<Vec<ComplianceEntry>, MyError> {
+        let rows = conn()?.query(
+            format!(
+                "SELECT {} FROM compliance_log
+                 WHERE tenant_id = $1 AND ($2::TEXT IS NULL OR person_id = $2) ORDER BY seq",
+                COMPLIANCE_COLUMNS
+            )
+            .as_str(),
+            &[&tenant.unwrap_or(""), &person_id],
+        )?;
+        rows.iter().map(compliance_from_row).collect()
+    }
+}
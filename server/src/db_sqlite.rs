@@ -0,0 +1,704 @@
+// server/src/db_sqlite.rs
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use bson::oid::ObjectId;
+use once_cell::sync::{Lazy, OnceCell};
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::{params, Connection, OptionalExtension, Row, NO_PARAMS};
+
+use crate::api_keys::{join_scopes, parse_scopes};
+use crate::config::Config;
+use crate::errors::MyError;
+use crate::metrics;
+use shared::{ApiKey, ComplianceEntry, Person, PersonId, User};
+
+pub type SqlitePool = r2d2::Pool<SqliteConnectionManager>;
+
+pub const DEFAULT_SQLITE_PATH: &str = "seed.db";
+
+static PATH: OnceCell<PathBuf> = OnceCell::new();
+
+// un pool par fichier : en mode database, chaque locataire a son fichier
+static POOLS: Lazy<Mutex<HashMap<PathBuf, SqlitePool>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+///
+/// les migrations du schéma SQLite, dans l'ordre
+/// la version atteinte est gardée dans PRAGMA user_version
+///
+const MIGRATIONS: &[&str] = &[
+    // 1 : la table des personnes ; tenant_id vaut '' sans locataire
+    "CREATE TABLE persons (
+        tenant_id TEXT NOT NULL DEFAULT '',
+        id TEXT NOT NULL,
+        nom TEXT NOT NULL,
+        prenom TEXT NOT NULL,
+        PRIMARY KEY (tenant_id, id)
+    );
+    CREATE INDEX persons_nom_prenom ON persons (tenant_id, nom, prenom);",
+    // 2 : les clés d'API, dans le fichier principal ; scopes vaut par exemple 'read,write'
+    "CREATE TABLE api_keys (
+        id TEXT PRIMARY KEY,
+        name TEXT NOT NULL,
+        prefix TEXT NOT NULL UNIQUE,
+        hash TEXT NOT NULL,
+        scopes TEXT NOT NULL,
+        tenant_id TEXT,
+        created_at INTEGER NOT NULL,
+        expires_at INTEGER,
+        last_used_at INTEGER,
+        revoked_at INTEGER
+    );",
+    // 3 : les utilisateurs OpenID Connect ; groups et scopes sont des listes séparées par des virgules
+    "CREATE TABLE users (
+        id TEXT PRIMARY KEY,
+        subject TEXT NOT NULL UNIQUE,
+        name TEXT NOT NULL,
+        email TEXT,
+        groups TEXT NOT NULL,
+        scopes TEXT NOT NULL,
+        tenant_id TEXT,
+        created_at INTEGER NOT NULL,
+        last_login_at INTEGER NOT NULL
+    );",
+    // 4 : le journal des demandes RGPD ; seq garde l'ordre des entrées d'une même milliseconde
+    "CREATE TABLE compliance_log (
+        seq INTEGER PRIMARY KEY AUTOINCREMENT,
+        id TEXT NOT NULL UNIQUE,
+        tenant_id TEXT NOT NULL DEFAULT '',
+        person_id TEXT NOT NULL,
+        action TEXT NOT NULL
+            CHECK (action IN ('export', 'erasure_started', 'erased', 'erasure_failed')),
+        actor TEXT NOT NULL,
+        at INTEGER NOT NULL,
+        details TEXT
+    );
+    CREATE INDEX compliance_log_person ON compliance_log (tenant_id, person_id);",
+];
+
+///
+/// la version du schéma attendue par cette version du serveur
+///
+pub const SCHEMA_VERSION: i32 = MIGRATIONS.len() as i32;
+
+///
+/// choisit le fichier SQLite principal
+/// à appeler avant le premier accès ; les appels suivants sont ignorés
+///
+pub fn configure(path: &str) {
+    let _ = PATH.set(PathBuf::from(path));
+}
+
+///
+/// le fichier choisi ; sans configure() (les tests), on lit SEED_SQLITE_PATH
+///
+pub fn path() -> &'static Path {
+    PATH.get_or_init(|| PathBuf::from(Config::from_env().sqlite_path))
+}
+
+///
+/// le fichier d'un locataire en mode database : seed.db -> seed_acme.db
+///
+pub fn tenant_path(tenant: &str) -> PathBuf {
+    let main = path();
+    let stem = main
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "seed".to_owned());
+    let file_name = match main.extension() {
+        Some(ext) => format!("{}_{}.{}", stem, tenant, ext.to_string_lossy()),
+        None => format!("{}_{}", stem, tenant),
+    };
+    main.with_file_name(file_name)
+}
+
+///
+/// applique les migrations qui manquent
+/// renvoie la version du schéma après coup
+///
+pub fn migrate(conn: &mut Connection) -> Result<i32, MyError> {
+    let current: i32 = conn.query_row("PRAGMA user_version", NO_PARAMS, |row| row.get(0))?;
+    for (index, sql) in MIGRATIONS.iter().enumerate().skip(current.max(0) as usize) {
+        let version = index as i32 + 1;
+        let tx = conn.transaction()?;
+        tx.execute_batch(sql)?;
+        tx.execute_batch(&format!("PRAGMA user_version = {}", version))?;
+        tx.commit()?;
+    }
+    Ok(MIGRATIONS.len() as i32)
+}
+
+///
+/// la version du schéma d'une base déjà ouverte
+///
+pub fn schema_version(conn: &Connection) -> Result<i32, MyError> {
+    Ok(conn.query_row("PRAGMA user_version", NO_PARAMS, |row| row.get(0))?)
+}
+
+///
+/// le pool de connexions d'un fichier, ouvert et migré au premier appel
+///
+pub fn pool(path: &Path) -> Result<SqlitePool, MyError> {
+    let mut pools = POOLS.lock().unwrap();
+    if let Some(pool) = pools.get(path) {
+        return Ok(pool.clone());
+    }
+    let pool = r2d2::Pool::builder()
+        .max_size(8)
+        .build(SqliteConnectionManager::file(path))?;
+    migrate(&mut pool.get()?)?;
+    pools.insert(path.to_owned(), pool.clone());
+    Ok(pool)
+}
+
+///
+/// le pool d'un fichier s'il est déjà ouvert, sans l'ouvrir (pour /metrics)
+///
+pub fn opened_pool(path: &Path) -> Option<SqlitePool> {
+    POOLS.lock().unwrap().get(path).cloned()
+}
+
+fn person_from_row(row: &Row) -> rusqlite::Result<Person> {
+    Ok(Person {
+        id: Some(PersonId::new(row.get::<_, String>(0)?)),
+        nom: row.get(1)?,
+        prenom: row.get(2)?,
+    })
+}
+
+// échappe % et _ pour chercher un texte avec LIKE ... ESCAPE '\'
+fn like_pattern(text: &str) -> String {
+    let mut pattern = String::with_capacity(text.len() + 2);
+    pattern.push('%');
+    for c in text.chars() {
+        if c == '%' || c == '_' || c == '\\' {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern.push('%');
+    pattern
+}
+
+///
+/// les personnes d'un fichier SQLite, vues par un locataire
+/// comme PersonsScope pour MongoDB, chaque requête filtre sur tenant_id
+///
+pub struct SqlitePersons {
+    pub path: PathBuf,
+    pub tenant_id: String,
+}
+
+impl SqlitePersons {
+    pub fn new(path: impl Into<PathBuf>, tenant_id: Option<String>) -> Self {
+        Self {
+            path: path.into(),
+            tenant_id: tenant_id.unwrap_or_default(),
+        }
+    }
+
+    fn conn(&self) -> Result<r2d2::PooledConnection<SqliteConnectionManager>, MyError> {
+        let pool = pool(&self.path)?;
+        Ok(metrics::observe_pool_wait("sqlite", || pool.get())?)
+    }
+
+    pub fn list(&self) -> Result<Vec<Person>, MyError> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, nom, prenom FROM persons WHERE tenant_id = ?1 ORDER BY nom, prenom",
+        )?;
+        let rows = stmt.query_map(params![self.tenant_id], person_from_row)?;
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+
+    ///
+    /// les personnes dont le nom et le prénom contiennent le texte, par pages
+    /// renvoie la page et le nombre total de personnes trouvées
+    ///
+    pub fn search(
+        &self,
+        nom: Option<&str>,
+        prenom: Option<&str>,
+        skip: i64,
+        limit: i64,
+    ) -> Result<(Vec<Person>, i64), MyError> {
+        let conn = self.conn()?;
+        let nom = like_pattern(nom.unwrap_or(""));
+        let prenom = like_pattern(prenom.unwrap_or(""));
+        let filter =
+            "WHERE tenant_id = ?1 AND nom LIKE ?2 ESCAPE '\\' AND prenom LIKE ?3 ESCAPE '\\'";
+
+        let total: i64 = conn.query_row(
+            &format!("SELECT COUNT(*) FROM persons {}", filter),
+            params![self.tenant_id, nom, prenom],
+            |row| row.get(0),
+        )?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT id, nom, prenom FROM persons {} ORDER BY nom, prenom LIMIT ?4 OFFSET ?5",
+            filter
+        ))?;
+        let rows = stmt.query_map(
+            params![self.tenant_id, nom, prenom, limit, skip],
+            person_from_row,
+        )?;
+        Ok((rows.collect::<Result<Vec<_>, _>>()?, total))
+    }
+
+    pub fn get(&self, id: &str) -> Result<Option<Person>, MyError> {
+        Ok(self
+            .conn()?
+            .query_row(
+                "SELECT id, nom, prenom FROM persons WHERE tenant_id = ?1 AND id = ?2",
+                params![self.tenant_id, id],
+                person_from_row,
+            )
+            .optional()?)
+    }
+
+    fn writer<'a>(&'a self, conn: &'a Connection) -> SqliteWriter<'a> {
+        SqliteWriter {
+            conn,
+            tenant_id: &self.tenant_id,
+        }
+    }
+
+    ///
+    /// ajoute la personne avec un nouvel id
+    /// les id ont la même forme que ceux de MongoDB, on peut passer de l'un à l'autre
+    ///
+    pub fn add(&self, person: Person) -> Result<Person, MyError> {
+        let conn = self.conn()?;
+        self.writer(&conn).add(person)
+    }
+
+    ///
+    /// remplace la personne ; renvoie l'ancienne, comme db_mongo
+    ///
+    pub fn modify(&self, id: &str, person: Person) -> Result<Option<Person>, MyError> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+        let previous = self.writer(&tx).modify(id, person)?;
+        tx.commit()?;
+        Ok(previous)
+    }
+
+    pub fn delete(&self, id: &str) -> Result<Option<Person>, MyError> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+        let previous = self.writer(&tx).delete(id)?;
+        tx.commit()?;
+        Ok(previous)
+    }
+
+    ///
+    /// appelle run avec les écritures d'une transaction, validée si run renvoie true
+    /// sinon, ou en cas d'erreur, rien n'est écrit
+    ///
+    pub fn transaction(&self, run: impl FnOnce(&SqliteWriter) -> bool) -> Result<bool, MyError> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+        let commit = run(&self.writer(&tx));
+        if commit {
+            tx.commit()?;
+        }
+        Ok(commit)
+    }
+
+    pub fn upsert(&self, person: Person) -> Result<(), MyError> {
+        let id = match &person.id {
+            Some(id) => id.clone(),
+            None => return self.add(person).map(|_| ()),
+        };
+        self.conn()?.execute(
+            "INSERT INTO persons (tenant_id, id, nom, prenom) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT (tenant_id, id) DO UPDATE SET nom = excluded.nom, prenom = excluded.prenom",
+            params![self.tenant_id, id.as_str(), person.nom, person.prenom],
+        )?;
+        Ok(())
+    }
+
+    pub fn clear(&self) -> Result<(), MyError> {
+        self.conn()?.execute(
+            "DELETE FROM persons WHERE tenant_id = ?1",
+            params![self.tenant_id],
+        )?;
+        Ok(())
+    }
+
+    ///
+    /// efface les personnes du locataire et écrit celles données, dans une transaction
+    /// les personnes sans id sont ignorées : PersonStore::replace_all leur en donne un
+    ///
+    pub fn replace_all(&self, persons: Vec<Person>) -> Result<(), MyError> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+        tx.execute(
+            "DELETE FROM persons WHERE tenant_id = ?1",
+            params![self.tenant_id],
+        )?;
+        for person in &persons {
+            let id = match &person.id {
+                Some(id) => id,
+                None => continue,
+            };
+            tx.execute(
+                "INSERT INTO persons (tenant_id, id, nom, prenom) VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT (tenant_id, id) DO UPDATE SET nom = excluded.nom, prenom = excluded.prenom",
+                params![self.tenant_id, id.as_str(), person.nom, person.prenom],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+///
+/// les écritures sur les personnes d'un locataire, avec une connexion donnée
+/// SqlitePersons::transaction les fait dans une transaction (lots d'opérations)
+///
+pub struct SqliteWriter<'a> {
+    conn: &'a Connection,
+    tenant_id: &'a str,
+}
+
+impl SqliteWriter<'_> {
+    pub fn add(&self, person: Person) -> Result<Person, MyError> {
+        let id = PersonId::from(ObjectId::new()?);
+        self.conn.execute(
+            "INSERT INTO persons (tenant_id, id, nom, prenom) VALUES (?1, ?2, ?3, ?4)",
+            params![self.tenant_id, id.as_str(), person.nom, person.prenom],
+        )?;
+        Ok(Person {
+            id: Some(id),
+            ..person
+        })
+    }
+
+    pub fn modify(&self, id: &str, person: Person) -> Result<Option<Person>, MyError> {
+        let previous = self
+            .conn
+            .query_row(
+                "SELECT id, nom, prenom FROM persons WHERE tenant_id = ?1 AND id = ?2",
+                params![self.tenant_id, id],
+                person_from_row,
+            )
+            .optional()?;
+        if previous.is_some() {
+            self.conn.execute(
+                "UPDATE persons SET nom = ?3, prenom = ?4 WHERE tenant_id = ?1 AND id = ?2",
+                params![self.tenant_id, id, person.nom, person.prenom],
+            )?;
+        }
+        Ok(previous)
+    }
+
+    pub fn delete(&self, id: &str) -> Result<Option<Person>, MyError> {
+        let previous = self
+            .conn
+            .query_row(
+                "SELECT id, nom, prenom FROM persons WHERE tenant_id = ?1 AND id = ?2",
+                params![self.tenant_id, id],
+                person_from_row,
+            )
+            .optional()?;
+        if previous.is_some() {
+            self.conn.execute(
+                "DELETE FROM persons WHERE tenant_id = ?1 AND id = ?2",
+                params![self.tenant_id, id],
+            )?;
+        }
+        Ok(previous)
+    }
+}
+
+const API_KEY_COLUMNS: &str = "id, name, prefix, hash, scopes, tenant_id, \
+                               created_at, expires_at, last_used_at, revoked_at";
+
+fn api_key_from_row(row: &Row) -> rusqlite::Result<ApiKey> {
+    Ok(ApiKey {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        prefix: row.get(2)?,
+        hash: row.get(3)?,
+        scopes: parse_scopes(&row.get::<_, String>(4)?),
+        tenant: row.get(5)?,
+        created_at: row.get(6)?,
+        expires_at: row.get(7)?,
+        last_used_at: row.get(8)?,
+        revoked_at: row.get(9)?,
+    })
+}
+
+///
+/// la table api_keys du fichier SQLite principal
+///
+pub struct SqliteApiKeys {
+    pub path: PathBuf,
+}
+
+impl SqliteApiKeys {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    fn conn(&self) -> Result<r2d2::PooledConnection<SqliteConnectionManager>, MyError> {
+        let pool = pool(&self.path)?;
+        Ok(metrics::observe_pool_wait("sqlite", || pool.get())?)
+    }
+
+    pub fn insert(&self, key: &ApiKey) -> Result<(), MyError> {
+        self.conn()?.execute(
+            &format!(
+                "INSERT INTO api_keys ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                API_KEY_COLUMNS
+            ),
+            params![
+                key.id,
+                key.name,
+                key.prefix,
+                key.hash,
+                join_scopes(&key.scopes),
+                key.tenant,
+                key.created_at,
+                key.expires_at,
+                key.last_used_at,
+                key.revoked_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn list(&self) -> Result<Vec<ApiKey>, MyError> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM api_keys ORDER BY created_at",
+            API_KEY_COLUMNS
+        ))?;
+        let rows = stmt.query_map(NO_PARAMS, api_key_from_row)?;
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+
+    fn find(&self, column: &str, value: &str) -> Result<Option<ApiKey>, MyError> {
+        Ok(self
+            .conn()?
+            .query_row(
+                &format!(
+                    "SELECT {} FROM api_keys WHERE {} = ?1",
+                    API_KEY_COLUMNS, column
+                ),
+                params![value],
+                api_key_from_row,
+            )
+            .optional()?)
+    }
+
+    pub fn get(&self, id: &str) -> Result<Option<ApiKey>, MyError> {
+        self.find("id", id)
+    }
+
+    pub fn find_by_prefix(&self, prefix: &str) -> Result<Option<ApiKey>, MyError> {
+        self.find("prefix", prefix)
+    }
+
+    pub fn update(&self, key: &ApiKey) -> Result<bool, MyError> {
+        let changed = self.conn()?.execute(
+            "UPDATE api_keys SET name = ?2, prefix = ?3, hash = ?4, scopes = ?5, tenant_id = ?6,
+             expires_at = ?7, last_used_at = ?8, revoked_at = ?9 WHERE id = ?1",
+            params![
+                key.id,
+                key.name,
+                key.prefix,
+                key.hash,
+                join_scopes(&key.scopes),
+                key.tenant,
+                key.expires_at,
+                key.last_used_at,
+                key.revoked_at
+            ],
+        )?;
+        Ok(changed > 0)
+    }
+
+    pub fn touch(&self, id: &str, at: i64) -> Result<(), MyError> {
+        self.conn()?.execute(
+            "UPDATE api_keys SET last_used_at = ?2 WHERE id = ?1",
+            params![id, at],
+        )?;
+        Ok(())
+    }
+}
+
+const USER_COLUMNS: &str =
+    "id, subject, name, email, groups, scopes, tenant_id, created_at, last_login_at";
+
+fn user_from_row(row: &Row) -> rusqlite::Result<User> {
+    let groups: String = row.get(4)?;
+    Ok(User {
+        id: row.get(0)?,
+        subject: row.get(1)?,
+        name: row.get(2)?,
+        email: row.get(3)?,
+        groups: groups
+            .split(',')
+            .filter(|group| !group.is_empty())
+            .map(str::to_owned)
+            .collect(),
+        scopes: parse_scopes(&row.get::<_, String>(5)?),
+        tenant: row.get(6)?,
+        created_at: row.get(7)?,
+        last_login_at: row.get(8)?,
+    })
+}
+
+///
+/// la table users du fichier SQLite principal
+///
+pub struct SqliteUsers {
+    pub path: PathBuf,
+}
+
+impl SqliteUsers {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    fn conn(&self) -> Result<r2d2::PooledConnection<SqliteConnectionManager>, MyError> {
+        let pool = pool(&self.path)?;
+        Ok(metrics::observe_pool_wait("sqlite", || pool.get())?)
+    }
+
+    fn find(&self, column: &str, value: &str) -> Result<Option<User>, MyError> {
+        Ok(self
+            .conn()?
+            .query_row(
+                &format!("SELECT {} FROM users WHERE {} = ?1", USER_COLUMNS, column),
+                params![value],
+                user_from_row,
+            )
+            .optional()?)
+    }
+
+    pub fn get(&self, id: &str) -> Result<Option<User>, MyError> {
+        self.find("id", id)
+    }
+
+    pub fn find_by_subject(&self, subject: &str) -> Result<Option<User>, MyError> {
+        self.find("subject", subject)
+    }
+
+    pub fn insert(&self, user: &User) -> Result<(), MyError> {
+        self.conn()?.execute(
+            &format!(
+                "INSERT INTO users ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                USER_COLUMNS
+            ),
+            params![
+                user.id,
+                user.subject,
+                user.name,
+                user.email,
+                user.groups.join(","),
+                join_scopes(&user.scopes),
+                user.tenant,
+                user.created_at,
+                user.last_login_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn update(&self, user: &User) -> Result<bool, MyError> {
+        let changed = self.conn()?.execute(
+            "UPDATE users SET name = ?2, email = ?3, groups = ?4, scopes = ?5, tenant_id = ?6,
+             last_login_at = ?7 WHERE id = ?1",
+            params![
+                user.id,
+                user.name,
+                user.email,
+                user.groups.join(","),
+                join_scopes(&user.scopes),
+                user.tenant,
+                user.last_login_at
+            ],
+        )?;
+        Ok(changed > 0)
+    }
+}
+
+const COMPLIANCE_COLUMNS: &str = "id, tenant_id, person_id, action, actor, at, details";
+
+fn compliance_from_row(row: &Row) -> rusqlite::Result<ComplianceEntry> {
+    let tenant: String = row.get(1)?;
+    let action: String = row.get(3)?;
+    Ok(ComplianceEntry {
+        id: row.get(0)?,
+        tenant: Some(tenant).filter(|tenant| !tenant.is_empty()),
+        person_id: row.get(2)?,
+        action: action.parse().map_err(|e: String| {
+            rusqlite::Error::FromSqlConversionFailure(3, rusqlite::types::Type::Text, e.into())
+        })?,
+        actor: row.get(4)?,
+        at: row.get(5)?,
+        details: row.get(6)?,
+    })
+}
+
+///
+/// la table compliance_log du fichier SQLite principal
+///
+pub struct SqliteComplianceLog {
+    pub path: PathBuf,
+}
+
+impl SqliteComplianceLog {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    fn conn(&self) -> Result<r2d2::PooledConnection<SqliteConnectionManager>, MyError> {
+        let pool = pool(&self.path)?;
+        Ok(metrics::observe_pool_wait("sqlite", || pool.get())?)
+    }
+
+    pub fn append(&self, entry: &ComplianceEntry) -> Result<(), MyError> {
+        self.conn()?.execute(
+            &format!(
+                "INSERT INTO compliance_log ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                COMPLIANCE_COLUMNS
+            ),
+            params![
+                entry.id,
+                entry.tenant.as_deref().unwrap_or(""),
+                entry.person_id,
+                entry.action.name(),
+                entry.actor,
+                entry.at,
+                entry.details
+            ],
+        )?;
+        Ok(())
+    }
+
+    ///
+    /// les entrées du locataire, dans l'ordre ; seulement celles de la personne si elle est donnée
+    ///
+    pub fn list(
+        &self,
+        tenant: Option<&str>,
+        person_id: Option<&str>,
+    ) -> Result<Vec<ComplianceEntry>, MyError> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM compliance_log WHERE tenant_id = ?1 AND (?2 IS NULL OR person_id = ?2)
+             ORDER BY seq",
+            COMPLIANCE_COLUMNS
+        ))?;
+        let rows = stmt.query_map(
+            params![tenant.unwrap_or(""), person_id],
+            compliance_from_row,
+        )?;
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+}
@@ -6,8 +6,12 @@ use bson::{
 
 use mongodb::{error::Error as MongoError, error::ErrorKind as MongoErrorKind};
 
+use actix_web::error::BlockingError;
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
 use thiserror::Error;
 
+use shared::ErrorEnvelope;
+
 #[derive(Error, Debug)]
 pub enum MyError {
     #[error("Mongo Error")]
@@ -16,6 +20,15 @@ pub enum MyError {
     #[error("Mongo ErrorKind")]
     MongoKindError(#[from] MongoErrorKind),
 
+    #[error("SQLite Error")]
+    Sqlite(#[from] rusqlite::Error),
+
+    #[error("PostgreSQL Error")]
+    Postgres(#[from] postgres::Error),
+
+    #[error("Connection pool Error")]
+    Pool(#[from] r2d2::Error),
+
     #[error("Error encoding BSON")]
     BsonEncode(#[from] BsonEncoderError),
 
@@ -24,4 +37,119 @@ pub enum MyError {
 
     #[error("Invalid document id")]
     BsonOid(#[from] BsonOidError),
+
+    #[error("I/O error")]
+    Io(#[from] std::io::Error),
+
+    #[error("JSON error")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Invalid backup archive: {0}")]
+    Backup(String),
+
+    #[error("Invalid tenant: {0}")]
+    Tenant(String),
+
+    #[error("Storage migration failed: {0}")]
+    Migration(String),
+
+    #[error("TLS error: {0}")]
+    Tls(String),
+
+    #[error("Invalid API key: {0}")]
+    InvalidApiKey(String),
+
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+
+    #[error("Identity provider error: {0}")]
+    Oidc(String),
+
+    #[error("Invalid idempotency key: {0}")]
+    InvalidIdempotencyKey(String),
+
+    #[error("Idempotency key already used for a different request")]
+    IdempotencyKeyReused,
+
+    #[error("A request with this idempotency key is in progress")]
+    IdempotencyInProgress,
+
+    #[error("Invalid batch: {0}")]
+    Batch(String),
+
+    #[error("The blocking task was canceled")]
+    Canceled,
+}
+
+///
+/// le résultat d'un appel fait dans web::block
+///
+impl From<BlockingError<MyError>> for MyError {
+    fn from(e: BlockingError<MyError>) -> Self {
+        match e {
+            BlockingError::Error(e) => e,
+            BlockingError::Canceled => MyError::Canceled,
+        }
+    }
+}
+
+impl MyError {
+    ///
+    /// le code d'erreur stable mis dans ErrorEnvelope
+    ///
+    pub fn code(&self) -> &'static str {
+        match self {
+            MyError::Mongo(_)
+            | MyError::MongoKindError(_)
+            | MyError::Sqlite(_)
+            | MyError::Postgres(_)
+            | MyError::Pool(_) => "database_error",
+            MyError::BsonEncode(_) | MyError::BsonDecode(_) => "invalid_document",
+            MyError::BsonOid(_) => "invalid_id",
+            MyError::Io(_) => "io_error",
+            MyError::Json(_) => "invalid_json",
+            MyError::Backup(_) => "invalid_backup",
+            MyError::Tenant(_) => "invalid_tenant",
+            MyError::Migration(_) => "migration_failed",
+            MyError::Tls(_) => "tls_error",
+            MyError::InvalidApiKey(_) => "invalid_api_key",
+            MyError::Unauthorized(_) => "unauthorized",
+            MyError::Forbidden(_) => "forbidden",
+            MyError::Oidc(_) => "oidc_error",
+            MyError::InvalidIdempotencyKey(_) => "invalid_idempotency_key",
+            MyError::IdempotencyKeyReused => "idempotency_key_reused",
+            MyError::IdempotencyInProgress => "idempotency_in_progress",
+            MyError::Batch(_) => "invalid_batch",
+            MyError::Canceled => "canceled",
+        }
+    }
+}
+
+impl ResponseError for MyError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            MyError::BsonOid(_)
+            | MyError::Json(_)
+            | MyError::Backup(_)
+            | MyError::Tenant(_)
+            | MyError::InvalidApiKey(_)
+            | MyError::InvalidIdempotencyKey(_)
+            | MyError::Batch(_) => StatusCode::BAD_REQUEST,
+            MyError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            MyError::Forbidden(_) => StatusCode::FORBIDDEN,
+            MyError::IdempotencyKeyReused => StatusCode::UNPROCESSABLE_ENTITY,
+            MyError::IdempotencyInProgress => StatusCode::CONFLICT,
+            // le fournisseur d'identité ne répond pas ou répond mal
+            MyError::Oidc(_) => StatusCode::BAD_GATEWAY,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code())
+            .json(ErrorEnvelope::new(self.code(), self.to_string()))
+    }
 }
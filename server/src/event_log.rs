@@ -0,0 +1,144 @@
+// server/src/event_log.rs
+
+use bson::{doc, from_bson, to_bson, Document};
+use mongodb::options::{FindOneOptions, FindOptions};
+use serde::Serialize;
+
+use crate::db_mongo;
+use crate::errors::MyError;
+use shared::PersonEvent;
+
+///
+/// nombre d'événements conservés pour rejouer les événements manqués
+/// les plus anciens sont effacés, toutes personnes confondues
+///
+pub const EVENT_LOG_CAPACITY: i64 = 10_000;
+
+///
+/// un événement avec son numéro d'ordre
+/// les numéros sont croissants et servent d'id pour Last-Event-ID
+///
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct LoggedEvent {
+    pub id: i64,
+    // le locataire de la personne ; les abonnés ne voient que leur locataire
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    pub event: PersonEvent,
+}
+
+impl LoggedEvent {
+    ///
+    /// met l'événement au format text/event-stream
+    ///
+    pub fn to_sse(&self) -> String {
+        let data = serde_json::to_string(self.event.person()).unwrap_or_default();
+        format!(
+            "id: {}\nevent: {}\ndata: {}\n\n",
+            self.id,
+            self.event.kind(),
+            data
+        )
+    }
+}
+
+///
+/// le journal des événements, dans la collection PersonEvents
+/// on garde seulement les EVENT_LOG_CAPACITY derniers événements
+///
+pub struct EventLog {
+    capacity: i64,
+}
+
+impl EventLog {
+    pub fn new() -> Self {
+        Self {
+            capacity: EVENT_LOG_CAPACITY,
+        }
+    }
+
+    ///
+    /// le nombre d'événements gardés
+    ///
+    pub fn capacity(&self) -> i64 {
+        self.capacity
+    }
+
+    pub fn last_id(&self) -> Result<i64, MyError> {
+        let coll = db_mongo::get_named_collection("PersonEvents")?;
+        let options = FindOneOptions::builder().sort(doc! {"seq": -1}).build();
+        let last = coll.find_one(None, Some(options))?;
+        Ok(last.and_then(|doc| doc.get_i64("seq").ok()).unwrap_or(0))
+    }
+
+    pub fn append(&self, logged: &LoggedEvent) -> Result<(), MyError> {
+        let coll = db_mongo::get_named_collection("PersonEvents")?;
+        coll.insert_one(
+            doc! {"seq": logged.id, "tenant": to_bson(&logged.tenant)?, "event": to_bson(&logged.event)?},
+            None,
+        )?;
+        // on efface les plus anciens de temps en temps
+        if logged.id % 100 == 0 {
+            coll.delete_many(doc! {"seq": {"$lte": logged.id - self.capacity}}, None)?;
+        }
+        Ok(())
+    }
+
+    ///
+    /// les événements qui suivent l'id donné, dans l'ordre
+    ///
+    pub fn since(&self, last_id: i64) -> Result<Vec<LoggedEvent>, MyError> {
+        self.find(doc! {"seq": {"$gt": last_id}})
+    }
+
+    ///
+    /// les événements encore gardés d'une personne du locataire, dans l'ordre
+    /// les plus anciens ont pu être effacés (EVENT_LOG_CAPACITY)
+    ///
+    pub fn person_events(
+        &self,
+        tenant: Option<&str>,
+        person_id: &str,
+    ) -> Result<Vec<LoggedEvent>, MyError> {
+        self.find(doc! {"tenant": to_bson(&tenant)?, "event.person.id": person_id})
+    }
+
+    fn find(&self, filter: Document) -> Result<Vec<LoggedEvent>, MyError> {
+        let coll = db_mongo::get_named_collection("PersonEvents")?;
+        let options = FindOptions::builder().sort(doc! {"seq": 1}).build();
+        let cursor = coll.find(Some(filter), Some(options))?;
+        let mut events = Vec::new();
+        for row in cursor {
+            let doc = row?;
+            let id = doc.get_i64("seq").unwrap_or_default();
+            let tenant = doc.get_str("tenant").ok().map(str::to_owned);
+            let event = match doc.get("event") {
+                Some(event) => from_bson::<PersonEvent>(event.clone())?,
+                None => continue,
+            };
+            events.push(LoggedEvent { id, tenant, event });
+        }
+        Ok(events)
+    }
+
+    ///
+    /// efface les événements d'une personne du locataire (droit à l'effacement)
+    /// renvoie leurs numéros, pour effacer aussi les livraisons des webhooks
+    ///
+    pub fn erase_person(&self, tenant: Option<&str>, person_id: &str) -> Result<Vec<i64>, MyError> {
+        let coll = db_mongo::get_named_collection("PersonEvents")?;
+        let filter = doc! {"tenant": to_bson(&tenant)?, "event.person.id": person_id};
+        let mut ids = Vec::new();
+        for row in coll.find(Some(filter.clone()), None)? {
+            ids.push(row?.get_i64("seq").unwrap_or_default());
+        }
+        coll.delete_many(filter, None)?;
+        Ok(ids)
+    }
+}
+
+impl Default for EventLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,238 @@
+// server/src/gdpr.rs
+
+use chrono::Utc;
+use serde::Serialize;
+
+use crate::broadcast::Broadcaster;
+use crate::compliance::{self, ComplianceAction, ComplianceEntry, ComplianceLog};
+use crate::errors::MyError;
+use crate::event_log::LoggedEvent;
+use crate::idempotency::IdempotencyStore;
+use crate::store::PersonStore;
+use crate::webhooks::{self, Delivery};
+use shared::{ErasureReport, Person, PersonEvent, PersonId};
+
+///
+/// la version du format de l'export, pour les outils qui le relisent
+///
+pub const EXPORT_FORMAT: &str = "seed-gdpr-export/1";
+
+///
+/// ce que le serveur garde sur une personne (droit d'accès)
+/// le serveur ne gère ni pièces jointes ni groupes : l'export n'en a pas
+///
+#[derive(Serialize, Debug, Clone)]
+pub struct GdprExport {
+    pub format: &'static str,
+    pub generated_at: i64,
+    pub tenant: Option<String>,
+    pub person: Person,
+    // les versions successives de la fiche, dans le journal des événements (MongoDB seulement)
+    pub revisions: Vec<LoggedEvent>,
+    // le journal ne garde que ce nombre d'événements, toutes personnes confondues :
+    // les plus anciennes versions peuvent manquer ; None sans journal des événements
+    pub revisions_limit: Option<i64>,
+    // les demandes RGPD sur la personne, cet export compris
+    pub audit: Vec<ComplianceEntry>,
+    // les envois de ses données aux webhooks
+    pub webhook_deliveries: Vec<Delivery>,
+}
+
+///
+/// une demande RGPD : la personne du locataire et celui qui la fait
+///
+pub struct GdprRequest {
+    pub tenant: Option<String>,
+    pub person_id: String,
+    // key:<id>, user:<id> ou admin-token
+    pub actor: String,
+}
+
+impl GdprRequest {
+    fn entry(&self, action: ComplianceAction, details: Option<String>) -> ComplianceEntry {
+        compliance::entry(
+            self.tenant.clone(),
+            &self.person_id,
+            action,
+            &self.actor,
+            details,
+        )
+    }
+}
+
+///
+/// la pierre tombale de la personne si elle a été effacée
+///
+pub fn tombstone(
+    log: &dyn ComplianceLog,
+    tenant: Option<&str>,
+    person_id: &str,
+) -> Result<Option<ComplianceEntry>, MyError> {
+    Ok(log
+        .list(tenant, Some(person_id))?
+        .into_iter()
+        .rev()
+        .find(|entry| entry.action == ComplianceAction::Erased))
+}
+
+///
+/// rassemble tout ce qui concerne la personne ; None si elle n'existe pas
+/// l'export est noté dans le journal de conformité
+///
+pub fn export(
+    store: &dyn PersonStore,
+    log: &dyn ComplianceLog,
+    broadcaster: &Broadcaster,
+    request: &GdprRequest,
+) -> Result<Option<GdprExport>, MyError> {
+    let person = match store.get(&request.person_id)? {
+        Some(person) => person,
+        None => return Ok(None),
+    };
+    let revisions = broadcaster.person_events(request.tenant.as_deref(), &request.person_id)?;
+    let event_ids: Vec<i64> = revisions.iter().map(|logged| logged.id).collect();
+    // sans journal des événements, pas de livraisons à chercher (et pas de MongoDB)
+    let webhook_deliveries = if event_ids.is_empty() {
+        Vec::new()
+    } else {
+        webhooks::deliveries_for_events(&event_ids)?
+    };
+
+    log.append(&request.entry(ComplianceAction::Export, None))?;
+    tracing::info!(
+        target: "compliance",
+        person = %request.person_id,
+        actor = %request.actor,
+        "personal data exported"
+    );
+    Ok(Some(GdprExport {
+        format: EXPORT_FORMAT,
+        generated_at: Utc::now().timestamp_millis(),
+        tenant: request.tenant.clone(),
+        person,
+        revisions,
+        revisions_limit: broadcaster.log_capacity(),
+        audit: log.list(request.tenant.as_deref(), Some(&request.person_id))?,
+        webhook_deliveries,
+    }))
+}
+
+// ce que les étapes de l'effacement ont retiré
+struct Removed {
+    person_deleted: bool,
+    events: usize,
+    webhook_deliveries: usize,
+    cached_responses: usize,
+}
+
+fn remove_everywhere(
+    store: &dyn PersonStore,
+    broadcaster: &Broadcaster,
+    idempotency: Option<&IdempotencyStore>,
+    request: &GdprRequest,
+) -> Result<Removed, MyError> {
+    let tenant = request.tenant.as_deref();
+    let person_deleted = store.delete(&request.person_id)?.is_some();
+    let event_ids = broadcaster.erase_person(tenant, &request.person_id)?;
+    let webhook_deliveries = if event_ids.is_empty() {
+        0
+    } else {
+        webhooks::delete_deliveries_for_events(&event_ids)? as usize
+    };
+    let cached_responses =
+        idempotency.map_or(0, |store| store.forget_containing(&request.person_id));
+    Ok(Removed {
+        person_deleted,
+        events: event_ids.len(),
+        webhook_deliveries,
+        cached_responses,
+    })
+}
+
+///
+/// efface la personne partout : sa fiche, ses événements, les livraisons des webhooks
+/// qui portaient ses données et les réponses gardées pour Idempotency-Key
+/// il ne reste que l'id, dans le journal de conformité et dans un événement deleted
+/// sans nom ni prénom, envoyé aux abonnés pour qu'ils effacent leur copie
+///
+/// None si la personne n'existe pas et qu'aucun effacement n'est en attente
+/// un effacement commencé puis en échec peut être refait : chaque étape est idempotente
+///
+pub fn erase(
+    store: &dyn PersonStore,
+    log: &dyn ComplianceLog,
+    broadcaster: &Broadcaster,
+    idempotency: Option<&IdempotencyStore>,
+    request: &GdprRequest,
+) -> Result<Option<ErasureReport>, MyError> {
+    let tenant = request.tenant.as_deref();
+    let exists = store.get(&request.person_id)?.is_some();
+    let pending = log
+        .list(tenant, Some(&request.person_id))?
+        .iter()
+        .rev()
+        .find(|entry| entry.action != ComplianceAction::Export)
+        .map_or(false, |entry| entry.action != ComplianceAction::Erased);
+    if !exists && !pending {
+        return Ok(None);
+    }
+
+    log.append(&request.entry(ComplianceAction::ErasureStarted, None))?;
+    tracing::info!(
+        target: "compliance",
+        person = %request.person_id,
+        actor = %request.actor,
+        "erasure started"
+    );
+
+    let removed = match remove_everywhere(store, broadcaster, idempotency, request) {
+        Ok(removed) => removed,
+        Err(e) => {
+            tracing::error!(
+                target: "compliance",
+                person = %request.person_id,
+                error = %e,
+                "erasure failed"
+            );
+            let failed = request.entry(ComplianceAction::ErasureFailed, Some(e.to_string()));
+            if let Err(log_error) = log.append(&failed) {
+                tracing::error!(error = %log_error, "failed to log the erasure failure");
+            }
+            return Err(e);
+        }
+    };
+
+    if removed.person_deleted {
+        let stub = Person {
+            id: Some(PersonId::new(request.person_id.clone())),
+            nom: String::new(),
+            prenom: String::new(),
+        };
+        broadcaster.send_for(request.tenant.clone(), PersonEvent::Deleted(stub));
+    }
+
+    let details = format!(
+        "person record: {}, events: {}, webhook deliveries: {}, cached responses: {}",
+        if removed.person_deleted { 1 } else { 0 },
+        removed.events,
+        removed.webhook_deliveries,
+        removed.cached_responses
+    );
+    let tombstone = request.entry(ComplianceAction::Erased, Some(details));
+    log.append(&tombstone)?;
+    tracing::info!(
+        target: "compliance",
+        person = %request.person_id,
+        actor = %request.actor,
+        events = removed.events,
+        webhook_deliveries = removed.webhook_deliveries,
+        "person erased"
+    );
+    Ok(Some(ErasureReport {
+        tombstone,
+        person_deleted: removed.person_deleted,
+        events: removed.events,
+        webhook_deliveries: removed.webhook_deliveries,
+        cached_responses: removed.cached_responses,
+    }))
+}
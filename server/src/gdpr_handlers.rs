@@ -0,0 +1,137 @@
+// src/gdpr_handlers.rs
+
+use actix_web::{web, HttpRequest, HttpResponse};
+use serde::Deserialize;
+
+use crate::admin::{admin_actor, check_admin};
+use crate::broadcast::Broadcaster;
+use crate::compliance::{ComplianceLog, SharedComplianceLog};
+use crate::config::Config;
+use crate::errors::MyError;
+use crate::gdpr::{self, GdprRequest};
+use crate::idempotency::SharedIdempotency;
+use crate::tenancy::Tenant;
+use shared::ErrorEnvelope;
+
+#[derive(Deserialize)]
+pub struct ComplianceQuery {
+    pub person: Option<String>,
+}
+
+fn gdpr_request(req: &HttpRequest, tenant: &Tenant, person_id: String) -> GdprRequest {
+    GdprRequest {
+        tenant: tenant.id.clone(),
+        person_id,
+        actor: admin_actor(req),
+    }
+}
+
+// 410 si la personne a été effacée, 404 sinon
+fn person_missing(
+    log: &dyn ComplianceLog,
+    tenant: &Tenant,
+    person_id: &str,
+) -> Result<HttpResponse, MyError> {
+    Ok(
+        match gdpr::tombstone(log, tenant.id.as_deref(), person_id)? {
+            Some(tombstone) => HttpResponse::Gone().json(ErrorEnvelope::new(
+                "erased",
+                format!(
+                    "the person {} was erased (entry {})",
+                    person_id, tombstone.id
+                ),
+            )),
+            None => HttpResponse::NotFound().json(ErrorEnvelope::new(
+                "not_found",
+                format!("no person with the id {}", person_id),
+            )),
+        },
+    )
+}
+
+///
+/// tout ce que le serveur garde sur une personne, en un fichier JSON (droit d'accès)
+///
+pub async fn gdpr_export_hdl(
+    req: HttpRequest,
+    config: web::Data<Config>,
+    broadcaster: web::Data<Broadcaster>,
+    log: web::Data<SharedComplianceLog>,
+    tenant: Tenant,
+    id: web::Path<String>,
+) -> Result<HttpResponse, MyError> {
+    if let Err(resp) = check_admin(&req, &config) {
+        return Ok(resp);
+    }
+    let request = gdpr_request(&req, &tenant, id.into_inner());
+    let person_id = request.person_id.clone();
+    let store = tenant.store()?;
+    let export_log = log.clone();
+    // le journal des événements est attendu et lu hors du worker
+    let export =
+        web::block(move || gdpr::export(&*store, &**export_log, &broadcaster, &request)).await?;
+    match export {
+        Some(export) => Ok(HttpResponse::Ok()
+            .header(
+                "Content-Disposition",
+                format!("attachment; filename=\"gdpr-export-{}.json\"", person_id),
+            )
+            .json(export)),
+        None => person_missing(&**log, &tenant, &person_id),
+    }
+}
+
+///
+/// efface la personne partout et renvoie la pierre tombale (droit à l'effacement)
+///
+pub async fn erase_person_hdl(
+    req: HttpRequest,
+    config: web::Data<Config>,
+    broadcaster: web::Data<Broadcaster>,
+    log: web::Data<SharedComplianceLog>,
+    tenant: Tenant,
+    id: web::Path<String>,
+) -> Result<HttpResponse, MyError> {
+    if let Err(resp) = check_admin(&req, &config) {
+        return Ok(resp);
+    }
+    let idempotency = req
+        .app_data::<web::Data<SharedIdempotency>>()
+        .map(|store| store.get_ref().clone());
+    let request = gdpr_request(&req, &tenant, id.into_inner());
+    let person_id = request.person_id.clone();
+    let store = tenant.store()?;
+    let erase_log = log.clone();
+    // le journal des événements est attendu et effacé hors du worker
+    let report = web::block(move || {
+        gdpr::erase(
+            &*store,
+            &**erase_log,
+            &broadcaster,
+            idempotency.as_deref(),
+            &request,
+        )
+    })
+    .await?;
+    match report {
+        Some(report) => Ok(HttpResponse::Ok().json(report)),
+        None => person_missing(&**log, &tenant, &person_id),
+    }
+}
+
+///
+/// le journal des demandes RGPD du locataire, pour le délégué à la protection des données
+///
+pub async fn list_compliance_hdl(
+    req: HttpRequest,
+    config: web::Data<Config>,
+    log: web::Data<SharedComplianceLog>,
+    tenant: Tenant,
+    query: web::Query<ComplianceQuery>,
+) -> Result<HttpResponse, MyError> {
+    if let Err(resp) = check_admin(&req, &config) {
+        return Ok(resp);
+    }
+    let entries = log.list(tenant.id.as_deref(), query.person.as_deref())?;
+    Ok(HttpResponse::Ok().json(entries))
+}
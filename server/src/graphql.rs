@@ -0,0 +1,312 @@
+// server/src/graphql.rs
+
+use std::sync::Arc;
+
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use async_graphql::http::graphiql_source;
+use async_graphql::{Context, FieldResult, Object, Schema, SimpleObject, Subscription, ID};
+use async_graphql_actix_web::{GQLRequest, GQLResponse, WSSubscription};
+use futures::{future, Stream, StreamExt};
+
+use crate::auth::Principal;
+use crate::broadcast::Broadcaster;
+use crate::config::Config;
+use crate::errors::MyError;
+use crate::store::PersonStore;
+use crate::tenancy::Tenant;
+use shared::{Person, PersonEvent, PersonId, Scope};
+
+pub type PersonSchema = Schema<QueryRoot, MutationRoot, SubscriptionRoot>;
+
+// nombre de personnes par page si le client ne le précise pas
+const DEFAULT_PAGE_SIZE: i32 = 50;
+const MAX_PAGE_SIZE: i32 = 500;
+
+///
+/// une personne telle que vue par GraphQL
+///
+#[SimpleObject]
+pub struct PersonObject {
+    pub id: Option<ID>,
+    pub nom: String,
+    pub prenom: String,
+}
+
+impl From<Person> for PersonObject {
+    fn from(pers: Person) -> Self {
+        Self {
+            id: pers.id.map(|id| ID::from(id.to_string())),
+            nom: pers.nom,
+            prenom: pers.prenom,
+        }
+    }
+}
+
+///
+/// une page de personnes avec le nombre total de résultats
+///
+#[SimpleObject]
+pub struct PersonPage {
+    pub total_count: i64,
+    pub offset: i32,
+    pub items: Vec<PersonObject>,
+}
+
+///
+/// un changement sur une personne, pour les abonnements
+///
+#[SimpleObject]
+pub struct PersonChange {
+    pub event_id: i64,
+    pub kind: String,
+    pub person: PersonObject,
+}
+
+///
+/// un stockage pour toutes les requêtes du schéma, à la place de celui du locataire
+/// sert aux tests, avec MemoryStore
+///
+pub struct FixedStore(pub Arc<dyn PersonStore>);
+
+// le stockage de la requête : celui du schéma s'il y en a un, sinon celui du locataire
+fn with_store<T>(
+    ctx: &Context<'_>,
+    f: impl FnOnce(&dyn PersonStore) -> Result<T, MyError>,
+) -> Result<T, MyError> {
+    match ctx.data_opt::<FixedStore>() {
+        Some(fixed) => f(&*fixed.0),
+        None => f(&*ctx.data::<Tenant>().store()?),
+    }
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// Une personne par son id
+    async fn person(&self, ctx: &Context<'_>, id: ID) -> FieldResult<Option<PersonObject>> {
+        let found = with_store(ctx, |store| store.get(&id))?;
+        Ok(found.map(PersonObject::from))
+    }
+
+    /// Les personnes triées par nom, filtrées et par pages
+    async fn persons(
+        &self,
+        ctx: &Context<'_>,
+        nom: Option<String>,
+        prenom: Option<String>,
+        offset: Option<i32>,
+        first: Option<i32>,
+    ) -> FieldResult<PersonPage> {
+        let offset = offset.unwrap_or(0).max(0);
+        let first = first.unwrap_or(DEFAULT_PAGE_SIZE).max(0).min(MAX_PAGE_SIZE);
+        let (persons, total_count) = with_store(ctx, |store| {
+            store.search(
+                nom.as_deref(),
+                prenom.as_deref(),
+                i64::from(offset),
+                i64::from(first),
+            )
+        })?;
+        Ok(PersonPage {
+            total_count,
+            offset,
+            items: persons.into_iter().map(PersonObject::from).collect(),
+        })
+    }
+}
+
+///
+/// la clé d'API de la requête GraphQL, s'il y en a une
+/// POST /graphql ne demande que le droit read : les mutations vérifient le droit write
+///
+pub struct Caller(pub Option<Principal>);
+
+fn check_write(ctx: &Context<'_>) -> Result<(), MyError> {
+    match &ctx.data::<Caller>().0 {
+        Some(principal) if !principal.allows(Scope::Write) => {
+            Err(MyError::Forbidden("the write scope is needed".to_owned()))
+        }
+        _ => Ok(()),
+    }
+}
+
+pub struct MutationRoot;
+
+#[Object]
+impl MutationRoot {
+    /// Ajoute une personne
+    async fn add_person(
+        &self,
+        ctx: &Context<'_>,
+        nom: String,
+        prenom: String,
+    ) -> FieldResult<PersonObject> {
+        check_write(ctx)?;
+        let tenant = ctx.data::<Tenant>();
+        let new_person = with_store(ctx, |store| {
+            store.add(Person {
+                id: None,
+                nom,
+                prenom,
+            })
+        })?;
+        ctx.data::<web::Data<Broadcaster>>()
+            .send_for(tenant.id.clone(), PersonEvent::Created(new_person.clone()));
+        Ok(new_person.into())
+    }
+
+    /// Modifie une personne, renvoie null si elle n'existe pas
+    async fn modify_person(
+        &self,
+        ctx: &Context<'_>,
+        id: ID,
+        nom: String,
+        prenom: String,
+    ) -> FieldResult<Option<PersonObject>> {
+        check_write(ctx)?;
+        let modified = Person {
+            id: Some(PersonId::new(id.as_str())),
+            nom,
+            prenom,
+        };
+        let tenant = ctx.data::<Tenant>();
+        match with_store(ctx, |store| store.modify(&id, modified.clone()))? {
+            Some(_) => {
+                ctx.data::<web::Data<Broadcaster>>()
+                    .send_for(tenant.id.clone(), PersonEvent::Updated(modified.clone()));
+                Ok(Some(modified.into()))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Efface une personne, renvoie null si elle n'existe pas
+    async fn delete_person(&self, ctx: &Context<'_>, id: ID) -> FieldResult<Option<PersonObject>> {
+        check_write(ctx)?;
+        let tenant = ctx.data::<Tenant>();
+        match with_store(ctx, |store| store.delete(&id))? {
+            Some(deleted) => {
+                ctx.data::<web::Data<Broadcaster>>()
+                    .send_for(tenant.id.clone(), PersonEvent::Deleted(deleted.clone()));
+                Ok(Some(deleted.into()))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+pub struct SubscriptionRoot;
+
+#[Subscription]
+impl SubscriptionRoot {
+    /// Les ajouts, modifications et effacements de personnes
+    /// kinds : "created", "updated", "deleted" ; tous si absent
+    async fn person_changes(
+        &self,
+        ctx: &Context<'_>,
+        kinds: Option<Vec<String>>,
+    ) -> impl Stream<Item = PersonChange> {
+        let tenant = ctx.data::<Tenant>().id.clone();
+        ctx.data::<web::Data<Broadcaster>>()
+            .new_client()
+            .filter(move |logged| {
+                future::ready(
+                    logged.tenant == tenant
+                        && match &kinds {
+                            Some(kinds) => kinds.iter().any(|k| k == logged.event.kind()),
+                            None => true,
+                        },
+                )
+            })
+            .map(|logged| PersonChange {
+                event_id: logged.id,
+                kind: logged.event.kind().to_owned(),
+                person: logged.event.person().clone().into(),
+            })
+    }
+}
+
+///
+/// construit le schéma GraphQL
+/// le Broadcaster sert aux mutations et aux abonnements
+/// le locataire par défaut et l'appelant anonyme sont remplacés par ceux de chaque requête
+///
+pub fn create_schema(broadcaster: web::Data<Broadcaster>) -> PersonSchema {
+    build_schema(broadcaster, Tenant::default(), Caller(None), None)
+}
+
+///
+/// le schéma GraphQL sur un stockage donné, quel que soit le locataire (tests)
+///
+pub fn create_schema_with_store(
+    broadcaster: web::Data<Broadcaster>,
+    store: Arc<dyn PersonStore>,
+) -> PersonSchema {
+    build_schema(
+        broadcaster,
+        Tenant::default(),
+        Caller(None),
+        Some(FixedStore(store)),
+    )
+}
+
+fn build_schema(
+    broadcaster: web::Data<Broadcaster>,
+    tenant: Tenant,
+    caller: Caller,
+    store: Option<FixedStore>,
+) -> PersonSchema {
+    let builder = Schema::build(QueryRoot, MutationRoot, SubscriptionRoot)
+        .data(broadcaster)
+        .data(tenant)
+        .data(caller);
+    match store {
+        Some(store) => builder.data(store).finish(),
+        None => builder.finish(),
+    }
+}
+
+pub async fn graphql_hdl(
+    schema: web::Data<PersonSchema>,
+    tenant: Tenant,
+    http_req: HttpRequest,
+    req: GQLRequest,
+) -> GQLResponse {
+    req.into_inner()
+        .data(tenant)
+        .data(Caller(Principal::from_request(&http_req)))
+        .execute(&schema)
+        .await
+        .into()
+}
+
+///
+/// les abonnements passent par un WebSocket sur /graphql
+/// chaque connexion a son schéma, avec le locataire et l'appelant de la requête d'ouverture
+///
+pub async fn graphql_ws_hdl(
+    broadcaster: web::Data<Broadcaster>,
+    tenant: Tenant,
+    req: HttpRequest,
+    payload: web::Payload,
+) -> Result<HttpResponse, Error> {
+    let caller = Caller(Principal::from_request(&req));
+    WSSubscription::start(
+        build_schema(broadcaster, tenant, caller, None),
+        &req,
+        payload,
+    )
+}
+
+///
+/// l'interface GraphiQL, seulement si SEED_GRAPHIQL est activé
+///
+pub async fn graphiql_hdl(config: web::Data<Config>) -> HttpResponse {
+    if !config.graphiql {
+        return HttpResponse::NotFound().finish();
+    }
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(graphiql_source("/graphql", Some("/graphql")))
+}
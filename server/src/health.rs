@@ -0,0 +1,262 @@
+// server/src/health.rs
+
+use std::path::Path;
+use std::time::Duration;
+
+use actix_web::{web, HttpResponse};
+use bson::doc;
+use chrono::{TimeZone, Utc};
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::db_mongo;
+use crate::db_postgres;
+use crate::db_sqlite;
+use crate::errors::MyError;
+use crate::migrations;
+use crate::store::{self, Backend};
+use crate::tenancy::TenantMode;
+
+///
+/// les routes de sonde pour l'orchestrateur (Kubernetes...)
+/// elles ne demandent pas d'authentification et ne sont pas limitées en débit :
+/// les middlewares doivent les laisser passer
+///
+pub const PROBE_PATHS: &[&str] = &["/healthz", "/readyz", "/version"];
+
+// le temps laissé au pool pour donner une connexion
+const POOL_TIMEOUT: Duration = Duration::from_secs(1);
+
+///
+/// le résultat d'une vérification de /readyz
+///
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Check {
+    pub name: &'static str,
+    pub ok: bool,
+    pub detail: String,
+}
+
+impl Check {
+    fn new<E: std::fmt::Display>(name: &'static str, result: Result<String, E>) -> Self {
+        match result {
+            Ok(detail) => Self {
+                name,
+                ok: true,
+                detail,
+            },
+            Err(e) => Self {
+                name,
+                ok: false,
+                detail: e.to_string(),
+            },
+        }
+    }
+}
+
+///
+/// la réponse de /readyz
+///
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Readiness {
+    pub status: &'static str,
+    pub backend: &'static str,
+    pub checks: Vec<Check>,
+}
+
+impl Readiness {
+    pub fn new(backend: Backend, checks: Vec<Check>) -> Self {
+        let status = if checks.iter().all(|c| c.ok) {
+            "ready"
+        } else {
+            "degraded"
+        };
+        Self {
+            status,
+            backend: backend.name(),
+            checks,
+        }
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.status == "ready"
+    }
+}
+
+///
+/// MongoDB : ping, une connexion du pool, les migrations en attente
+/// en mode database, les migrations de chaque base de locataire comptent aussi
+///
+pub fn mongo_checks(mode: TenantMode) -> Vec<Check> {
+    let ping = db_mongo::get_database()
+        .and_then(|db| Ok(db.run_command(doc! {"ping": 1}, None)?))
+        .map(|_| "ping ok".to_owned());
+
+    let pool = db_mongo::pool();
+    let state = pool.state();
+    let available = pool.get_timeout(POOL_TIMEOUT).map(|_| {
+        format!(
+            "{} connections, {} idle",
+            state.connections, state.idle_connections
+        )
+    });
+
+    let pending = migrations::pending_migrations(mode).and_then(|pending| {
+        if pending.is_empty() {
+            Ok(format!(
+                "{} migrations applied",
+                migrations::MIGRATIONS.len()
+            ))
+        } else {
+            let versions: Vec<String> = pending
+                .iter()
+                .map(|(database, m)| format!("{}:{}", database, m.version))
+                .collect();
+            Err(MyError::Migration(format!(
+                "pending migrations {}",
+                versions.join(", ")
+            )))
+        }
+    });
+
+    vec![
+        Check::new("database", ping),
+        Check::new("pool", available),
+        Check::new("migrations", pending),
+    ]
+}
+
+// la version du schéma comparée à celle attendue
+fn schema_detail(version: i32, expected: i32) -> Result<String, String> {
+    if version >= expected {
+        Ok(format!("schema version {}", version))
+    } else {
+        Err(format!("schema version {}, expected {}", version, expected))
+    }
+}
+
+///
+/// SQLite : le fichier s'ouvre, une connexion du pool, la version du schéma
+///
+pub fn sqlite_checks(path: &Path) -> Vec<Check> {
+    let pool = match db_sqlite::pool(path) {
+        Ok(pool) => pool,
+        Err(e) => return vec![Check::new("database", Err(e))],
+    };
+    let state = pool.state();
+    let conn = match pool.get_timeout(POOL_TIMEOUT) {
+        Ok(conn) => conn,
+        Err(e) => return vec![Check::new("pool", Err(e))],
+    };
+    let select = conn
+        .query_row("SELECT 1", rusqlite::NO_PARAMS, |row| row.get::<_, i32>(0))
+        .map(|_| "select ok".to_owned());
+    let version = db_sqlite::schema_version(&conn)
+        .map_err(|e| e.to_string())
+        .and_then(|v| schema_detail(v, db_sqlite::SCHEMA_VERSION));
+
+    vec![
+        Check::new("database", select),
+        Check::new::<String>(
+            "pool",
+            Ok(format!(
+                "{} connections, {} idle",
+                state.connections, state.idle_connections
+            )),
+        ),
+        Check::new("migrations", version),
+    ]
+}
+
+///
+/// PostgreSQL : le serveur répond, une connexion du pool, la version du schéma
+///
+pub fn postgres_checks() -> Vec<Check> {
+    let pool = match db_postgres::pool() {
+        Ok(pool) => pool,
+        Err(e) => return vec![Check::new("database", Err(e))],
+    };
+    let state = pool.state();
+    let mut conn = match pool.get_timeout(POOL_TIMEOUT) {
+        Ok(conn) => conn,
+        Err(e) => return vec![Check::new("pool", Err(e))],
+    };
+    let select = conn
+        .query_one("SELECT 1", &[])
+        .map(|_| "select ok".to_owned());
+    let version = db_postgres::schema_version(&mut conn)
+        .map_err(|e| e.to_string())
+        .and_then(|v| schema_detail(v, db_postgres::SCHEMA_VERSION));
+
+    vec![
+        Check::new("database", select),
+        Check::new::<String>(
+            "pool",
+            Ok(format!(
+                "{} connections, {} idle",
+                state.connections, state.idle_connections
+            )),
+        ),
+        Check::new("migrations", version),
+    ]
+}
+
+///
+/// toutes les vérifications du stockage configuré
+///
+pub fn readiness(mode: TenantMode) -> Readiness {
+    let backend = store::backend();
+    let checks = match backend {
+        Backend::Mongo => mongo_checks(mode),
+        Backend::Sqlite => sqlite_checks(db_sqlite::path()),
+        Backend::Postgres => postgres_checks(),
+    };
+    Readiness::new(backend, checks)
+}
+
+///
+/// les informations de compilation, pour /version
+///
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct BuildInfo {
+    pub name: &'static str,
+    pub version: &'static str,
+    pub git_hash: &'static str,
+    pub build_time: String,
+}
+
+impl BuildInfo {
+    pub fn current() -> Self {
+        // SEED_BUILD_TIME est fixé par build.rs, en secondes depuis epoch
+        let seconds = env!("SEED_BUILD_TIME").parse::<i64>().unwrap_or(0);
+        Self {
+            name: env!("CARGO_PKG_NAME"),
+            version: env!("CARGO_PKG_VERSION"),
+            git_hash: env!("SEED_GIT_HASH"),
+            build_time: Utc.timestamp(seconds, 0).to_rfc3339(),
+        }
+    }
+}
+
+///
+/// liveness : le processus répond, sans toucher à la base
+///
+pub async fn healthz_hdl() -> HttpResponse {
+    HttpResponse::Ok().json(serde_json::json!({"status": "ok"}))
+}
+
+///
+/// readiness : 200 si le stockage est utilisable, 503 avec le détail sinon
+///
+pub async fn readyz_hdl(config: web::Data<Config>) -> HttpResponse {
+    let readiness = readiness(config.tenant_mode);
+    if readiness.is_ready() {
+        HttpResponse::Ok().json(readiness)
+    } else {
+        HttpResponse::ServiceUnavailable().json(readiness)
+    }
+}
+
+pub async fn version_hdl() -> HttpResponse {
+    HttpResponse::Ok().json(BuildInfo::current())
+}
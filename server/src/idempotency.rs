@@ -0,0 +1,297 @@
+// server/src/idempotency.rs
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use actix_web::dev::{Body, Payload, ResponseBody};
+use actix_web::http::{header, StatusCode};
+use actix_web::{web, FromRequest, HttpRequest, HttpResponse, ResponseError};
+use futures::future::{ready, Ready};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::auth::Principal;
+use crate::config::Config;
+use crate::errors::MyError;
+use crate::tenancy::resolve_tenant;
+
+///
+/// l'en-tête de la clé d'idempotence, choisie par le client (un uuid par exemple)
+///
+pub const IDEMPOTENCY_KEY: &str = "Idempotency-Key";
+
+///
+/// l'en-tête mis sur une réponse rejouée
+///
+pub const IDEMPOTENCY_REPLAYED: &str = "Idempotency-Replayed";
+
+// un uuid en fait 36
+const MAX_KEY_LEN: usize = 255;
+
+///
+/// une réponse gardée pour être rejouée
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredResponse {
+    pub status: u16,
+    pub content_type: Option<String>,
+    pub body: Vec<u8>,
+}
+
+impl StoredResponse {
+    ///
+    /// la réponse d'un handler ; None si son corps est un flux
+    ///
+    pub fn from_response(response: &HttpResponse) -> Option<Self> {
+        let body = match response.body() {
+            ResponseBody::Body(Body::Bytes(bytes)) => bytes.to_vec(),
+            ResponseBody::Body(Body::Empty) | ResponseBody::Body(Body::None) => Vec::new(),
+            _ => return None,
+        };
+        Some(Self {
+            status: response.status().as_u16(),
+            content_type: response
+                .headers()
+                .get(header::CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_owned),
+            body,
+        })
+    }
+
+    pub fn to_response(&self) -> HttpResponse {
+        let mut builder =
+            HttpResponse::build(StatusCode::from_u16(self.status).unwrap_or(StatusCode::OK));
+        if let Some(content_type) = &self.content_type {
+            builder.content_type(content_type.as_str());
+        }
+        builder
+            .header(IDEMPOTENCY_REPLAYED, "true")
+            .body(self.body.clone())
+    }
+}
+
+enum State {
+    // la première requête est en cours
+    Pending,
+    Done(StoredResponse),
+}
+
+struct Entry {
+    fingerprint: String,
+    state: State,
+    expires: Instant,
+}
+
+///
+/// ce que begin trouve pour une clé
+///
+#[derive(Debug, PartialEq)]
+pub enum Begin {
+    // première requête avec cette clé : le handler s'exécute
+    Started,
+    // la même requête a déjà eu sa réponse
+    Replay(StoredResponse),
+}
+
+///
+/// les clés d'idempotence vues pendant la fenêtre (SEED_IDEMPOTENCY_TTL), en mémoire
+/// chaque clé garde l'empreinte de la requête et sa réponse
+/// un redémarrage du serveur oublie les clés
+///
+pub struct IdempotencyStore {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+///
+/// le stockage partagé par les workers (web::Data<SharedIdempotency>)
+///
+pub type SharedIdempotency = Arc<IdempotencyStore>;
+
+impl IdempotencyStore {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    ///
+    /// réserve la clé, ou renvoie la réponse déjà gardée
+    /// 422 si la clé a servi pour une autre requête, 409 si la première est encore en cours
+    ///
+    pub fn begin(&self, key: &str, fingerprint: &str) -> Result<Begin, MyError> {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, entry| entry.expires > now);
+        match entries.get(key) {
+            Some(entry) if entry.fingerprint != fingerprint => Err(MyError::IdempotencyKeyReused),
+            Some(Entry {
+                state: State::Done(response),
+                ..
+            }) => Ok(Begin::Replay(response.clone())),
+            Some(_) => Err(MyError::IdempotencyInProgress),
+            None => {
+                entries.insert(
+                    key.to_owned(),
+                    Entry {
+                        fingerprint: fingerprint.to_owned(),
+                        state: State::Pending,
+                        expires: now + self.ttl,
+                    },
+                );
+                Ok(Begin::Started)
+            }
+        }
+    }
+
+    ///
+    /// garde la réponse de la requête réservée par begin
+    ///
+    pub fn complete(&self, key: &str, response: StoredResponse) {
+        if let Some(entry) = self.entries.lock().unwrap().get_mut(key) {
+            entry.state = State::Done(response);
+        }
+    }
+
+    ///
+    /// libère la clé : la requête pourra être refaite
+    ///
+    pub fn abandon(&self, key: &str) {
+        self.entries.lock().unwrap().remove(key);
+    }
+
+    ///
+    /// oublie les réponses gardées qui contiennent le texte (l'id d'une personne effacée)
+    /// renvoie le nombre de réponses oubliées
+    ///
+    pub fn forget_containing(&self, needle: &str) -> usize {
+        let needle = needle.as_bytes();
+        if needle.is_empty() {
+            return 0;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        let before = entries.len();
+        entries.retain(|_, entry| match &entry.state {
+            State::Done(response) => !response
+                .body
+                .windows(needle.len())
+                .any(|window| window == needle),
+            State::Pending => true,
+        });
+        before - entries.len()
+    }
+}
+
+///
+/// l'empreinte d'une requête : méthode, chemin et corps
+/// le corps est relu par serde : l'ordre des champs et les espaces ne comptent pas
+///
+pub fn fingerprint<B: Serialize>(method: &str, path: &str, body: &B) -> Result<String, MyError> {
+    let mut hasher = Sha256::new();
+    hasher.input(method.as_bytes());
+    hasher.input(b"\n");
+    hasher.input(path.as_bytes());
+    hasher.input(b"\n");
+    hasher.input(&serde_json::to_vec(body)?);
+    Ok(hex::encode(hasher.result()))
+}
+
+///
+/// l'extracteur des handlers qui créent : la clé de l'en-tête Idempotency-Key
+/// sans en-tête, ou sans IdempotencyStore dans l'App, le handler s'exécute normalement
+///
+pub struct Idempotency {
+    store: Option<SharedIdempotency>,
+    // la clé préfixée par le client et le locataire : deux clients peuvent choisir la même
+    key: Option<String>,
+    method: String,
+    path: String,
+}
+
+impl Idempotency {
+    fn parse(req: &HttpRequest) -> Result<Self, MyError> {
+        let key = match req.headers().get(IDEMPOTENCY_KEY) {
+            Some(value) => Some(
+                value
+                    .to_str()
+                    .ok()
+                    .map(str::trim)
+                    .filter(|key| !key.is_empty() && key.len() <= MAX_KEY_LEN)
+                    .ok_or_else(|| {
+                        MyError::InvalidIdempotencyKey(format!(
+                            "the {} header must have 1 to {} visible characters",
+                            IDEMPOTENCY_KEY, MAX_KEY_LEN
+                        ))
+                    })?,
+            ),
+            None => None,
+        };
+        let client = Principal::from_request(req)
+            .map(|principal| principal.subject)
+            .unwrap_or_default();
+        // un locataire invalide est refusé par l'extracteur Tenant du handler
+        let tenant = req
+            .app_data::<web::Data<Config>>()
+            .and_then(|config| resolve_tenant(req, config).ok())
+            .and_then(|tenant| tenant.id)
+            .unwrap_or_default();
+        Ok(Self {
+            store: req
+                .app_data::<web::Data<SharedIdempotency>>()
+                .map(|store| store.get_ref().clone()),
+            key: key.map(|key| format!("{}\n{}\n{}", client, tenant, key)),
+            method: req.method().to_string(),
+            path: req.path().to_owned(),
+        })
+    }
+
+    ///
+    /// exécute le handler une seule fois par clé et rejoue sa réponse ensuite
+    /// les erreurs du serveur (5xx) ne sont pas gardées : une nouvelle tentative peut réussir
+    ///
+    pub async fn run<B, F, Fut>(self, body: B, handler: F) -> HttpResponse
+    where
+        B: Serialize,
+        F: FnOnce(B) -> Fut,
+        Fut: Future<Output = Result<HttpResponse, MyError>>,
+    {
+        let (store, key) = match (self.store, self.key) {
+            (Some(store), Some(key)) => (store, key),
+            _ => return handler(body).await.unwrap_or_else(|e| e.error_response()),
+        };
+        let begin = fingerprint(&self.method, &self.path, &body)
+            .and_then(|fingerprint| store.begin(&key, &fingerprint));
+        match begin {
+            Ok(Begin::Started) => {}
+            Ok(Begin::Replay(response)) => {
+                tracing::info!("idempotent request replayed");
+                return response.to_response();
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, "idempotency key refused");
+                return e.error_response();
+            }
+        }
+
+        let response = handler(body).await.unwrap_or_else(|e| e.error_response());
+        match StoredResponse::from_response(&response) {
+            Some(stored) if !response.status().is_server_error() => store.complete(&key, stored),
+            _ => store.abandon(&key),
+        }
+        response
+    }
+}
+
+impl FromRequest for Idempotency {
+    type Error = MyError;
+    type Future = Ready<Result<Self, MyError>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(Idempotency::parse(req))
+    }
+}
@@ -0,0 +1,59 @@
+// lib.rs
+
+// la bibliothèque du serveur
+// utilisée par le binaire server (main.rs) et par l'outil seedctl
+
+// import des fichiers internes
+pub mod admin;
+pub mod api_key_handlers;
+pub mod api_keys;
+pub mod assets;
+pub mod auth;
+pub mod auth_handlers;
+pub mod backup;
+pub mod backup_handlers;
+pub mod batch;
+pub mod broadcast;
+pub mod compliance;
+pub mod config;
+pub mod cors;
+pub mod db_mongo;
+pub mod db_postgres;
+pub mod db_sqlite;
+pub mod errors;
+pub mod event_log;
+pub mod gdpr;
+pub mod gdpr_handlers;
+pub mod graphql;
+pub mod health;
+pub mod idempotency;
+pub mod limits;
+pub mod metrics;
+pub mod migrations;
+pub mod oidc;
+pub mod openapi;
+pub mod person_handlers;
+pub mod routes;
+pub mod sessions;
+pub mod sse;
+pub mod store;
+pub mod store_migration;
+pub mod telemetry;
+pub mod tenancy;
+pub mod tls;
+pub mod users;
+pub mod webhook_handlers;
+pub mod webhooks;
+pub mod ws;
+
+use crate::db_mongo::Conn;
+
+///
+/// la structure AppState permet de mettre des données
+/// accessibles partout
+///
+pub struct AppState {
+    pub app_name: String,
+    // la connexion MongoDB ; None avec les stockages SQLite et PostgreSQL
+    pub conn: Option<Conn>,
+}
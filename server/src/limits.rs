@@ -0,0 +1,297 @@
+// server/src/limits.rs
+
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::error::{InternalError, JsonPayloadError};
+use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue, RETRY_AFTER};
+use actix_web::http::Method;
+use actix_web::{web, Error, HttpMessage, HttpRequest, HttpResponse};
+use futures::future::{ok, Either, Future, Ready};
+
+use crate::auth::Principal;
+use crate::config::Config;
+use crate::health::PROBE_PATHS;
+use shared::ErrorEnvelope;
+
+///
+/// les routes qui reçoivent des imports : leur quota est à part
+///
+pub const IMPORT_PATHS: &[&str] = &["/admin/restore"];
+
+// un seau inutilisé depuis une minute est plein : on peut l'oublier
+const BUCKET_IDLE: Duration = Duration::from_secs(60);
+// au-delà, les seaux pleins sont retirés de la table
+const MAX_BUCKETS: usize = 100_000;
+
+///
+/// les groupes de routes, chacun avec son quota
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RouteGroup {
+    Read,
+    Write,
+    Import,
+}
+
+impl RouteGroup {
+    ///
+    /// le groupe d'une requête ; None pour les sondes et les preflight CORS, jamais limités
+    ///
+    pub fn of(method: &Method, path: &str) -> Option<Self> {
+        if PROBE_PATHS.contains(&path) || method == Method::OPTIONS {
+            None
+        } else if IMPORT_PATHS.contains(&path) {
+            Some(RouteGroup::Import)
+        } else if method == Method::GET || method == Method::HEAD {
+            Some(RouteGroup::Read)
+        } else {
+            Some(RouteGroup::Write)
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            RouteGroup::Read => "reads",
+            RouteGroup::Write => "writes",
+            RouteGroup::Import => "imports",
+        }
+    }
+}
+
+///
+/// le résultat d'une vérification : à mettre dans les en-têtes RateLimit-*
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateStatus {
+    pub allowed: bool,
+    pub limit: u32,
+    pub remaining: u32,
+    // secondes avant que le quota soit entier
+    pub reset: u64,
+    // secondes avant la prochaine requête acceptée (0 si acceptée)
+    pub retry_after: u64,
+}
+
+impl RateStatus {
+    fn set_headers(&self, headers: &mut HeaderMap) {
+        let mut set = |name: &'static str, value: u64| {
+            headers.insert(HeaderName::from_static(name), HeaderValue::from(value));
+        };
+        set("ratelimit-limit", u64::from(self.limit));
+        set("ratelimit-remaining", u64::from(self.remaining));
+        set("ratelimit-reset", self.reset);
+        if !self.allowed {
+            headers.insert(RETRY_AFTER, HeaderValue::from(self.retry_after));
+        }
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+///
+/// un seau de jetons par client et par groupe de routes
+/// le quota est un nombre de requêtes par minute, qui est aussi la rafale maximale
+///
+pub struct RateLimiter {
+    reads: u32,
+    writes: u32,
+    imports: u32,
+    buckets: Mutex<HashMap<(String, RouteGroup), Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            reads: config.rate_limit_reads,
+            writes: config.rate_limit_writes,
+            imports: config.rate_limit_imports,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // requêtes par minute ; 0 : pas de limite
+    fn quota(&self, group: RouteGroup) -> u32 {
+        match group {
+            RouteGroup::Read => self.reads,
+            RouteGroup::Write => self.writes,
+            RouteGroup::Import => self.imports,
+        }
+    }
+
+    ///
+    /// prend un jeton du seau du client ; None si le groupe n'est pas limité
+    ///
+    pub fn check(&self, client: &str, group: RouteGroup, now: Instant) -> Option<RateStatus> {
+        let limit = self.quota(group);
+        if limit == 0 {
+            return None;
+        }
+        let capacity = f64::from(limit);
+        let per_second = capacity / 60.0;
+
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAX_BUCKETS {
+            buckets.retain(|_, bucket| now.duration_since(bucket.updated) < BUCKET_IDLE);
+        }
+        let bucket = buckets.entry((client.to_owned(), group)).or_insert(Bucket {
+            tokens: capacity,
+            updated: now,
+        });
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * per_second).min(capacity);
+        bucket.updated = now;
+
+        let allowed = bucket.tokens >= 1.0;
+        if allowed {
+            bucket.tokens -= 1.0;
+        }
+        let seconds = |tokens: f64| (tokens.max(0.0) / per_second).ceil() as u64;
+        Some(RateStatus {
+            allowed,
+            limit,
+            remaining: bucket.tokens.floor() as u32,
+            reset: seconds(capacity - bucket.tokens),
+            retry_after: if allowed {
+                0
+            } else {
+                seconds(1.0 - bucket.tokens)
+            },
+        })
+    }
+}
+
+///
+/// le client d'une requête : la clé d'API ou l'utilisateur vérifié par Authentication,
+/// sinon son adresse IP ; un Bearer inventé ne donne donc pas un seau neuf
+///
+pub fn client_key(req: &ServiceRequest) -> String {
+    if let Some(principal) = req.extensions().get::<Principal>() {
+        return principal.subject.clone();
+    }
+    // l'adresse de la connexion : X-Forwarded-For se falsifie trop facilement
+    match req.peer_addr() {
+        Some(addr) => format!("ip:{}", addr.ip()),
+        None => "ip:unknown".to_owned(),
+    }
+}
+
+///
+/// le middleware de limitation de débit
+/// les requêtes au-delà du quota reçoivent 429 avec Retry-After,
+/// toutes les réponses limitées portent RateLimit-Limit, RateLimit-Remaining et RateLimit-Reset
+///
+pub struct RateLimit {
+    limiter: Arc<RateLimiter>,
+}
+
+impl RateLimit {
+    // le même RateLimiter pour tous les workers
+    pub fn new(limiter: Arc<RateLimiter>) -> Self {
+        Self { limiter }
+    }
+}
+
+impl<S, B> Transform<S> for RateLimit
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RateLimitMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(RateLimitMiddleware {
+            service,
+            limiter: self.limiter.clone(),
+        })
+    }
+}
+
+pub struct RateLimitMiddleware<S> {
+    service: S,
+    limiter: Arc<RateLimiter>,
+}
+
+impl<S, B> Service for RateLimitMiddleware<S>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Either<
+        Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>,
+        Ready<Result<Self::Response, Self::Error>>,
+    >;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: ServiceRequest) -> Self::Future {
+        let status = RouteGroup::of(req.method(), req.path()).and_then(|group| {
+            self.limiter
+                .check(&client_key(&req), group, Instant::now())
+                .map(|status| (group, status))
+        });
+        let status = match status {
+            Some((group, status)) if !status.allowed => {
+                tracing::warn!(group = group.name(), "rate limited");
+                let mut response = HttpResponse::TooManyRequests().json(ErrorEnvelope::new(
+                    "rate_limited",
+                    format!(
+                        "too many {}, retry in {} s",
+                        group.name(),
+                        status.retry_after
+                    ),
+                ));
+                status.set_headers(response.headers_mut());
+                return Either::Right(ok(req.into_response(response.into_body())));
+            }
+            Some((_, status)) => Some(status),
+            None => None,
+        };
+
+        let fut = self.service.call(req);
+        Either::Left(Box::pin(async move {
+            let mut res = fut.await?;
+            if let Some(status) = status {
+                status.set_headers(res.headers_mut());
+            }
+            Ok(res)
+        }))
+    }
+}
+
+///
+/// la limite des corps JSON (web::Json<Person>...) ; au-delà, 413 au format ErrorEnvelope
+///
+pub fn json_config(config: &Config) -> web::JsonConfig {
+    web::JsonConfig::default()
+        .limit(config.json_limit)
+        .error_handler(|err: JsonPayloadError, _req: &HttpRequest| {
+            let response = match &err {
+                JsonPayloadError::Overflow => HttpResponse::PayloadTooLarge().json(
+                    ErrorEnvelope::new("payload_too_large", "JSON body is too large"),
+                ),
+                other => HttpResponse::BadRequest()
+                    .json(ErrorEnvelope::new("invalid_json", other.to_string())),
+            };
+            InternalError::from_response(err, response).into()
+        })
+}
@@ -0,0 +1,297 @@
+// server/src/metrics.rs
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::{Error, HttpResponse};
+use futures::future::{ok, Future, Ready};
+use once_cell::sync::Lazy;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry,
+    TextEncoder,
+};
+
+use crate::db_mongo;
+use crate::db_postgres;
+use crate::db_sqlite;
+use crate::routes::ROUTE_TABLE;
+use crate::store::{self, Backend};
+use crate::tenancy::Tenant;
+
+///
+/// toutes les mesures du serveur, exposées sur /metrics
+///
+struct Collectors {
+    registry: Registry,
+    http_requests: IntCounterVec,
+    http_duration: HistogramVec,
+    http_in_flight: IntGauge,
+    db_duration: HistogramVec,
+    pool_connections: IntGaugeVec,
+    pool_wait: HistogramVec,
+    persons_total: IntGauge,
+}
+
+impl Collectors {
+    // les définitions sont fixes : une erreur ici est une erreur de programmation
+    fn new() -> Self {
+        let registry =
+            Registry::new_custom(Some("seed".to_owned()), None).expect("metrics registry");
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests by route and status"),
+            &["method", "route", "status"],
+        )
+        .expect("http_requests_total");
+        let http_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "HTTP request latency by route and status",
+            ),
+            &["method", "route", "status"],
+        )
+        .expect("http_request_duration_seconds");
+        let http_in_flight = IntGauge::new("http_requests_in_flight", "HTTP requests in progress")
+            .expect("http_requests_in_flight");
+        let db_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "db_operation_duration_seconds",
+                "Database operation latency by backend and function",
+            ),
+            &["backend", "operation"],
+        )
+        .expect("db_operation_duration_seconds");
+        let pool_connections = IntGaugeVec::new(
+            Opts::new(
+                "db_pool_connections",
+                "Connections of the database pool, by state (open, idle, max)",
+            ),
+            &["backend", "state"],
+        )
+        .expect("db_pool_connections");
+        let pool_wait = HistogramVec::new(
+            HistogramOpts::new(
+                "db_pool_wait_seconds",
+                "Time spent waiting for a pool connection",
+            ),
+            &["backend"],
+        )
+        .expect("db_pool_wait_seconds");
+        let persons_total =
+            IntGauge::new("persons_total", "Persons without a tenant").expect("persons_total");
+
+        for collector in vec![
+            Box::new(http_requests.clone()) as Box<dyn prometheus::core::Collector>,
+            Box::new(http_duration.clone()),
+            Box::new(http_in_flight.clone()),
+            Box::new(db_duration.clone()),
+            Box::new(pool_connections.clone()),
+            Box::new(pool_wait.clone()),
+            Box::new(persons_total.clone()),
+        ] {
+            registry.register(collector).expect("metric registration");
+        }
+
+        Self {
+            registry,
+            http_requests,
+            http_duration,
+            http_in_flight,
+            db_duration,
+            pool_connections,
+            pool_wait,
+            persons_total,
+        }
+    }
+}
+
+static COLLECTORS: Lazy<Collectors> = Lazy::new(Collectors::new);
+
+///
+/// mesure la durée d'une opération de la base, dans un span "db"
+/// db_mongo l'utilise pour chacune de ses fonctions
+///
+pub fn observe_db<T>(backend: &str, operation: &str, f: impl FnOnce() -> T) -> T {
+    let span = tracing::debug_span!("db", backend, operation);
+    let _entered = span.enter();
+    let start = Instant::now();
+    let result = f();
+    COLLECTORS
+        .db_duration
+        .with_label_values(&[backend, operation])
+        .observe(start.elapsed().as_secs_f64());
+    result
+}
+
+///
+/// mesure l'attente d'une connexion du pool
+///
+pub fn observe_pool_wait<T>(backend: &str, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let result = f();
+    COLLECTORS
+        .pool_wait
+        .with_label_values(&[backend])
+        .observe(start.elapsed().as_secs_f64());
+    result
+}
+
+// vrai si le chemin correspond au modèle : /json/{id} pour /json/5e29...
+fn matches(pattern: &str, path: &str) -> bool {
+    let mut pattern_parts = pattern.trim_end_matches('/').split('/');
+    let mut path_parts = path.trim_end_matches('/').split('/');
+    loop {
+        match (pattern_parts.next(), path_parts.next()) {
+            (None, None) => return true,
+            // {file:.*} prend toute la fin du chemin
+            (Some(p), Some(_)) if p.starts_with('{') && p.ends_with(":.*}") => return true,
+            (Some(p), Some(s)) if p.starts_with('{') && p.ends_with('}') && !s.is_empty() => {}
+            (Some(p), Some(s)) if p == s => {}
+            _ => return false,
+        }
+    }
+}
+
+///
+/// le modèle de la route de ROUTE_TABLE qui correspond au chemin
+/// les autres chemins sont regroupés sous "unmatched", pour garder peu de séries
+///
+pub fn route_label(path: &str) -> &'static str {
+    ROUTE_TABLE
+        .iter()
+        .map(|(_, pattern)| *pattern)
+        .find(|pattern| matches(pattern, path))
+        .unwrap_or("unmatched")
+}
+
+///
+/// le middleware qui compte les requêtes et mesure leur durée
+/// à mettre avec App::wrap(RequestMetrics)
+///
+pub struct RequestMetrics;
+
+impl<S, B> Transform<S> for RequestMetrics
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RequestMetricsMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(RequestMetricsMiddleware { service })
+    }
+}
+
+pub struct RequestMetricsMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service for RequestMetricsMiddleware<S>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: ServiceRequest) -> Self::Future {
+        let method = req.method().as_str().to_owned();
+        let route = route_label(req.path());
+        let start = Instant::now();
+        COLLECTORS.http_in_flight.inc();
+
+        let fut = self.service.call(req);
+        Box::pin(async move {
+            let res = fut.await;
+            COLLECTORS.http_in_flight.dec();
+            let status = match &res {
+                Ok(res) => res.status(),
+                Err(e) => e.as_response_error().status_code(),
+            };
+            let labels = [method.as_str(), route, status.as_str()];
+            COLLECTORS.http_requests.with_label_values(&labels).inc();
+            COLLECTORS
+                .http_duration
+                .with_label_values(&labels)
+                .observe(start.elapsed().as_secs_f64());
+            res
+        })
+    }
+}
+
+// l'état d'un pool r2d2 : connexions ouvertes, libres et maximum
+fn set_pool_state(backend: Backend, state: r2d2::State, max_size: u32) {
+    let gauge = |name: &str, value: u32| {
+        COLLECTORS
+            .pool_connections
+            .with_label_values(&[backend.name(), name])
+            .set(i64::from(value));
+    };
+    gauge("open", state.connections);
+    gauge("idle", state.idle_connections);
+    gauge("max", max_size);
+}
+
+// les mesures lues au moment de la collecte : pools et nombre de personnes
+fn refresh_gauges() {
+    let backend = store::backend();
+    match backend {
+        Backend::Mongo => {
+            if let Some(pool) = db_mongo::opened_pool() {
+                set_pool_state(backend, pool.state(), pool.max_size());
+            }
+        }
+        Backend::Sqlite => {
+            if let Some(pool) = db_sqlite::opened_pool(db_sqlite::path()) {
+                set_pool_state(backend, pool.state(), pool.max_size());
+            }
+        }
+        Backend::Postgres => {
+            if let Some(pool) = db_postgres::opened_pool() {
+                set_pool_state(backend, pool.state(), pool.max_size());
+            }
+        }
+    }
+
+    // une page d'une personne suffit : search renvoie aussi le total
+    let total =
+        store::open_store(&Tenant::default()).and_then(|store| store.search(None, None, 0, 1));
+    if let Ok((_, total)) = total {
+        COLLECTORS.persons_total.set(total);
+    }
+}
+
+///
+/// les mesures au format texte de Prometheus
+///
+pub fn render() -> Result<(String, String), prometheus::Error> {
+    refresh_gauges();
+    let encoder = TextEncoder::new();
+    let mut buffer = Vec::new();
+    encoder.encode(&COLLECTORS.registry.gather(), &mut buffer)?;
+    Ok((
+        encoder.format_type().to_owned(),
+        String::from_utf8_lossy(&buffer).into_owned(),
+    ))
+}
+
+pub async fn metrics_hdl() -> HttpResponse {
+    match render() {
+        Ok((content_type, body)) => HttpResponse::Ok().content_type(content_type).body(body),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
@@ -0,0 +1,253 @@
+// server/src/migrations.rs
+
+use bson::{doc, Bson, Document};
+use chrono::Utc;
+use mongodb::options::UpdateOptions;
+use mongodb::{Client, Database};
+use serde::Serialize;
+
+use crate::db_mongo;
+use crate::errors::MyError;
+use crate::tenancy::TenantMode;
+
+///
+/// la version du schéma des documents Person écrits par cette version du serveur
+///
+pub const CURRENT_SCHEMA_VERSION: i32 = 1;
+
+const MIGRATIONS_COLLECTION: &str = "_migrations";
+
+///
+/// une migration de la base
+/// pending compte les documents à changer, apply les change
+/// les deux doivent pouvoir être relancés sans effet de bord (idempotents)
+///
+pub struct Migration {
+    pub version: i32,
+    pub name: &'static str,
+    pub pending: fn(&Database) -> Result<i64, MyError>,
+    pub apply: fn(&Database) -> Result<i64, MyError>,
+}
+
+///
+/// toutes les migrations, dans l'ordre des versions
+///
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "persons_schema_version",
+        pending: persons_schema_version_pending,
+        apply: persons_schema_version_apply,
+    },
+    Migration {
+        version: 2,
+        name: "persons_name_index",
+        pending: persons_name_index_pending,
+        apply: persons_name_index_apply,
+    },
+];
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct MigrationReport {
+    pub database: String,
+    pub version: i32,
+    pub name: String,
+    pub applied: bool,
+    pub documents: i64,
+}
+
+// ---- mise à jour d'un document Person ----
+
+///
+/// met un document Person au schéma actuel, en mémoire
+/// utilisé à la lecture : un vieux document ne fait plus échouer toute la liste
+///
+pub fn upgrade_person_document(mut doc: Document) -> Document {
+    let version = match doc.get("schema_version") {
+        Some(Bson::I32(v)) => *v,
+        Some(Bson::I64(v)) => *v as i32,
+        _ => 0,
+    };
+    if version < 1 {
+        doc = person_v0_to_v1(doc);
+    }
+    doc
+}
+
+// v1 : nom et prenom sont toujours des chaînes, et schema_version est présent
+fn person_v0_to_v1(mut doc: Document) -> Document {
+    for field in &["nom", "prenom"] {
+        let value = match doc.get(field) {
+            Some(Bson::String(s)) => s.clone(),
+            Some(Bson::Null) | None => String::new(),
+            Some(other) => other.to_string().trim_matches('"').to_owned(),
+        };
+        doc.insert(*field, value);
+    }
+    doc.insert("schema_version", 1);
+    doc
+}
+
+// ---- migration 1 : schema_version sur tous les documents Person ----
+
+fn persons_without_version() -> Document {
+    doc! {"schema_version": {"$exists": false}}
+}
+
+fn persons_schema_version_pending(db: &Database) -> Result<i64, MyError> {
+    Ok(db
+        .collection("Persons")
+        .count_documents(Some(persons_without_version()), None)?)
+}
+
+fn persons_schema_version_apply(db: &Database) -> Result<i64, MyError> {
+    let coll = db.collection("Persons");
+    let cursor = coll.find(Some(persons_without_version()), None)?;
+    let mut count = 0;
+    for row in cursor {
+        let old = row?;
+        let id = match old.get("_id") {
+            Some(id) => id.clone(),
+            None => continue,
+        };
+        let new = person_v0_to_v1(old);
+        coll.replace_one(doc! {"_id": id}, new, None)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+// ---- migration 2 : index sur nom et prenom, pour le tri des pages ----
+
+fn persons_name_index_pending(_db: &Database) -> Result<i64, MyError> {
+    Ok(1)
+}
+
+fn persons_name_index_apply(db: &Database) -> Result<i64, MyError> {
+    // createIndexes ne fait rien si l'index existe déjà
+    db.run_command(
+        doc! {
+            "createIndexes": "Persons",
+            "indexes": [{"key": {"nom": 1, "prenom": 1}, "name": "nom_prenom"}],
+        },
+        None,
+    )?;
+    Ok(1)
+}
+
+// ---- exécution ----
+
+///
+/// les versions déjà appliquées, lues dans _migrations
+///
+pub fn applied_versions(db: &Database) -> Result<Vec<i32>, MyError> {
+    let cursor = db.collection(MIGRATIONS_COLLECTION).find(None, None)?;
+    let mut versions = Vec::new();
+    for row in cursor {
+        if let Ok(version) = row?.get_i32("_id") {
+            versions.push(version);
+        }
+    }
+    versions.sort();
+    Ok(versions)
+}
+
+///
+/// les bases à migrer : la base principale et, en mode database,
+/// toutes les bases de locataire déjà créées, nommées {database}_{tenant}
+/// une base de locataire créée après coup n'a que la mise à jour à la lecture
+/// jusqu'au prochain seedctl migrate ou redémarrage
+///
+pub fn databases(mode: TenantMode) -> Result<Vec<String>, MyError> {
+    let main = db_mongo::settings().database.clone();
+    let mut names = vec![main.clone()];
+    if mode == TenantMode::Database {
+        let prefix = format!("{}_", main);
+        let client = Client::with_uri_str(&db_mongo::settings().uri)?;
+        let mut tenants: Vec<String> = client
+            .list_database_names(None)?
+            .into_iter()
+            .filter(|name| name.starts_with(&prefix))
+            .collect();
+        tenants.sort();
+        names.extend(tenants);
+    }
+    Ok(names)
+}
+
+// les migrations pas encore appliquées dans une base
+fn pending_in(db: &Database) -> Result<Vec<&'static Migration>, MyError> {
+    let applied = applied_versions(db)?;
+    Ok(MIGRATIONS
+        .iter()
+        .filter(|m| !applied.contains(&m.version))
+        .collect())
+}
+
+///
+/// les migrations qui n'ont pas encore été appliquées, avec le nom de leur base
+///
+pub fn pending_migrations(mode: TenantMode) -> Result<Vec<(String, &'static Migration)>, MyError> {
+    let mut pending = Vec::new();
+    for name in databases(mode)? {
+        let db = db_mongo::get_database_named(&name)?;
+        for migration in pending_in(&db)? {
+            pending.push((name.clone(), migration));
+        }
+    }
+    Ok(pending)
+}
+
+///
+/// applique dans l'ordre les migrations manquantes, base par base
+/// chaque base garde ses versions appliquées dans sa propre collection _migrations
+/// avec dry_run, on compte seulement les documents qui seraient changés
+///
+pub fn run_migrations(dry_run: bool, mode: TenantMode) -> Result<Vec<MigrationReport>, MyError> {
+    let mut reports = Vec::new();
+    for name in databases(mode)? {
+        let db = db_mongo::get_database_named(&name)?;
+        reports.extend(run_migrations_in(&name, &db, dry_run)?);
+    }
+    Ok(reports)
+}
+
+fn run_migrations_in(
+    name: &str,
+    db: &Database,
+    dry_run: bool,
+) -> Result<Vec<MigrationReport>, MyError> {
+    let mut reports = Vec::new();
+    for migration in pending_in(db)? {
+        if dry_run {
+            reports.push(MigrationReport {
+                database: name.to_owned(),
+                version: migration.version,
+                name: migration.name.to_owned(),
+                applied: false,
+                documents: (migration.pending)(db)?,
+            });
+            continue;
+        }
+
+        let documents = (migration.apply)(db)?;
+        let options = UpdateOptions::builder().upsert(true).build();
+        db.collection(MIGRATIONS_COLLECTION).update_one(
+            doc! {"_id": migration.version},
+            doc! {"$set": {
+                "name": migration.name,
+                "applied_at": Utc::now().to_rfc3339(),
+                "documents": documents,
+            }},
+            Some(options),
+        )?;
+        reports.push(MigrationReport {
+            database: name.to_owned(),
+            version: migration.version,
+            name: migration.name.to_owned(),
+            applied: true,
+            documents,
+        });
+    }
+    Ok(reports)
+}
@@ -0,0 +1,441 @@
+// server/src/oidc.rs
+
+use std::collections::HashMap;
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use actix_web::client::Client;
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+
+use crate::config::Config;
+use crate::errors::MyError;
+use crate::tenancy::validate_tenant_id;
+use crate::users::Identity;
+use shared::Scope;
+
+// une connexion commencée doit revenir du fournisseur dans ce délai
+pub const LOGIN_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+// au-delà, les connexions en cours les plus anciennes sont oubliées
+const MAX_PENDING_LOGINS: usize = 10_000;
+// les clés du fournisseur sont relues au moins toutes les heures
+const JWKS_TTL: Duration = Duration::from_secs(3600);
+// une clé inconnue (kid) fait relire les clés, au plus une fois par minute
+const JWKS_REFRESH_MIN: Duration = Duration::from_secs(60);
+// l'écart d'horloge toléré avec le fournisseur, en secondes
+const CLOCK_LEEWAY: u64 = 60;
+
+// les signatures acceptées pour l'ID token : jamais none ni HMAC
+const ALGORITHMS: &[Algorithm] = &[
+    Algorithm::RS256,
+    Algorithm::RS384,
+    Algorithm::RS512,
+    Algorithm::PS256,
+    Algorithm::PS384,
+    Algorithm::PS512,
+];
+
+///
+/// le document /.well-known/openid-configuration du fournisseur
+///
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Discovery {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks_uri: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct Jwk {
+    kid: Option<String>,
+    kty: String,
+    n: Option<String>,
+    e: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct JwkSet {
+    keys: Vec<Jwk>,
+}
+
+struct JwksCache {
+    keys: Vec<Jwk>,
+    fetched: Instant,
+}
+
+#[derive(Deserialize, Debug)]
+struct TokenResponse {
+    id_token: String,
+}
+
+// une connexion en cours, retrouvée par son state au retour du fournisseur
+struct PendingLogin {
+    verifier: String,
+    nonce: String,
+    return_to: String,
+    created: Instant,
+}
+
+///
+/// un verifier PKCE aléatoire (64 caractères) et son challenge S256
+///
+pub fn pkce_pair() -> (String, String) {
+    let verifier = format!(
+        "{}{}",
+        uuid::Uuid::new_v4().to_simple(),
+        uuid::Uuid::new_v4().to_simple()
+    );
+    let challenge = pkce_challenge(&verifier);
+    (verifier, challenge)
+}
+
+///
+/// BASE64URL(SHA256(verifier)), sans remplissage (RFC 7636)
+///
+pub fn pkce_challenge(verifier: &str) -> String {
+    base64::encode_config(Sha256::digest(verifier.as_bytes()), base64::URL_SAFE_NO_PAD)
+}
+
+///
+/// l'empreinte du state gardée dans le cookie du navigateur, BASE64URL(SHA256(state))
+///
+pub fn state_hash(state: &str) -> String {
+    pkce_challenge(state)
+}
+
+///
+/// les droits donnés par les groupes du fournisseur (SEED_OIDC_ROLES)
+/// sans groupe reconnu, le droit par défaut (SEED_OIDC_DEFAULT_ROLE), s'il y en a un
+///
+pub fn roles_for(
+    groups: &[String],
+    roles: &[(String, Scope)],
+    default: Option<Scope>,
+) -> Vec<Scope> {
+    let mut scopes: Vec<Scope> = roles
+        .iter()
+        .filter(|(group, _)| groups.contains(group))
+        .map(|(_, scope)| *scope)
+        .collect();
+    if scopes.is_empty() {
+        scopes.extend(default);
+    }
+    scopes.sort();
+    scopes.dedup();
+    scopes
+}
+
+///
+/// seuls les chemins locaux sont acceptés comme retour après connexion
+///
+pub fn safe_return_to(return_to: Option<&str>) -> String {
+    match return_to {
+        Some(path) if path.starts_with('/') && !path.starts_with("//") && !path.contains('\\') => {
+            path.to_owned()
+        }
+        _ => "/".to_owned(),
+    }
+}
+
+// une revendication texte, ou une liste de textes (groups)
+fn claim_strings(value: Option<&Value>) -> Vec<String> {
+    match value {
+        Some(Value::Array(items)) => items
+            .iter()
+            .filter_map(|item| item.as_str().map(str::to_owned))
+            .collect(),
+        Some(Value::String(item)) => vec![item.clone()],
+        _ => Vec::new(),
+    }
+}
+
+///
+/// la connexion par OpenID Connect : code d'autorisation avec PKCE
+/// la découverte et les clés (JWKS) du fournisseur sont gardées en cache
+///
+pub struct OidcClient {
+    issuer: String,
+    client_id: String,
+    client_secret: Option<String>,
+    redirect_url: String,
+    scopes: Vec<String>,
+    groups_claim: String,
+    tenant_claim: Option<String>,
+    roles: Vec<(String, Scope)>,
+    default_role: Option<Scope>,
+    discovery: RwLock<Option<Discovery>>,
+    jwks: RwLock<Option<JwksCache>>,
+    pending: Mutex<HashMap<String, PendingLogin>>,
+}
+
+impl OidcClient {
+    ///
+    /// None si SEED_OIDC_ISSUER n'est pas configuré : la connexion est désactivée
+    ///
+    pub fn new(config: &Config) -> Option<Self> {
+        let issuer = config.oidc_issuer.as_ref()?;
+        Some(Self {
+            issuer: issuer.clone(),
+            client_id: config.oidc_client_id.clone(),
+            client_secret: config.oidc_client_secret.clone(),
+            redirect_url: config.oidc_redirect_url.clone(),
+            scopes: config.oidc_scopes.clone(),
+            groups_claim: config.oidc_groups_claim.clone(),
+            tenant_claim: config.oidc_tenant_claim.clone(),
+            roles: config.oidc_roles.clone(),
+            default_role: config.oidc_default_role,
+            discovery: RwLock::new(None),
+            jwks: RwLock::new(None),
+            pending: Mutex::new(HashMap::new()),
+        })
+    }
+
+    ///
+    /// le document de découverte, lu une fois
+    /// son issuer doit être exactement celui de la configuration, comme l'iss des ID tokens
+    ///
+    pub async fn discovery(&self) -> Result<Discovery, MyError> {
+        if let Some(discovery) = self.discovery.read().unwrap().as_ref() {
+            return Ok(discovery.clone());
+        }
+        let url = format!(
+            "{}/.well-known/openid-configuration",
+            self.issuer.trim_end_matches('/')
+        );
+        let discovery: Discovery = fetch_json(&url).await?;
+        if discovery.issuer != self.issuer {
+            return Err(MyError::Oidc(format!(
+                "the provider announces the issuer {}",
+                discovery.issuer
+            )));
+        }
+        *self.discovery.write().unwrap() = Some(discovery.clone());
+        Ok(discovery)
+    }
+
+    ///
+    /// l'adresse du fournisseur où envoyer le navigateur, et le state à lier au navigateur
+    /// state, nonce et le verifier PKCE sont gardés jusqu'au retour
+    ///
+    pub async fn authorize_url(
+        &self,
+        return_to: Option<&str>,
+    ) -> Result<(String, String), MyError> {
+        let discovery = self.discovery().await?;
+        let (verifier, challenge) = pkce_pair();
+        let state = uuid::Uuid::new_v4().to_simple().to_string();
+        let nonce = uuid::Uuid::new_v4().to_simple().to_string();
+        let query = serde_urlencoded::to_string(&[
+            ("response_type", "code"),
+            ("client_id", self.client_id.as_str()),
+            ("redirect_uri", self.redirect_url.as_str()),
+            ("scope", self.scopes.join(" ").as_str()),
+            ("state", state.as_str()),
+            ("nonce", nonce.as_str()),
+            ("code_challenge", challenge.as_str()),
+            ("code_challenge_method", "S256"),
+        ])
+        .map_err(|e| MyError::Oidc(e.to_string()))?;
+
+        let now = Instant::now();
+        let mut pending = self.pending.lock().unwrap();
+        pending.retain(|_, login| now.duration_since(login.created) < LOGIN_TIMEOUT);
+        while pending.len() >= MAX_PENDING_LOGINS {
+            let oldest = pending
+                .iter()
+                .min_by_key(|(_, login)| login.created)
+                .map(|(state, _)| state.clone());
+            match oldest {
+                Some(oldest) => pending.remove(&oldest),
+                None => break,
+            };
+        }
+        pending.insert(
+            state.clone(),
+            PendingLogin {
+                verifier,
+                nonce,
+                return_to: safe_return_to(return_to),
+                created: now,
+            },
+        );
+
+        let separator = if discovery.authorization_endpoint.contains('?') {
+            '&'
+        } else {
+            '?'
+        };
+        let url = format!("{}{}{}", discovery.authorization_endpoint, separator, query);
+        Ok((url, state))
+    }
+
+    ///
+    /// le retour du fournisseur : échange le code contre l'ID token et le valide
+    /// renvoie l'identité et le chemin où renvoyer le navigateur
+    ///
+    pub async fn callback(&self, code: &str, state: &str) -> Result<(Identity, String), MyError> {
+        let login = self
+            .pending
+            .lock()
+            .unwrap()
+            .remove(state)
+            .filter(|login| login.created.elapsed() < LOGIN_TIMEOUT)
+            .ok_or_else(|| MyError::Unauthorized("unknown or expired login".to_owned()))?;
+
+        let discovery = self.discovery().await?;
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", self.redirect_url.as_str()),
+            ("client_id", self.client_id.as_str()),
+            ("code_verifier", login.verifier.as_str()),
+        ];
+        if let Some(secret) = &self.client_secret {
+            form.push(("client_secret", secret.as_str()));
+        }
+        let mut response = Client::default()
+            .post(&discovery.token_endpoint)
+            .header("Accept", "application/json")
+            .send_form(&form)
+            .await
+            .map_err(|e| MyError::Oidc(format!("token endpoint: {}", e)))?;
+        if !response.status().is_success() {
+            return Err(MyError::Unauthorized(format!(
+                "the provider refused the code ({})",
+                response.status()
+            )));
+        }
+        let tokens: TokenResponse = response
+            .json()
+            .await
+            .map_err(|e| MyError::Oidc(format!("token endpoint: {}", e)))?;
+
+        let identity = self
+            .validate_id_token(&tokens.id_token, &login.nonce)
+            .await?;
+        Ok((identity, login.return_to))
+    }
+
+    ///
+    /// vérifie la signature (clés JWKS), l'issuer, l'audience, les dates et le nonce
+    ///
+    pub async fn validate_id_token(&self, token: &str, nonce: &str) -> Result<Identity, MyError> {
+        let invalid =
+            |reason: String| MyError::Unauthorized(format!("invalid ID token: {}", reason));
+        let header = decode_header(token).map_err(|e| invalid(e.to_string()))?;
+        if !ALGORITHMS.contains(&header.alg) {
+            return Err(invalid(format!(
+                "{:?} signatures are not accepted",
+                header.alg
+            )));
+        }
+        let jwk = self.signing_key(header.kid.as_deref()).await?;
+        let (n, e) = match (&jwk.n, &jwk.e) {
+            (Some(n), Some(e)) if jwk.kty == "RSA" => (n, e),
+            _ => return Err(invalid("the signing key is not an RSA key".to_owned())),
+        };
+
+        let mut validation = Validation::new(header.alg);
+        validation.leeway = CLOCK_LEEWAY;
+        validation.iss = Some(self.issuer.clone());
+        validation.set_audience(&[self.client_id.as_str()]);
+        let claims = decode::<Map<String, Value>>(
+            token,
+            &DecodingKey::from_rsa_components(n, e),
+            &validation,
+        )
+        .map_err(|e| invalid(e.to_string()))?
+        .claims;
+
+        if claims.get("nonce").and_then(Value::as_str) != Some(nonce) {
+            return Err(invalid("nonce mismatch".to_owned()));
+        }
+        let sub = claims
+            .get("sub")
+            .and_then(Value::as_str)
+            .ok_or_else(|| invalid("no sub".to_owned()))?;
+        let email = claims
+            .get("email")
+            .and_then(Value::as_str)
+            .map(str::to_owned);
+        let name = ["name", "preferred_username"]
+            .iter()
+            .find_map(|claim| claims.get(*claim).and_then(Value::as_str))
+            .map(str::to_owned)
+            .or_else(|| email.clone())
+            .unwrap_or_else(|| sub.to_owned());
+        let tenant = self
+            .tenant_claim
+            .as_ref()
+            .and_then(|claim| claims.get(claim))
+            .and_then(Value::as_str)
+            .map(validate_tenant_id)
+            .transpose()?;
+
+        Ok(Identity {
+            subject: format!("{}#{}", self.issuer, sub),
+            name,
+            email,
+            groups: claim_strings(claims.get(&self.groups_claim)),
+            tenant,
+        })
+    }
+
+    ///
+    /// les droits de l'identité d'après ses groupes
+    ///
+    pub fn roles(&self, identity: &Identity) -> Vec<Scope> {
+        roles_for(&identity.groups, &self.roles, self.default_role)
+    }
+
+    // la clé du kid ; les clés sont relues si elles sont vieilles ou si le kid est inconnu
+    async fn signing_key(&self, kid: Option<&str>) -> Result<Jwk, MyError> {
+        let find = |keys: &[Jwk]| {
+            keys.iter()
+                .find(|key| kid.is_none() || key.kid.as_deref() == kid)
+                .cloned()
+        };
+        let refetch = match self.jwks.read().unwrap().as_ref() {
+            Some(cache) if cache.fetched.elapsed() < JWKS_TTL => match find(&cache.keys) {
+                Some(key) => return Ok(key),
+                None => cache.fetched.elapsed() >= JWKS_REFRESH_MIN,
+            },
+            _ => true,
+        };
+        if refetch {
+            let discovery = self.discovery().await?;
+            let set: JwkSet = fetch_json(&discovery.jwks_uri).await?;
+            *self.jwks.write().unwrap() = Some(JwksCache {
+                keys: set.keys,
+                fetched: Instant::now(),
+            });
+        }
+        self.jwks
+            .read()
+            .unwrap()
+            .as_ref()
+            .and_then(|cache| find(&cache.keys))
+            .ok_or_else(|| {
+                MyError::Unauthorized("invalid ID token: unknown signing key".to_owned())
+            })
+    }
+}
+
+async fn fetch_json<T: serde::de::DeserializeOwned>(url: &str) -> Result<T, MyError> {
+    let mut response = Client::default()
+        .get(url)
+        .header("Accept", "application/json")
+        .send()
+        .await
+        .map_err(|e| MyError::Oidc(format!("{}: {}", url, e)))?;
+    if !response.status().is_success() {
+        return Err(MyError::Oidc(format!("{}: {}", url, response.status())));
+    }
+    response
+        .json()
+        .await
+        .map_err(|e| MyError::Oidc(format!("{}: {}", url, e)))
+}
@@ -0,0 +1,534 @@
+// server/src/openapi.rs
+
+use actix_web::{web, HttpResponse};
+use schemars::gen::SchemaSettings;
+use serde_json::{json, Map, Value};
+
+use actix_web::http::Method;
+
+use crate::auth::required_scope;
+use crate::health::PROBE_PATHS;
+use crate::idempotency::IDEMPOTENCY_KEY;
+use crate::routes::ROUTE_TABLE;
+use crate::sessions::SESSION_COOKIE;
+use shared::{
+    ApiKey, BatchRequest, BatchResponse, ComplianceEntry, Delivery, ErasureReport, ErrorEnvelope,
+    IssuedApiKey, ListPersons, NewApiKey, Person, PersonEvent,
+};
+
+///
+/// la description d'une route de ROUTE_TABLE
+/// None si la route n'est pas documentée : le test le signale
+///
+fn describe(method: &str, path: &str) -> Option<Value> {
+    let person = json!({"$ref": "#/components/schemas/Person"});
+    let persons = json!({"type": "array", "items": person});
+    let api_key = json!({"$ref": "#/components/schemas/ApiKey"});
+    let issued_key = json!({"$ref": "#/components/schemas/IssuedApiKey"});
+    let compliance_entry = json!({"$ref": "#/components/schemas/ComplianceEntry"});
+    let id_param = |name: &str| json!({"name": name, "in": "path", "required": true, "schema": {"type": "string"}});
+
+    let op = match (method, path) {
+        ("get", "/") => json!({
+            "summary": "The client page, or the application name when the client is not built",
+            "responses": {"200": {
+                "description": "index.html, or a hello message",
+                "content": {
+                    "text/html": {"schema": {"type": "string"}},
+                    "text/plain": {"schema": {"type": "string"}},
+                },
+            }},
+        }),
+        ("get", "/string") => json!({
+            "summary": "List persons as plain text",
+            "responses": {"200": text_response("One person per line")},
+        }),
+        ("get", "/json") => json!({
+            "summary": "List persons",
+            "responses": {"200": json_response("All persons", persons)},
+        }),
+        ("post", "/json") => idempotent(json!({
+            "summary": "Add a person",
+            "requestBody": json_body(person.clone()),
+            "responses": {"200": json_response("The added person with its id", person.clone())},
+        })),
+        ("get", "/json_list") => json!({
+            "summary": "List persons wrapped in ListPersons",
+            "responses": {"200": json_response(
+                "All persons",
+                json!({"$ref": "#/components/schemas/ListPersons"}),
+            )},
+        }),
+        ("get", "/json/{id}") => json!({
+            "summary": "Get a person by id",
+            "parameters": [id_param("id")],
+            "responses": {"200": json_response(
+                "The person, or null",
+                json!({"nullable": true, "allOf": [person]}),
+            )},
+        }),
+        ("put", "/json/{id}") => json!({
+            "summary": "Replace a person",
+            "parameters": [id_param("id")],
+            "requestBody": json_body(person.clone()),
+            "responses": {"200": json_response(
+                "The person before the change, or null",
+                json!({"nullable": true, "allOf": [person]}),
+            )},
+        }),
+        ("delete", "/json/{id}") => json!({
+            "summary": "Delete the person of the path id",
+            "parameters": [id_param("id")],
+            "requestBody": {"required": false, "content": {"application/json": {
+                "schema": person.clone()}}},
+            "responses": {
+                "200": json_response(
+                    "The deleted person, or null",
+                    json!({"nullable": true, "allOf": [person]}),
+                ),
+                "400": {"$ref": "#/components/responses/Error"},
+            },
+        }),
+        ("post", "/persons/batch") => idempotent(json!({
+            "summary": "Run person operations in order, all or nothing unless partial is true",
+            "requestBody": json_body(json!({"$ref": "#/components/schemas/BatchRequest"})),
+            "responses": {"200": json_response(
+                "One result per operation; committed is false when an all-or-nothing batch failed",
+                json!({"$ref": "#/components/schemas/BatchResponse"}),
+            )},
+        })),
+        ("get", "/persons/{id}/gdpr-export") => admin(json!({
+            "summary": "Everything stored about a person, as one JSON file (right of access)",
+            "parameters": [id_param("id")],
+            "responses": {
+                "200": json_response("The export, noted in the compliance log", json!({
+                    "type": "object",
+                    "properties": {
+                        "format": {"type": "string"},
+                        "generated_at": {"type": "integer"},
+                        "tenant": {"type": "string", "nullable": true},
+                        "person": person.clone(),
+                        "revisions": {"type": "array", "items": {"type": "object"}},
+                        "audit": {"type": "array", "items": compliance_entry.clone()},
+                        "webhook_deliveries": {"type": "array", "items": {"type": "object"}},
+                    },
+                })),
+                "404": {"$ref": "#/components/responses/Error"},
+                "410": {"$ref": "#/components/responses/Error"},
+            },
+        })),
+        ("post", "/persons/{id}/erase") => admin(json!({
+            "summary": "Erase a person everywhere, history included, and keep a tombstone",
+            "parameters": [id_param("id")],
+            "responses": {
+                "200": json_response(
+                    "What was removed, with the tombstone",
+                    json!({"$ref": "#/components/schemas/ErasureReport"}),
+                ),
+                "404": {"$ref": "#/components/responses/Error"},
+                "410": {"$ref": "#/components/responses/Error"},
+            },
+        })),
+        ("get", "/ws") => json!({
+            "summary": "WebSocket stream of PersonEvent messages",
+            "responses": {"101": {"description": "Switching to the WebSocket protocol"}},
+        }),
+        ("get", "/events") => json!({
+            "summary": "Server-Sent Events stream of person changes",
+            "parameters": [
+                {"name": "types", "in": "query", "required": false,
+                 "description": "Comma-separated event types: created, updated, deleted",
+                 "schema": {"type": "string"}},
+                {"name": "Last-Event-ID", "in": "header", "required": false,
+                 "description": "Replay the events after this id",
+                 "schema": {"type": "integer"}},
+            ],
+            "responses": {"200": {
+                "description": "text/event-stream, data is a Person",
+                "content": {"text/event-stream": {"schema": {"type": "string"}}},
+            }},
+        }),
+        ("get", "/webhooks") => admin(json!({
+            "summary": "List the webhook subscriptions of the request tenant",
+            "responses": {"200": json_response("Webhooks", json!({"type": "array", "items": {"type": "object"}}))},
+        })),
+        ("post", "/webhooks") => admin(json!({
+            "summary": "Register a webhook subscription for the request tenant",
+            "requestBody": json_body(json!({
+                "type": "object",
+                "required": ["url", "secret"],
+                "properties": {
+                    "url": {"type": "string"},
+                    "events": {"type": "array", "items": {"type": "string"}},
+                    "secret": {"type": "string"},
+                },
+            })),
+            "responses": {"200": json_response("The webhook", json!({"type": "object"}))},
+        })),
+        ("delete", "/webhooks/{id}") => admin(json!({
+            "summary": "Delete a webhook subscription",
+            "parameters": [id_param("id")],
+            "responses": {"200": json_response("The deleted webhook", json!({"type": "object"}))},
+        })),
+        ("get", "/webhooks/{id}/deliveries") => admin(json!({
+            "summary": "Deliveries of a webhook, most recent first",
+            "description": "404 when the webhook belongs to another tenant",
+            "parameters": [id_param("id")],
+            "responses": {"200": json_response("Deliveries", json!({"type": "array", "items": {"$ref": "#/components/schemas/Delivery"}}))},
+        })),
+        ("post", "/graphql") => json!({
+            "summary": "GraphQL queries and mutations (subscriptions over WebSocket on GET)",
+            "requestBody": json_body(json!({"type": "object"})),
+            "responses": {"200": json_response("GraphQL response", json!({"type": "object"}))},
+        }),
+        ("get", "/graphiql") => json!({
+            "summary": "GraphiQL playground, when SEED_GRAPHIQL is enabled",
+            "responses": {"200": html_response()},
+        }),
+        ("get", "/openapi.json") => json!({
+            "summary": "This OpenAPI document",
+            "responses": {"200": json_response("OpenAPI 3 document", json!({"type": "object"}))},
+        }),
+        ("get", "/docs") => json!({
+            "summary": "API documentation page (Swagger UI)",
+            "responses": {"200": html_response()},
+        }),
+        ("get", "/docs/{file}") => json!({
+            "summary": "Swagger UI files used by the documentation page",
+            "parameters": [id_param("file")],
+            "responses": {
+                "200": {"description": "swagger-ui-bundle.js or swagger-ui.css"},
+                "404": {"description": "No such file"},
+            },
+        }),
+        ("get", "/graphql") => json!({
+            "summary": "GraphQL subscriptions over WebSocket (Upgrade: websocket)",
+            "responses": {"101": {"description": "Switching to the WebSocket protocol"}},
+        }),
+        ("get", "/admin/backup") => admin(json!({
+            "summary": "Download a tar.gz backup of the persons and the audit log",
+            "responses": {"200": {
+                "description": "Backup archive with manifest and SHA-256 checksums",
+                "content": {"application/gzip": {"schema": {"type": "string", "format": "binary"}}},
+            }},
+        })),
+        ("post", "/admin/restore") => admin(json!({
+            "summary": "Verify then restore a backup archive",
+            "parameters": [{"name": "mode", "in": "query", "required": false,
+                            "schema": {"type": "string", "enum": ["merge", "replace"]}}],
+            "requestBody": {"required": true, "content": {"application/gzip": {
+                "schema": {"type": "string", "format": "binary"}}}},
+            "responses": {"200": json_response("Restore report", json!({"type": "object"}))},
+        })),
+        ("get", "/admin/keys") => admin(json!({
+            "summary": "List API keys, revoked ones included; secrets are never returned",
+            "responses": {"200": json_response("API keys", json!({"type": "array", "items": api_key}))},
+        })),
+        ("post", "/admin/keys") => admin(json!({
+            "summary": "Issue an API key",
+            "requestBody": json_body(json!({"$ref": "#/components/schemas/NewApiKey"})),
+            "responses": {"200": json_response("The key and its secret, shown only once", issued_key.clone())},
+        })),
+        ("delete", "/admin/keys/{id}") => admin(json!({
+            "summary": "Revoke an API key",
+            "parameters": [id_param("id")],
+            "responses": {
+                "200": json_response("The revoked key", api_key.clone()),
+                "404": {"$ref": "#/components/responses/Error"},
+            },
+        })),
+        ("post", "/admin/keys/{id}/rotate") => admin(json!({
+            "summary": "Replace the secret of an API key; the old secret stops working",
+            "parameters": [id_param("id")],
+            "responses": {
+                "200": json_response("The key and its new secret", issued_key),
+                "404": {"$ref": "#/components/responses/Error"},
+            },
+        })),
+        ("get", "/admin/compliance") => admin(json!({
+            "summary": "Compliance log of exports and erasures, oldest first",
+            "parameters": [{"name": "person", "in": "query", "required": false,
+                            "description": "Only the entries of this person id",
+                            "schema": {"type": "string"}}],
+            "responses": {"200": json_response(
+                "Compliance entries",
+                json!({"type": "array", "items": compliance_entry}),
+            )},
+        })),
+        ("get", "/auth/login") => json!({
+            "summary": "Start single sign-on: redirect to the OpenID Connect provider (PKCE)",
+            "parameters": [{"name": "return_to", "in": "query", "required": false,
+                            "description": "Local path to open after the login",
+                            "schema": {"type": "string"}}],
+            "responses": {
+                "302": {"description": "Redirect to the provider"},
+                "404": {"$ref": "#/components/responses/Error"},
+            },
+        }),
+        ("get", "/auth/callback") => json!({
+            "summary": "Return from the provider: validate the ID token, provision the user, open a session",
+            "parameters": [
+                {"name": "code", "in": "query", "required": true, "schema": {"type": "string"}},
+                {"name": "state", "in": "query", "required": true, "schema": {"type": "string"}},
+            ],
+            "responses": {
+                "302": {"description": "Redirect to return_to, with the seed_session cookie"},
+                "401": {"$ref": "#/components/responses/Error"},
+                "403": {"$ref": "#/components/responses/Error"},
+            },
+        }),
+        ("post", "/auth/logout") => json!({
+            "summary": "Close the session and clear its cookie",
+            "responses": {"204": {"description": "Logged out"}},
+        }),
+        ("get", "/auth/me") => json!({
+            "summary": "The logged-in user or the API key of the request",
+            "security": [{"session": []}, {"apiKey": []}],
+            "responses": {
+                "200": json_response("Subject, name, scopes and tenant", json!({"type": "object"})),
+                "401": {"$ref": "#/components/responses/Error"},
+            },
+        }),
+        ("get", "/healthz") => json!({
+            "summary": "Liveness probe, does not touch the database",
+            "responses": {"200": json_response("Always ok", json!({"type": "object"}))},
+        }),
+        ("get", "/readyz") => json!({
+            "summary": "Readiness probe: database, connection pool and migrations",
+            "responses": {
+                "200": json_response("Ready, with each check", json!({"type": "object"})),
+                "503": json_response("Degraded, with the failing checks", json!({"type": "object"})),
+            },
+        }),
+        ("get", "/version") => json!({
+            "summary": "Crate version, git hash and build time",
+            "responses": {"200": json_response("Build information", json!({"type": "object"}))},
+        }),
+        ("get", "/metrics") => json!({
+            "summary": "Prometheus metrics: requests, latency, database timings and pools",
+            "responses": {"200": text_response("Prometheus text format 0.0.4")},
+        }),
+        ("get", "/pkg/{file:.*}") => json!({
+            "summary": "Client files: JavaScript and application/wasm, precompressed when possible",
+            "parameters": [id_param("file")],
+            "responses": {
+                "200": {"description": "The file, cached forever when requested with ?v=<hash>"},
+                "304": {"description": "Not modified (If-None-Match)"},
+                "404": {"$ref": "#/components/responses/Error"},
+            },
+        }),
+        _ => return None,
+    };
+    Some(op)
+}
+
+fn json_body(schema: Value) -> Value {
+    json!({"required": true, "content": {"application/json": {"schema": schema}}})
+}
+
+fn json_response(description: &str, schema: Value) -> Value {
+    json!({"description": description, "content": {"application/json": {"schema": schema}}})
+}
+
+fn text_response(description: &str) -> Value {
+    json!({"description": description, "content": {"text/plain": {"schema": {"type": "string"}}}})
+}
+
+fn html_response() -> Value {
+    json!({"description": "HTML page", "content": {"text/html": {"schema": {"type": "string"}}}})
+}
+
+// les routes d'administration demandent l'en-tête X-Admin-Token, une clé d'API
+// ou une session avec le droit admin
+fn admin(mut op: Value) -> Value {
+    op["security"] = json!([{"adminToken": []}, {"apiKey": []}, {"session": []}]);
+    op["responses"]["401"] = json!({"$ref": "#/components/responses/Error"});
+    op["responses"]["403"] = json!({"$ref": "#/components/responses/Error"});
+    op
+}
+
+// les routes qui créent acceptent Idempotency-Key : une nouvelle tentative rejoue la réponse
+fn idempotent(mut op: Value) -> Value {
+    op["parameters"] = json!([{"$ref": "#/components/parameters/IdempotencyKey"}]);
+    op["responses"]["409"] = json!({"$ref": "#/components/responses/Error"});
+    op["responses"]["422"] = json!({"$ref": "#/components/responses/Error"});
+    op
+}
+
+///
+/// les schémas des types partagés, générés par schemars
+///
+fn component_schemas() -> Map<String, Value> {
+    let mut gen = SchemaSettings::openapi3().into_generator();
+    gen.subschema_for::<Person>();
+    gen.subschema_for::<ListPersons>();
+    gen.subschema_for::<PersonEvent>();
+    gen.subschema_for::<ErrorEnvelope>();
+    gen.subschema_for::<ApiKey>();
+    gen.subschema_for::<NewApiKey>();
+    gen.subschema_for::<IssuedApiKey>();
+    gen.subschema_for::<BatchRequest>();
+    gen.subschema_for::<BatchResponse>();
+    gen.subschema_for::<ComplianceEntry>();
+    gen.subschema_for::<ErasureReport>();
+    gen.subschema_for::<Delivery>();
+    gen.take_definitions()
+        .into_iter()
+        .map(|(name, schema)| (name, serde_json::to_value(schema).unwrap_or(Value::Null)))
+        .collect()
+}
+
+///
+/// le chemin d'une route au format OpenAPI : /pkg/{file:.*} devient /pkg/{file}
+///
+pub fn openapi_path(path: &str) -> String {
+    let mut out = String::with_capacity(path.len());
+    let (mut in_param, mut in_regex) = (false, false);
+    for c in path.chars() {
+        match c {
+            '{' => {
+                in_param = true;
+                out.push(c);
+            }
+            '}' => {
+                in_param = false;
+                in_regex = false;
+                out.push(c);
+            }
+            ':' if in_param => in_regex = true,
+            _ if in_regex => {}
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+///
+/// construit le document OpenAPI 3 à partir de ROUTE_TABLE
+///
+pub fn openapi_spec() -> Value {
+    let mut paths = Map::new();
+    for (method, path) in ROUTE_TABLE {
+        if let Some(mut op) = describe(method, path) {
+            // toutes les routes peuvent renvoyer une erreur au format ErrorEnvelope
+            op["responses"]["default"] = json!({"$ref": "#/components/responses/Error"});
+            // les sondes mises à part, les routes sont limitées en débit
+            if !PROBE_PATHS.contains(path) {
+                op["responses"]["429"] = json!({"$ref": "#/components/responses/RateLimited"});
+            }
+            // une clé d'API ou une session facultative, obligatoire avec SEED_AUTH_REQUIRED
+            let method_upper = method.to_uppercase();
+            let scope = Method::from_bytes(method_upper.as_bytes())
+                .ok()
+                .and_then(|m| required_scope(&m, path));
+            if scope.is_some() && op.get("security").is_none() {
+                op["security"] = json!([{"apiKey": []}, {"session": []}, {}]);
+            }
+            let item = paths
+                .entry(openapi_path(path))
+                .or_insert_with(|| Value::Object(Map::new()));
+            item[*method] = op;
+        }
+    }
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "seed-server",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": paths,
+        "components": {
+            "schemas": component_schemas(),
+            "parameters": {
+                "IdempotencyKey": {
+                    "name": IDEMPOTENCY_KEY,
+                    "in": "header",
+                    "required": false,
+                    "description": "Unique key of the request; a retry with the same key and body \
+                                    gets the first response back (with Idempotency-Replayed), \
+                                    a different body gets 422",
+                    "schema": {"type": "string", "maxLength": 255},
+                },
+            },
+            "responses": {
+                "Error": json_response(
+                    "Error",
+                    json!({"$ref": "#/components/schemas/ErrorEnvelope"}),
+                ),
+                "RateLimited": {
+                    "description": "Too many requests; retry after Retry-After seconds",
+                    "headers": {
+                        "Retry-After": {"schema": {"type": "integer"}},
+                        "RateLimit-Limit": {"schema": {"type": "integer"}},
+                        "RateLimit-Remaining": {"schema": {"type": "integer"}},
+                        "RateLimit-Reset": {"schema": {"type": "integer"}},
+                    },
+                    "content": {"application/json": {
+                        "schema": {"$ref": "#/components/schemas/ErrorEnvelope"},
+                    }},
+                },
+            },
+            "securitySchemes": {
+                "adminToken": {"type": "apiKey", "in": "header", "name": "X-Admin-Token"},
+                "apiKey": {"type": "http", "scheme": "bearer", "bearerFormat": "seed_<prefix>_<secret>"},
+                "session": {"type": "apiKey", "in": "cookie", "name": SESSION_COOKIE},
+            },
+        },
+    })
+}
+
+pub async fn openapi_hdl() -> HttpResponse {
+    HttpResponse::Ok().json(openapi_spec())
+}
+
+///
+/// la page de documentation : Swagger UI qui lit /openapi.json, avec « Try it out »
+///
+pub async fn docs_hdl() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(DOCS_PAGE)
+}
+
+///
+/// les fichiers de Swagger UI, gardés dans le binaire : la page marche sans accès à Internet
+///
+pub async fn docs_asset_hdl(file: web::Path<String>) -> HttpResponse {
+    let (content_type, body) = match file.as_str() {
+        "swagger-ui-bundle.js" => ("application/javascript", SWAGGER_UI_BUNDLE),
+        "swagger-ui.css" => ("text/css", SWAGGER_UI_CSS),
+        _ => return HttpResponse::NotFound().finish(),
+    };
+    HttpResponse::Ok()
+        .content_type(content_type)
+        .header("Cache-Control", "public, max-age=86400")
+        .body(body)
+}
+
+// Swagger UI 5.17.14 (dist), licence Apache 2.0 : voir server/swagger-ui/LICENSE
+const SWAGGER_UI_BUNDLE: &[u8] = include_bytes!("../swagger-ui/swagger-ui-bundle.js");
+const SWAGGER_UI_CSS: &[u8] = include_bytes!("../swagger-ui/swagger-ui.css");
+
+const DOCS_PAGE: &str = r##"<!DOCTYPE html>
+<html lang="fr">
+    <head>
+        <meta charset="utf-8">
+        <title>seed-server API</title>
+        <link rel="stylesheet" href="/docs/swagger-ui.css">
+    </head>
+    <body>
+        <div id="swagger-ui"></div>
+        <script src="/docs/swagger-ui-bundle.js"></script>
+        <script>
+            window.ui = SwaggerUIBundle({
+                url: "/openapi.json",
+                dom_id: "#swagger-ui",
+                deepLinking: true,
+                validatorUrl: null,
+                presets: [SwaggerUIBundle.presets.apis],
+            });
+        </script>
+    </body>
+</html>
+"##;
@@ -1,35 +1,58 @@
 // src/person_handlers.rs
 use std::sync::Mutex;
 
-use actix_web::{web, HttpResponse, Responder};
-
-use crate::db_mongo;
+use actix_web::{web, HttpRequest, HttpResponse};
+
+use crate::assets::client_index;
+use crate::batch;
+use crate::broadcast::Broadcaster;
+use crate::config::Config;
+use crate::errors::MyError;
+use crate::idempotency::Idempotency;
+use crate::tenancy::Tenant;
 use crate::AppState;
-use shared::{ListPersons, Person};
-
-pub async fn simple_index(data: web::Data<Mutex<AppState>>) -> String {
+use shared::{BatchRequest, ErrorEnvelope, ListPersons, Person, PersonEvent, PersonId};
+
+///
+/// la page du client s'il a été compilé, sinon le nom de l'application
+///
+pub async fn simple_index(req: HttpRequest, data: web::Data<Mutex<AppState>>) -> HttpResponse {
+    if let Some(page) = client_index(&req) {
+        return page;
+    }
     let app_name = &data.lock().unwrap().app_name; // <- get app_name
-    format!("Hello {}!", app_name) // <- response with app_name
+    HttpResponse::Ok()
+        .content_type("text/plain; charset=utf-8")
+        .body(format!("Hello {}!", app_name)) // <- response with app_name
 }
 
-pub async fn list_persons_str(_state: web::Data<Mutex<AppState>>) -> impl Responder {
+pub async fn list_persons_str(
+    _state: web::Data<Mutex<AppState>>,
+    tenant: Tenant,
+) -> Result<HttpResponse, MyError> {
     //let conn = &state.lock().unwrap().conn;
     //let vec_pers = conn.get_list_persons().unwrap();
 
-    let vec_pers = db_mongo::get_list_persons().unwrap();
+    let vec_pers = tenant.store()?.list()?;
 
     let str_pers: ListPersons = ListPersons::new(vec_pers);
     let str = str_pers.vec_to_string();
 
-    HttpResponse::Ok().body(str)
+    Ok(HttpResponse::Ok().body(str))
 }
 
-pub async fn list_persons_json(_state: web::Data<Mutex<AppState>>) -> impl Responder {
-    let res = db_mongo::get_list_persons().unwrap();
-    HttpResponse::Ok().json(res)
+pub async fn list_persons_json(
+    _state: web::Data<Mutex<AppState>>,
+    tenant: Tenant,
+) -> Result<HttpResponse, MyError> {
+    let res = tenant.store()?.list()?;
+    Ok(HttpResponse::Ok().json(res))
 }
 
-pub async fn list_persons_json_from_list(_state: web::Data<Mutex<AppState>>) -> impl Responder {
+pub async fn list_persons_json_from_list(
+    _state: web::Data<Mutex<AppState>>,
+    tenant: Tenant,
+) -> Result<HttpResponse, MyError> {
     /*
     let conn = &state.lock().unwrap().conn;
     let coll = conn.get_collection().unwrap().find(None, None).unwrap();
@@ -44,40 +67,110 @@ pub async fn list_persons_json_from_list(_state: web::Data<Mutex<AppState>>) ->
         })
         .collect::<Result<Vec<Person>, MongoError>>();
      */
-    let res = db_mongo::get_list_persons().unwrap();
+    let res = tenant.store()?.list()?;
     let list = ListPersons::new(res);
 
-    HttpResponse::Ok().json(list)
+    Ok(HttpResponse::Ok().json(list))
 }
 
+///
+/// ajoute une personne ; avec Idempotency-Key, une nouvelle tentative ne crée pas de doublon
+/// sans Broadcaster dans l'App (tests des handlers seuls), rien n'est diffusé
+///
 pub async fn add_person_hdl(
-    _state: web::Data<Mutex<AppState>>,
+    broadcaster: Option<web::Data<Broadcaster>>,
+    tenant: Tenant,
+    idempotency: Idempotency,
     pers: web::Json<Person>,
-) -> impl Responder {
-    let my_person = pers.into_inner();
-    let new_person = db_mongo::add_person(my_person);
-    HttpResponse::Ok().json(new_person.unwrap())
+) -> HttpResponse {
+    idempotency
+        .run(pers.into_inner(), |my_person| async move {
+            let new_person = tenant.store()?.add(my_person)?;
+            if let Some(broadcaster) = &broadcaster {
+                broadcaster.send_for(tenant.id, PersonEvent::Created(new_person.clone()));
+            }
+            Ok(HttpResponse::Ok().json(new_person))
+        })
+        .await
 }
 
-pub async fn show_one_person_id(id: web::Path<String>) -> impl Responder {
+pub async fn show_one_person_id(
+    id: web::Path<String>,
+    tenant: Tenant,
+) -> Result<HttpResponse, MyError> {
     let in_id = id.into_inner();
-    let found_person = Some(db_mongo::get_person_by_id(&in_id).unwrap());
-    HttpResponse::Ok().json(found_person)
+    let found_person = Some(tenant.store()?.get(&in_id)?);
+    Ok(HttpResponse::Ok().json(found_person))
 }
 
 pub async fn modify_person_hdl(
     id: web::Path<String>,
     modifyed_person: web::Json<Person>,
-) -> impl Responder {
+    broadcaster: Option<web::Data<Broadcaster>>,
+    tenant: Tenant,
+) -> Result<HttpResponse, MyError> {
     let in_id = id.into_inner();
     let mod_pers = modifyed_person.into_inner();
 
-    let succes = Some(db_mongo::modify_person_by_id(&in_id, mod_pers).unwrap());
-    HttpResponse::Ok().json(succes)
+    let succes = tenant.store()?.modify(&in_id, mod_pers.clone())?;
+    // modify renvoie l'ancienne personne
+    // on envoie donc la personne modifiée avec son id
+    if let (Some(_), Some(broadcaster)) = (&succes, &broadcaster) {
+        broadcaster.send_for(
+            tenant.id,
+            PersonEvent::Updated(Person {
+                id: Some(PersonId::new(in_id)),
+                ..mod_pers
+            }),
+        );
+    }
+    Ok(HttpResponse::Ok().json(Some(succes)))
+}
+
+///
+/// efface la personne de l'id du chemin
+/// le corps est facultatif (anciens clients) ; s'il a un id, ce doit être celui du chemin
+///
+pub async fn delete_person_hdl(
+    id: web::Path<String>,
+    delete_pers: Option<web::Json<Person>>,
+    broadcaster: Option<web::Data<Broadcaster>>,
+    tenant: Tenant,
+) -> Result<HttpResponse, MyError> {
+    let in_id = id.into_inner();
+    let body_id = delete_pers.as_ref().and_then(|pers| pers.id.as_ref());
+    if body_id.map_or(false, |body_id| body_id.as_str() != in_id) {
+        return Ok(HttpResponse::BadRequest().json(ErrorEnvelope::new(
+            "id_mismatch",
+            "the id in the body is not the id in the path",
+        )));
+    }
+    let succes = tenant.store()?.delete(&in_id)?;
+    if let (Some(deleted), Some(broadcaster)) = (&succes, &broadcaster) {
+        broadcaster.send_for(tenant.id, PersonEvent::Deleted(deleted.clone()));
+    }
+    Ok(HttpResponse::Ok().json(succes))
 }
 
-pub async fn delete_person_hdl(delete_pers: web::Json<Person>) -> impl Responder {
-    let del_pers = delete_pers.into_inner();
-    let succes = Some(db_mongo::delete_person(del_pers).unwrap());
-    HttpResponse::Ok().json(succes.unwrap())
+///
+/// exécute un lot d'opérations sur les personnes, dans l'ordre
+/// tout ou rien par défaut ; "partial": true garde les opérations réussies
+///
+pub async fn batch_persons_hdl(
+    config: web::Data<Config>,
+    broadcaster: web::Data<Broadcaster>,
+    tenant: Tenant,
+    idempotency: Idempotency,
+    request: web::Json<BatchRequest>,
+) -> HttpResponse {
+    idempotency
+        .run(request.into_inner(), |request| async move {
+            batch::validate(&request, config.batch_limit)?;
+            let outcome = batch::execute(&*tenant.store()?, request);
+            for event in outcome.events {
+                broadcaster.send_for(tenant.id.clone(), event);
+            }
+            Ok(HttpResponse::Ok().json(outcome.response))
+        })
+        .await
 }
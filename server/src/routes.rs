@@ -0,0 +1,84 @@
+// server/src/routes.rs
+
+use actix_web::{guard, web};
+
+use crate::api_key_handlers::*;
+use crate::assets::pkg_hdl;
+use crate::auth_handlers::{callback_hdl, login_hdl, logout_hdl, me_hdl};
+use crate::backup_handlers::{backup_hdl, restore_hdl};
+use crate::gdpr_handlers::{erase_person_hdl, gdpr_export_hdl, list_compliance_hdl};
+use crate::graphql::{graphiql_hdl, graphql_hdl, graphql_ws_hdl};
+use crate::health::{healthz_hdl, readyz_hdl, version_hdl};
+use crate::metrics::metrics_hdl;
+use crate::openapi::{docs_asset_hdl, docs_hdl, openapi_hdl};
+use crate::person_handlers::*;
+use crate::sse::events_stream;
+use crate::webhook_handlers::*;
+use crate::ws::ws_index;
+
+///
+/// la table des routes
+/// la macro enregistre chaque route dans l'App, dans l'ordre,
+/// et garde la liste (méthode, chemin) dans ROUTE_TABLE
+/// pour que la spécification OpenAPI soit complète
+/// `if <guard>` ajoute une garde : la route suivante du même chemin prend le reste
+///
+macro_rules! route_table {
+    ($( $method:ident $path:literal $( if $guard:expr )? => $handler:path ),* $(,)?) => {
+        fn configure_table(cfg: &mut web::ServiceConfig) {
+            $( cfg.route($path, web::$method() $( .guard($guard) )? .to($handler)); )*
+        }
+
+        pub const ROUTE_TABLE: &[(&str, &str)] = &[ $( (stringify!($method), $path) ),* ];
+    };
+}
+
+route_table! {
+    get "/" => simple_index,
+    get "/string" => list_persons_str,
+    get "/json" => list_persons_json,
+    post "/json" => add_person_hdl,
+    get "/json_list" => list_persons_json_from_list,
+    get "/json/{id}" => show_one_person_id,
+    put "/json/{id}" => modify_person_hdl,
+    delete "/json/{id}" => delete_person_hdl,
+    post "/persons/batch" => batch_persons_hdl,
+    get "/persons/{id}/gdpr-export" => gdpr_export_hdl,
+    post "/persons/{id}/erase" => erase_person_hdl,
+    get "/ws" => ws_index,
+    get "/events" => events_stream,
+    get "/webhooks" => list_webhooks_hdl,
+    post "/webhooks" => add_webhook_hdl,
+    delete "/webhooks/{id}" => delete_webhook_hdl,
+    get "/webhooks/{id}/deliveries" => list_deliveries_hdl,
+    // les abonnements GraphQL arrivent en WebSocket sur le chemin de POST /graphql
+    get "/graphql" if guard::Header("upgrade", "websocket") => graphql_ws_hdl,
+    post "/graphql" => graphql_hdl,
+    get "/graphiql" => graphiql_hdl,
+    get "/openapi.json" => openapi_hdl,
+    get "/docs" => docs_hdl,
+    get "/docs/{file}" => docs_asset_hdl,
+    get "/admin/backup" => backup_hdl,
+    post "/admin/restore" => restore_hdl,
+    get "/admin/keys" => list_keys_hdl,
+    post "/admin/keys" => create_key_hdl,
+    delete "/admin/keys/{id}" => revoke_key_hdl,
+    post "/admin/keys/{id}/rotate" => rotate_key_hdl,
+    get "/admin/compliance" => list_compliance_hdl,
+    get "/auth/login" => login_hdl,
+    get "/auth/callback" => callback_hdl,
+    post "/auth/logout" => logout_hdl,
+    get "/auth/me" => me_hdl,
+    get "/healthz" => healthz_hdl,
+    get "/readyz" => readyz_hdl,
+    get "/version" => version_hdl,
+    get "/metrics" => metrics_hdl,
+    get "/pkg/{file:.*}" => pkg_hdl,
+}
+
+///
+/// enregistre toutes les routes de l'application
+///
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    configure_table(cfg);
+}
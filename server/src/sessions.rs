@@ -0,0 +1,99 @@
+// server/src/sessions.rs
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use actix_web::cookie::{Cookie, SameSite};
+
+use crate::api_keys::hash_secret;
+use crate::auth::Principal;
+
+///
+/// le cookie de session, posé après la connexion OpenID Connect
+///
+pub const SESSION_COOKIE: &str = "seed_session";
+
+struct Session {
+    principal: Principal,
+    expires: Instant,
+}
+
+///
+/// les sessions des utilisateurs connectés, en mémoire
+/// le cookie porte un jeton aléatoire, seule son empreinte est gardée
+/// un redémarrage du serveur oblige à se reconnecter
+///
+pub struct SessionStore {
+    ttl: Duration,
+    sessions: Mutex<HashMap<String, Session>>,
+}
+
+///
+/// le stockage partagé par le middleware et les routes (web::Data<SharedSessions>)
+///
+pub type SharedSessions = Arc<SessionStore>;
+
+impl SessionStore {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    ///
+    /// ouvre une session ; renvoie le jeton à mettre dans le cookie
+    ///
+    pub fn create(&self, principal: Principal) -> String {
+        let token = format!(
+            "{}{}",
+            uuid::Uuid::new_v4().to_simple(),
+            uuid::Uuid::new_v4().to_simple()
+        );
+        let now = Instant::now();
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.retain(|_, session| session.expires > now);
+        sessions.insert(
+            hash_secret(&token),
+            Session {
+                principal,
+                expires: now + self.ttl,
+            },
+        );
+        token
+    }
+
+    ///
+    /// le Principal de la session, si elle n'a pas expiré
+    ///
+    pub fn get(&self, token: &str) -> Option<Principal> {
+        let key = hash_secret(token);
+        let mut sessions = self.sessions.lock().unwrap();
+        match sessions.get(&key) {
+            Some(session) if session.expires > Instant::now() => Some(session.principal.clone()),
+            Some(_) => {
+                sessions.remove(&key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    pub fn remove(&self, token: &str) {
+        self.sessions.lock().unwrap().remove(&hash_secret(token));
+    }
+}
+
+///
+/// le cookie de session : HttpOnly, SameSite=Lax, Secure en HTTPS
+/// sans Max-Age, il disparaît à la fermeture du navigateur ; la session expire de toute façon
+///
+pub fn session_cookie(token: &str, secure: bool) -> Cookie<'static> {
+    Cookie::build(SESSION_COOKIE, token.to_owned())
+        .path("/")
+        .http_only(true)
+        .secure(secure)
+        .same_site(SameSite::Lax)
+        .finish()
+}
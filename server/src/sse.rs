@@ -0,0 +1,95 @@
+// server/src/sse.rs
+
+use actix_web::web::Bytes;
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use futures::{future, stream, StreamExt};
+use serde::Deserialize;
+
+use crate::broadcast::Broadcaster;
+use crate::errors::MyError;
+use crate::tenancy::Tenant;
+
+///
+/// les paramètres de /events
+/// types : liste des types d'événements séparés par des virgules
+/// par exemple /events?types=created,deleted
+///
+#[derive(Deserialize)]
+pub struct EventsQuery {
+    pub types: Option<String>,
+}
+
+impl EventsQuery {
+    fn accepted_types(&self) -> Option<Vec<String>> {
+        self.types.as_ref().map(|types| {
+            types
+                .split(',')
+                .map(|t| t.trim().to_lowercase())
+                .filter(|t| !t.is_empty())
+                .collect()
+        })
+    }
+}
+
+///
+/// la route /events en text/event-stream
+/// si le client envoie Last-Event-ID, on rejoue d'abord
+/// les événements manqués depuis le journal
+/// seuls les événements du locataire de la requête sont envoyés
+///
+pub async fn events_stream(
+    req: HttpRequest,
+    query: web::Query<EventsQuery>,
+    broadcaster: web::Data<Broadcaster>,
+    tenant: Tenant,
+) -> HttpResponse {
+    let last_event_id = req
+        .headers()
+        .get("Last-Event-ID")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<i64>().ok());
+
+    // on s'abonne avant de lire le journal pour ne rien perdre
+    // les doublons sont filtrés grâce aux numéros
+    let live = broadcaster.new_client();
+    let replay = match last_event_id {
+        Some(id) => {
+            // la lecture attend le journal : elle se fait hors du worker
+            let broadcaster = broadcaster.clone();
+            web::block(move || Ok::<_, MyError>(broadcaster.events_since(id)))
+                .await
+                .unwrap_or_else(|e| {
+                    tracing::error!(error = %e, "failed to replay the event log");
+                    Vec::new()
+                })
+        }
+        None => Vec::new(),
+    };
+    let replayed_up_to = replay
+        .last()
+        .map(|logged| logged.id)
+        .or(last_event_id)
+        .unwrap_or(0);
+
+    let accepted_types = query.accepted_types();
+    let events = stream::iter(replay)
+        .chain(live.filter(move |logged| future::ready(logged.id > replayed_up_to)))
+        .filter(move |logged| future::ready(logged.tenant == tenant.id))
+        .filter(move |logged| {
+            future::ready(match &accepted_types {
+                Some(types) => types.iter().any(|t| t == logged.event.kind()),
+                None => true,
+            })
+        })
+        .map(|logged| Ok::<_, Error>(Bytes::from(logged.to_sse())));
+
+    let body = stream::once(future::ok::<_, Error>(Bytes::from_static(
+        b"retry: 3000\n\n",
+    )))
+    .chain(events);
+
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .header("Cache-Control", "no-cache")
+        .streaming(body)
+}
@@ -0,0 +1,533 @@
+// server/src/store.rs
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use bson::oid::ObjectId;
+use bson::{doc, Bson};
+use mongodb::options::ReplaceOptions;
+use once_cell::sync::OnceCell;
+use postgres::GenericClient;
+
+use crate::config::Config;
+use crate::db_mongo::{object_id, person_filter};
+use crate::db_postgres::{PostgresPersons, PostgresWriter};
+use crate::db_sqlite::{SqlitePersons, SqliteWriter};
+use crate::errors::MyError;
+use crate::migrations::CURRENT_SCHEMA_VERSION;
+use crate::tenancy::Tenant;
+use shared::{Person, PersonId};
+
+///
+/// le stockage des personnes choisi dans la configuration (SEED_STORAGE)
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Backend {
+    Mongo,
+    Sqlite,
+    Postgres,
+}
+
+impl Backend {
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::Mongo => "mongo",
+            Backend::Sqlite => "sqlite",
+            Backend::Postgres => "postgres",
+        }
+    }
+}
+
+impl std::str::FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mongo" | "mongodb" => Ok(Backend::Mongo),
+            "sqlite" => Ok(Backend::Sqlite),
+            "postgres" | "postgresql" => Ok(Backend::Postgres),
+            other => Err(format!(
+                "unknown storage '{}' (mongo, sqlite or postgres)",
+                other
+            )),
+        }
+    }
+}
+
+static BACKEND: OnceCell<Backend> = OnceCell::new();
+
+///
+/// choisit le stockage utilisé par open_store
+/// à appeler avant le premier accès ; les appels suivants sont ignorés
+///
+pub fn configure(backend: Backend) {
+    let _ = BACKEND.set(backend);
+}
+
+///
+/// le stockage choisi ; sans configure() (les tests), on lit SEED_STORAGE
+///
+pub fn backend() -> Backend {
+    *BACKEND.get_or_init(|| Config::from_env().storage)
+}
+
+///
+/// le stockage des personnes d'un locataire, avec le backend configuré
+///
+pub fn open_store(tenant: &Tenant) -> Result<Box<dyn PersonStore>, MyError> {
+    open_store_with(backend(), tenant)
+}
+
+///
+/// le stockage des personnes d'un locataire, avec un backend donné
+/// sert à copier les personnes d'un stockage à l'autre
+///
+pub fn open_store_with(backend: Backend, tenant: &Tenant) -> Result<Box<dyn PersonStore>, MyError> {
+    Ok(match backend {
+        Backend::Mongo => Box::new(MongoStore::new(tenant.clone())),
+        Backend::Sqlite => Box::new(tenant.sqlite_persons()?),
+        Backend::Postgres => Box::new(tenant.postgres_persons()?),
+    })
+}
+
+///
+/// les opérations de stockage des personnes
+/// MongoStore passe par db_mongo, SqlitePersons par db_sqlite,
+/// PostgresPersons par db_postgres, MemoryStore garde tout en mémoire (tests)
+///
+pub trait PersonStore: Send + Sync {
+    fn list(&self) -> Result<Vec<Person>, MyError>;
+
+    ///
+    /// les personnes dont le nom et le prénom contiennent le texte,
+    /// sans tenir compte de la casse, triées par nom et prénom
+    /// renvoie la page demandée et le nombre total de personnes trouvées
+    ///
+    fn search(
+        &self,
+        nom: Option<&str>,
+        prenom: Option<&str>,
+        skip: i64,
+        limit: i64,
+    ) -> Result<(Vec<Person>, i64), MyError>;
+
+    fn get(&self, id: &str) -> Result<Option<Person>, MyError>;
+    fn add(&self, person: Person) -> Result<Person, MyError>;
+    fn modify(&self, id: &str, person: Person) -> Result<Option<Person>, MyError>;
+    fn delete(&self, id: &str) -> Result<Option<Person>, MyError>;
+
+    ///
+    /// enregistre la personne avec son id, en remplaçant celle qui a le même id
+    /// sert à la restauration des sauvegardes
+    ///
+    fn upsert(&self, person: Person) -> Result<(), MyError>;
+
+    ///
+    /// efface toutes les personnes
+    ///
+    fn clear(&self) -> Result<(), MyError>;
+
+    ///
+    /// remplace toutes les personnes par celles données (restauration en mode replace)
+    /// en cas d'échec, les personnes d'avant restent là
+    ///
+    fn replace_all(&self, persons: Vec<Person>) -> Result<(), MyError>;
+
+    ///
+    /// appelle run avec les écritures d'une transaction, validée si run renvoie true
+    /// None si le stockage n'a pas de transaction (MongoDB, mémoire) : run n'est pas appelé
+    ///
+    fn transaction(
+        &self,
+        _run: &mut dyn FnMut(&dyn PersonWriter) -> bool,
+    ) -> Option<Result<bool, MyError>> {
+        None
+    }
+}
+
+///
+/// les écritures d'un lot d'opérations (batch)
+///
+pub trait PersonWriter {
+    fn add(&self, person: Person) -> Result<Person, MyError>;
+    fn modify(&self, id: &str, person: Person) -> Result<Option<Person>, MyError>;
+    fn delete(&self, id: &str) -> Result<Option<Person>, MyError>;
+}
+
+///
+/// les écritures faites directement sur le stockage, hors transaction
+///
+pub struct Direct<'a>(pub &'a dyn PersonStore);
+
+impl PersonWriter for Direct<'_> {
+    fn add(&self, person: Person) -> Result<Person, MyError> {
+        self.0.add(person)
+    }
+
+    fn modify(&self, id: &str, person: Person) -> Result<Option<Person>, MyError> {
+        self.0.modify(id, person)
+    }
+
+    fn delete(&self, id: &str) -> Result<Option<Person>, MyError> {
+        self.0.delete(id)
+    }
+}
+
+impl PersonWriter for SqliteWriter<'_> {
+    fn add(&self, person: Person) -> Result<Person, MyError> {
+        SqliteWriter::add(self, person)
+    }
+
+    fn modify(&self, id: &str, person: Person) -> Result<Option<Person>, MyError> {
+        SqliteWriter::modify(self, id, person)
+    }
+
+    fn delete(&self, id: &str) -> Result<Option<Person>, MyError> {
+        SqliteWriter::delete(self, id)
+    }
+}
+
+impl<C: GenericClient> PersonWriter for PostgresWriter<'_, C> {
+    fn add(&self, person: Person) -> Result<Person, MyError> {
+        PostgresWriter::add(self, person)
+    }
+
+    fn modify(&self, id: &str, person: Person) -> Result<Option<Person>, MyError> {
+        PostgresWriter::modify(self, id, person)
+    }
+
+    fn delete(&self, id: &str) -> Result<Option<Person>, MyError> {
+        PostgresWriter::delete(self, id)
+    }
+}
+
+// les personnes sans id en reçoivent un, de la même forme que ceux de MongoDB
+fn with_ids(persons: Vec<Person>) -> Result<Vec<Person>, MyError> {
+    persons
+        .into_iter()
+        .map(|person| match person.id {
+            Some(_) => Ok(person),
+            None => Ok(Person {
+                id: Some(PersonId::from(ObjectId::new()?)),
+                ..person
+            }),
+        })
+        .collect()
+}
+
+///
+/// le stockage MongoDB, dans la collection Persons du locataire
+/// MongoStore::default() utilise la base configurée, sans locataire
+///
+#[derive(Debug, Clone, Default)]
+pub struct MongoStore {
+    pub tenant: Tenant,
+}
+
+impl MongoStore {
+    pub fn new(tenant: Tenant) -> Self {
+        Self { tenant }
+    }
+}
+
+impl PersonStore for MongoStore {
+    fn list(&self) -> Result<Vec<Person>, MyError> {
+        self.tenant.persons()?.get_list_persons()
+    }
+
+    fn search(
+        &self,
+        nom: Option<&str>,
+        prenom: Option<&str>,
+        skip: i64,
+        limit: i64,
+    ) -> Result<(Vec<Person>, i64), MyError> {
+        self.tenant
+            .persons()?
+            .find_persons(person_filter(nom, prenom), skip, limit)
+    }
+
+    fn get(&self, id: &str) -> Result<Option<Person>, MyError> {
+        self.tenant.persons()?.get_person_by_id(id)
+    }
+
+    fn add(&self, person: Person) -> Result<Person, MyError> {
+        self.tenant.persons()?.add_person(person)
+    }
+
+    fn modify(&self, id: &str, person: Person) -> Result<Option<Person>, MyError> {
+        self.tenant.persons()?.modify_person_by_id(id, person)
+    }
+
+    fn delete(&self, id: &str) -> Result<Option<Person>, MyError> {
+        self.tenant.persons()?.delete_person(id)
+    }
+
+    fn upsert(&self, person: Person) -> Result<(), MyError> {
+        let persons = self.tenant.persons()?;
+        let id = match &person.id {
+            Some(id) => object_id(id)?,
+            None => {
+                persons.add_person(person)?;
+                return Ok(());
+            }
+        };
+        let options = ReplaceOptions::builder().upsert(true).build();
+        persons.collection.replace_one(
+            persons.scoped(doc! {"_id": id.clone()}),
+            persons.scoped(doc! {"_id": id, "nom": person.nom, "prenom": person.prenom,
+            "schema_version": CURRENT_SCHEMA_VERSION}),
+            Some(options),
+        )?;
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<(), MyError> {
+        let persons = self.tenant.persons()?;
+        persons
+            .collection
+            .delete_many(persons.scoped(doc! {}), None)?;
+        Ok(())
+    }
+
+    // MongoDB n'a pas de transaction ici : les personnes de l'archive sont écrites d'abord,
+    // les autres ne sont effacées qu'ensuite ; un échec laisse les anciennes en place
+    fn replace_all(&self, persons: Vec<Person>) -> Result<(), MyError> {
+        let mut keep = Vec::new();
+        for person in with_ids(persons)? {
+            if let Some(id) = &person.id {
+                keep.push(Bson::ObjectId(object_id(id)?));
+            }
+            self.upsert(person)?;
+        }
+        let scope = self.tenant.persons()?;
+        scope
+            .collection
+            .delete_many(scope.scoped(doc! {"_id": {"$nin": keep}}), None)?;
+        Ok(())
+    }
+}
+
+///
+/// le stockage en mémoire, rangé par id
+///
+pub struct MemoryStore {
+    persons: Mutex<BTreeMap<String, Person>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self {
+            persons: Mutex::new(BTreeMap::new()),
+        }
+    }
+}
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PersonStore for MemoryStore {
+    fn list(&self) -> Result<Vec<Person>, MyError> {
+        Ok(self.persons.lock().unwrap().values().cloned().collect())
+    }
+
+    fn search(
+        &self,
+        nom: Option<&str>,
+        prenom: Option<&str>,
+        skip: i64,
+        limit: i64,
+    ) -> Result<(Vec<Person>, i64), MyError> {
+        let contains = |field: &str, text: Option<&str>| match text {
+            Some(text) => field.to_lowercase().contains(&text.to_lowercase()),
+            None => true,
+        };
+        let mut found: Vec<Person> = self
+            .list()?
+            .into_iter()
+            .filter(|p| contains(&p.nom, nom) && contains(&p.prenom, prenom))
+            .collect();
+        found.sort_by(|a, b| (&a.nom, &a.prenom).cmp(&(&b.nom, &b.prenom)));
+        let total = found.len() as i64;
+        let page = found
+            .into_iter()
+            .skip(skip.max(0) as usize)
+            .take(limit.max(0) as usize)
+            .collect();
+        Ok((page, total))
+    }
+
+    fn get(&self, id: &str) -> Result<Option<Person>, MyError> {
+        Ok(self.persons.lock().unwrap().get(id).cloned())
+    }
+
+    fn add(&self, person: Person) -> Result<Person, MyError> {
+        let id = PersonId::from(ObjectId::new()?);
+        let added = Person {
+            id: Some(id.clone()),
+            ..person
+        };
+        self.persons
+            .lock()
+            .unwrap()
+            .insert(id.to_string(), added.clone());
+        Ok(added)
+    }
+
+    fn modify(&self, id: &str, person: Person) -> Result<Option<Person>, MyError> {
+        let mut persons = self.persons.lock().unwrap();
+        match persons.get_mut(id) {
+            Some(current) => {
+                let previous = current.clone();
+                *current = Person {
+                    id: Some(PersonId::new(id)),
+                    ..person
+                };
+                Ok(Some(previous))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn delete(&self, id: &str) -> Result<Option<Person>, MyError> {
+        Ok(self.persons.lock().unwrap().remove(id))
+    }
+
+    fn upsert(&self, person: Person) -> Result<(), MyError> {
+        match &person.id {
+            Some(id) => {
+                self.persons
+                    .lock()
+                    .unwrap()
+                    .insert(id.to_string(), person.clone());
+                Ok(())
+            }
+            None => self.add(person).map(|_| ()),
+        }
+    }
+
+    fn clear(&self) -> Result<(), MyError> {
+        self.persons.lock().unwrap().clear();
+        Ok(())
+    }
+
+    fn replace_all(&self, persons: Vec<Person>) -> Result<(), MyError> {
+        let replaced = with_ids(persons)?
+            .into_iter()
+            .filter_map(|person| Some((person.id.as_ref()?.to_string(), person)))
+            .collect();
+        *self.persons.lock().unwrap() = replaced;
+        Ok(())
+    }
+}
+
+///
+/// le stockage SQLite, dans le fichier du locataire
+///
+impl PersonStore for SqlitePersons {
+    fn list(&self) -> Result<Vec<Person>, MyError> {
+        SqlitePersons::list(self)
+    }
+
+    fn search(
+        &self,
+        nom: Option<&str>,
+        prenom: Option<&str>,
+        skip: i64,
+        limit: i64,
+    ) -> Result<(Vec<Person>, i64), MyError> {
+        SqlitePersons::search(self, nom, prenom, skip, limit)
+    }
+
+    fn get(&self, id: &str) -> Result<Option<Person>, MyError> {
+        SqlitePersons::get(self, id)
+    }
+
+    fn add(&self, person: Person) -> Result<Person, MyError> {
+        SqlitePersons::add(self, person)
+    }
+
+    fn modify(&self, id: &str, person: Person) -> Result<Option<Person>, MyError> {
+        SqlitePersons::modify(self, id, person)
+    }
+
+    fn delete(&self, id: &str) -> Result<Option<Person>, MyError> {
+        SqlitePersons::delete(self, id)
+    }
+
+    fn upsert(&self, person: Person) -> Result<(), MyError> {
+        SqlitePersons::upsert(self, person)
+    }
+
+    fn clear(&self) -> Result<(), MyError> {
+        SqlitePersons::clear(self)
+    }
+
+    fn replace_all(&self, persons: Vec<Person>) -> Result<(), MyError> {
+        SqlitePersons::replace_all(self, with_ids(persons)?)
+    }
+
+    fn transaction(
+        &self,
+        run: &mut dyn FnMut(&dyn PersonWriter) -> bool,
+    ) -> Option<Result<bool, MyError>> {
+        Some(SqlitePersons::transaction(self, |writer| run(writer)))
+    }
+}
+
+///
+/// le stockage PostgreSQL
+///
+impl PersonStore for PostgresPersons {
+    fn list(&self) -> Result<Vec<Person>, MyError> {
+        PostgresPersons::list(self)
+    }
+
+    fn search(
+        &self,
+        nom: Option<&str>,
+        prenom: Option<&str>,
+        skip: i64,
+        limit: i64,
+    ) -> Result<(Vec<Person>, i64), MyError> {
+        PostgresPersons::search(self, nom, prenom, skip, limit)
+    }
+
+    fn get(&self, id: &str) -> Result<Option<Person>, MyError> {
+        PostgresPersons::get(self, id)
+    }
+
+    fn add(&self, person: Person) -> Result<Person, MyError> {
+        PostgresPersons::add(self, person)
+    }
+
+    fn modify(&self, id: &str, person: Person) -> Result<Option<Person>, MyError> {
+        PostgresPersons::modify(self, id, person)
+    }
+
+    fn delete(&self, id: &str) -> Result<Option<Person>, MyError> {
+        PostgresPersons::delete(self, id)
+    }
+
+    fn upsert(&self, person: Person) -> Result<(), MyError> {
+        PostgresPersons::upsert(self, person)
+    }
+
+    fn clear(&self) -> Result<(), MyError> {
+        PostgresPersons::clear(self)
+    }
+
+    fn replace_all(&self, persons: Vec<Person>) -> Result<(), MyError> {
+        PostgresPersons::replace_all(self, with_ids(persons)?)
+    }
+
+    fn transaction(
+        &self,
+        run: &mut dyn FnMut(&dyn PersonWriter) -> bool,
+    ) -> Option<Result<bool, MyError>> {
+        Some(PostgresPersons::transaction(self, |writer| run(writer)))
+    }
+}
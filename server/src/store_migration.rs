@@ -0,0 +1,165 @@
+// server/src/store_migration.rs
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::db_postgres::PostgresIdMap;
+use crate::errors::MyError;
+use crate::store::PersonStore;
+use shared::Person;
+
+///
+/// la correspondance entre l'id d'origine et l'id dans le nouveau stockage
+/// une personne déjà dans la table n'est pas recopiée : la copie peut reprendre
+///
+pub trait IdMap {
+    fn get(&self, source_id: &str) -> Result<Option<String>, MyError>;
+    fn insert(&self, source_id: &str, target_id: &str) -> Result<(), MyError>;
+    fn all(&self) -> Result<Vec<(String, String)>, MyError>;
+}
+
+impl IdMap for PostgresIdMap {
+    fn get(&self, source_id: &str) -> Result<Option<String>, MyError> {
+        PostgresIdMap::get(self, source_id)
+    }
+
+    fn insert(&self, source_id: &str, target_id: &str) -> Result<(), MyError> {
+        PostgresIdMap::insert(self, source_id, target_id)
+    }
+
+    fn all(&self) -> Result<Vec<(String, String)>, MyError> {
+        PostgresIdMap::all(self)
+    }
+}
+
+///
+/// la correspondance en mémoire (tests)
+///
+#[derive(Default)]
+pub struct MemoryIdMap {
+    ids: Mutex<BTreeMap<String, String>>,
+}
+
+impl IdMap for MemoryIdMap {
+    fn get(&self, source_id: &str) -> Result<Option<String>, MyError> {
+        Ok(self.ids.lock().unwrap().get(source_id).cloned())
+    }
+
+    fn insert(&self, source_id: &str, target_id: &str) -> Result<(), MyError> {
+        self.ids
+            .lock()
+            .unwrap()
+            .insert(source_id.to_owned(), target_id.to_owned());
+        Ok(())
+    }
+
+    fn all(&self) -> Result<Vec<(String, String)>, MyError> {
+        Ok(self
+            .ids
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(source, target)| (source.clone(), target.clone()))
+            .collect())
+    }
+}
+
+///
+/// le résultat de la copie et de sa vérification
+/// les sommes portent sur (id dans le nouveau stockage, nom, prénom)
+///
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CopyReport {
+    pub copied: usize,
+    pub skipped: usize,
+    pub source_count: usize,
+    pub target_count: usize,
+    pub source_checksum: String,
+    pub target_checksum: String,
+}
+
+impl CopyReport {
+    pub fn verified(&self) -> bool {
+        self.source_count == self.target_count && self.source_checksum == self.target_checksum
+    }
+}
+
+// la somme SHA-256 d'une liste de personnes, indépendante de l'ordre
+fn checksum(mut lines: Vec<String>) -> String {
+    lines.sort();
+    let mut hasher = Sha256::new();
+    for line in lines {
+        hasher.input(line.as_bytes());
+        hasher.input(b"\n");
+    }
+    hex::encode(hasher.result())
+}
+
+fn line(id: &str, person: &Person) -> String {
+    format!("{}\t{}\t{}", id, person.nom, person.prenom)
+}
+
+///
+/// copie toutes les personnes de source vers target en gardant leurs id
+/// chaque personne copiée est notée dans map ; relancée, la copie reprend là où elle s'est arrêtée
+/// à la fin, on compare les nombres et les sommes des deux côtés
+///
+pub fn copy_persons(
+    source: &dyn PersonStore,
+    target: &dyn PersonStore,
+    map: &dyn IdMap,
+) -> Result<CopyReport, MyError> {
+    let persons = source.list()?;
+    let mut copied = 0;
+    let mut skipped = 0;
+    for person in &persons {
+        let source_id = match &person.id {
+            Some(id) => id.to_string(),
+            None => return Err(MyError::Migration("a source person has no id".to_owned())),
+        };
+        if map.get(&source_id)?.is_some() {
+            skipped += 1;
+            continue;
+        }
+        // upsert garde l'id ; si la copie est coupée avant insert, on recopie la même personne
+        target.upsert(person.clone())?;
+        map.insert(&source_id, &source_id)?;
+        copied += 1;
+    }
+
+    // vérification
+    let ids: BTreeMap<String, String> = map.all()?.into_iter().collect();
+    let source_lines: Vec<String> = persons
+        .iter()
+        .filter_map(|p| {
+            let source_id = p.id.as_ref()?.to_string();
+            let target_id = ids.get(&source_id)?;
+            Some(line(target_id, p))
+        })
+        .collect();
+    let mapped_targets: Vec<&String> = ids.values().collect();
+    let target_lines: Vec<String> = target
+        .list()?
+        .iter()
+        .filter_map(|p| {
+            let target_id = p.id.as_ref()?.to_string();
+            if mapped_targets.contains(&&target_id) {
+                Some(line(&target_id, p))
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    Ok(CopyReport {
+        copied,
+        skipped,
+        source_count: persons.len(),
+        target_count: target_lines.len(),
+        source_checksum: checksum(source_lines),
+        target_checksum: checksum(target_lines),
+    })
+}
@@ -0,0 +1,233 @@
+// server/src/telemetry.rs
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::Error;
+use futures::future::{ok, Future, Ready};
+use tracing::field::Empty;
+use tracing_futures::Instrument;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::EnvFilter;
+
+use crate::config::Config;
+use crate::metrics::route_label;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+// le filtre quand RUST_LOG n'est pas fixé
+const DEFAULT_FILTER: &str = "info,actix_server=info,actix_web=info";
+
+// longueur maximale d'un X-Request-Id reçu
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+///
+/// le format des journaux : json (par défaut) ou text, plus lisible en développement
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogFormat {
+    Json,
+    Text,
+}
+
+impl std::str::FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(LogFormat::Json),
+            "text" => Ok(LogFormat::Text),
+            other => Err(format!("unknown log format '{}' (json or text)", other)),
+        }
+    }
+}
+
+///
+/// garde l'export OTLP en vie ; les dernières traces partent quand il est lâché
+/// à garder jusqu'à la fin de main
+///
+pub struct TelemetryGuard {
+    _otlp: Option<Box<dyn std::any::Any>>,
+}
+
+#[cfg(feature = "otlp")]
+fn otlp_layer<S>(
+    endpoint: &str,
+) -> Result<
+    (
+        tracing_opentelemetry::OpenTelemetryLayer<S, opentelemetry::sdk::trace::Tracer>,
+        Box<dyn std::any::Any>,
+    ),
+    String,
+>
+where
+    S: tracing::Subscriber + for<'span> tracing_subscriber::registry::LookupSpan<'span>,
+{
+    let (tracer, uninstall) = opentelemetry_otlp::new_pipeline()
+        .with_endpoint(endpoint)
+        .with_service_name("seed-server")
+        .install()
+        .map_err(|e| e.to_string())?;
+    Ok((
+        tracing_opentelemetry::layer().with_tracer(tracer),
+        Box::new(uninstall),
+    ))
+}
+
+///
+/// installe le journal structuré
+/// le filtre vient de RUST_LOG s'il est fixé ; il n'est jamais écrasé
+/// avec la feature otlp et SEED_OTLP_ENDPOINT, les spans partent aussi vers un collecteur
+///
+pub fn init(config: &Config) -> TelemetryGuard {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER));
+    let (json, text) = match config.log_format {
+        LogFormat::Json => (Some(tracing_subscriber::fmt::layer().json()), None),
+        LogFormat::Text => (None, Some(tracing_subscriber::fmt::layer())),
+    };
+    let registry = tracing_subscriber::registry()
+        .with(filter)
+        .with(json)
+        .with(text);
+
+    #[cfg(feature = "otlp")]
+    {
+        if let Some(endpoint) = &config.otlp_endpoint {
+            match otlp_layer(endpoint) {
+                Ok((layer, uninstall)) => {
+                    registry.with(layer).init();
+                    tracing::info!(endpoint = %endpoint, "OTLP export enabled");
+                    return TelemetryGuard {
+                        _otlp: Some(uninstall),
+                    };
+                }
+                Err(e) => {
+                    registry.init();
+                    tracing::error!(endpoint = %endpoint, error = %e, "OTLP export disabled");
+                    return TelemetryGuard { _otlp: None };
+                }
+            }
+        }
+    }
+
+    registry.init();
+    if cfg!(not(feature = "otlp")) && config.otlp_endpoint.is_some() {
+        tracing::warn!("SEED_OTLP_ENDPOINT needs the otlp feature, spans are not exported");
+    }
+    TelemetryGuard { _otlp: None }
+}
+
+///
+/// l'identifiant de la requête : celui du client s'il est raisonnable, sinon un nouveau
+///
+pub fn request_id(received: Option<&HeaderValue>) -> String {
+    received
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| {
+            !id.is_empty()
+                && id.len() <= MAX_REQUEST_ID_LEN
+                && id.chars().all(|c| c.is_ascii_graphic())
+        })
+        .map(str::to_owned)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string())
+}
+
+///
+/// le middleware qui ouvre un span par requête et journalise sa fin
+/// le span porte request_id, method, route, status et latency_ms
+/// tenant est rempli par l'extracteur Tenant, user par l'authentification
+/// l'en-tête X-Request-Id est renvoyé dans la réponse
+///
+pub struct RequestTracing;
+
+impl<S, B> Transform<S> for RequestTracing
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RequestTracingMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(RequestTracingMiddleware { service })
+    }
+}
+
+pub struct RequestTracingMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service for RequestTracingMiddleware<S>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: ServiceRequest) -> Self::Future {
+        let id = request_id(req.headers().get(REQUEST_ID_HEADER));
+        let span = tracing::info_span!(
+            "request",
+            request_id = %id,
+            method = %req.method(),
+            route = route_label(req.path()),
+            path = %req.path(),
+            tenant = Empty,
+            user = Empty,
+            status = Empty,
+            latency_ms = Empty,
+        );
+        let start = Instant::now();
+
+        // les extracteurs peuvent tourner dès l'appel : on entre dans le span avant
+        let fut = {
+            let _entered = span.enter();
+            self.service.call(req)
+        };
+        let request_span = span.clone();
+        Box::pin(
+            async move {
+                let mut res = fut.await;
+                let latency_ms = start.elapsed().as_millis() as u64;
+                let status = match &res {
+                    Ok(res) => res.status(),
+                    Err(e) => e.as_response_error().status_code(),
+                };
+                request_span.record("status", &status.as_u16());
+                request_span.record("latency_ms", &latency_ms);
+                if status.is_server_error() {
+                    tracing::error!(status = status.as_u16(), latency_ms, "request failed");
+                } else {
+                    tracing::info!(status = status.as_u16(), latency_ms, "request finished");
+                }
+
+                if let Ok(res) = &mut res {
+                    if let Ok(value) = HeaderValue::from_str(&id) {
+                        res.headers_mut()
+                            .insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+                    }
+                }
+                res
+            }
+            .instrument(span),
+        )
+    }
+}
@@ -0,0 +1,230 @@
+// server/src/tenancy.rs
+
+use actix_web::dev::Payload;
+use actix_web::{web, FromRequest, HttpRequest};
+use futures::future::{ready, Ready};
+
+use crate::auth::Principal;
+use crate::config::Config;
+use crate::db_mongo::{self, PersonsScope};
+use crate::db_postgres::PostgresPersons;
+use crate::db_sqlite::{self, SqlitePersons};
+use crate::errors::MyError;
+use crate::store::{self, PersonStore};
+
+// longueur maximale d'un identifiant de locataire
+// le nom de la base "{database}_{tenant}" doit rester sous la limite de MongoDB
+const MAX_TENANT_LEN: usize = 48;
+
+///
+/// comment les personnes des locataires (tenants) sont séparées
+/// single : un seul locataire, pas d'identifiant
+/// database : une base par locataire, nommée {database}_{tenant}
+/// collection : une seule collection Persons, chaque document porte un tenant_id
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TenantMode {
+    Single,
+    Database,
+    Collection,
+}
+
+impl Default for TenantMode {
+    fn default() -> Self {
+        TenantMode::Single
+    }
+}
+
+impl std::str::FromStr for TenantMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "single" => Ok(TenantMode::Single),
+            "database" => Ok(TenantMode::Database),
+            "collection" => Ok(TenantMode::Collection),
+            other => Err(format!(
+                "unknown tenant mode '{}' (single, database or collection)",
+                other
+            )),
+        }
+    }
+}
+
+///
+/// le locataire d'une requête
+/// s'utilise comme extracteur dans les handlers : tenant.store()
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Tenant {
+    pub mode: TenantMode,
+    pub id: Option<String>,
+}
+
+impl Tenant {
+    pub fn new(mode: TenantMode, id: Option<String>) -> Self {
+        Self { mode, id }
+    }
+
+    ///
+    /// le stockage des personnes de ce locataire, avec le backend configuré
+    /// toutes les lectures et écritures de personnes doivent passer par là
+    ///
+    pub fn store(&self) -> Result<Box<dyn PersonStore>, MyError> {
+        store::open_store(self)
+    }
+
+    ///
+    /// la collection Persons de ce locataire, avec MongoDB
+    ///
+    pub fn persons(&self) -> Result<PersonsScope, MyError> {
+        match (self.mode, &self.id) {
+            (TenantMode::Database, Some(id)) => {
+                let name = format!("{}_{}", db_mongo::settings().database, id);
+                Ok(PersonsScope {
+                    collection: db_mongo::get_database_named(&name)?.collection("Persons"),
+                    tenant_id: None,
+                })
+            }
+            (TenantMode::Collection, Some(id)) => Ok(PersonsScope {
+                collection: db_mongo::get_collection()?,
+                tenant_id: Some(id.clone()),
+            }),
+            (TenantMode::Single, _) => PersonsScope::default_scope(),
+            (_, None) => Err(MyError::Tenant("no tenant given".to_owned())),
+        }
+    }
+
+    ///
+    /// les personnes de ce locataire, avec SQLite
+    /// en mode database, chaque locataire a son fichier : seed_acme.db
+    ///
+    pub fn sqlite_persons(&self) -> Result<SqlitePersons, MyError> {
+        match (self.mode, &self.id) {
+            (TenantMode::Database, Some(id)) => {
+                Ok(SqlitePersons::new(db_sqlite::tenant_path(id), None))
+            }
+            (TenantMode::Collection, Some(id)) => {
+                Ok(SqlitePersons::new(db_sqlite::path(), Some(id.clone())))
+            }
+            (TenantMode::Single, _) => Ok(SqlitePersons::new(db_sqlite::path(), None)),
+            (_, None) => Err(MyError::Tenant("no tenant given".to_owned())),
+        }
+    }
+
+    ///
+    /// les personnes de ce locataire, avec PostgreSQL
+    /// les modes database et collection utilisent tous deux la colonne tenant_id
+    ///
+    pub fn postgres_persons(&self) -> Result<PostgresPersons, MyError> {
+        match (self.mode, &self.id) {
+            (TenantMode::Single, _) => Ok(PostgresPersons::new(None)),
+            (_, Some(id)) => Ok(PostgresPersons::new(Some(id.clone()))),
+            (_, None) => Err(MyError::Tenant("no tenant given".to_owned())),
+        }
+    }
+}
+
+///
+/// un identifiant de locataire : lettres minuscules, chiffres, - et _
+///
+pub fn validate_tenant_id(id: &str) -> Result<String, MyError> {
+    let id = id.trim().to_lowercase();
+    let valid = !id.is_empty()
+        && id.len() <= MAX_TENANT_LEN
+        && id
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+    if valid {
+        Ok(id)
+    } else {
+        Err(MyError::Tenant(format!(
+            "'{}' is not a valid tenant id",
+            id
+        )))
+    }
+}
+
+// le premier élément du nom d'hôte, s'il y a un sous-domaine
+// acme.example.com:8000 -> acme ; example.com et localhost -> rien
+fn subdomain(host: &str) -> Option<&str> {
+    let host = host.split(':').next().unwrap_or(host);
+    let labels: Vec<&str> = host.split('.').collect();
+    if labels.len() > 2 && !labels.iter().all(|l| l.chars().all(|c| c.is_ascii_digit())) {
+        Some(labels[0])
+    } else {
+        None
+    }
+}
+
+///
+/// trouve le locataire de la requête : l'en-tête d'abord, puis le sous-domaine
+/// sans locataire, les modes database et collection refusent la requête
+///
+/// le locataire d'une clé d'API passe avant l'en-tête :
+/// un en-tête qui désigne un autre locataire est refusé
+///
+pub fn resolve_tenant(req: &HttpRequest, config: &Config) -> Result<Tenant, MyError> {
+    if config.tenant_mode == TenantMode::Single {
+        return Ok(Tenant::default());
+    }
+    let from_principal = Principal::from_request(req).and_then(|principal| principal.tenant);
+
+    let from_header = req
+        .headers()
+        .get(config.tenant_header.as_str())
+        .map(|value| {
+            value
+                .to_str()
+                .map_err(|_| MyError::Tenant("the tenant header is not valid text".to_owned()))
+        })
+        .transpose()?;
+    let from_host = if config.tenant_subdomain {
+        req.headers()
+            .get("Host")
+            .and_then(|value| value.to_str().ok())
+            .and_then(subdomain)
+    } else {
+        None
+    };
+
+    if let Some(tenant) = from_principal {
+        return match from_header {
+            Some(id) if id != tenant => Err(MyError::Forbidden(format!(
+                "the API key is limited to the tenant {}",
+                tenant
+            ))),
+            _ => Ok(Tenant::new(config.tenant_mode, Some(tenant))),
+        };
+    }
+
+    match from_header.or(from_host) {
+        Some(id) => Ok(Tenant::new(
+            config.tenant_mode,
+            Some(validate_tenant_id(id)?),
+        )),
+        None => Err(MyError::Tenant(format!(
+            "missing {} header",
+            config.tenant_header
+        ))),
+    }
+}
+
+impl FromRequest for Tenant {
+    type Error = MyError;
+    type Future = Ready<Result<Self, MyError>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        // sans configuration (les tests), un seul locataire
+        let result = match req.app_data::<web::Data<Config>>() {
+            Some(config) => resolve_tenant(req, config),
+            None => Ok(Tenant::default()),
+        };
+        // le locataire apparaît dans le span de la requête (telemetry)
+        if let Ok(Tenant { id: Some(id), .. }) = &result {
+            tracing::Span::current().record("tenant", &id.as_str());
+        }
+        ready(result)
+    }
+}
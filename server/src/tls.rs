@@ -0,0 +1,237 @@
+// server/src/tls.rs
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime};
+
+use actix_web::http::header;
+use actix_web::{web, HttpRequest, HttpResponse};
+use rustls::internal::pemfile;
+use rustls::sign::{self, CertifiedKey};
+use rustls::{ClientHello, NoClientAuth, ResolvesServerCert, ServerConfig};
+
+use crate::errors::MyError;
+
+// l'intervalle de vérification des fichiers du certificat
+const WATCH_INTERVAL: Duration = Duration::from_secs(10);
+
+///
+/// le certificat (chaîne complète) et la clé privée, au format PEM
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct TlsFiles {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+impl TlsFiles {
+    pub fn new(cert: impl Into<PathBuf>, key: impl Into<PathBuf>) -> Self {
+        Self {
+            cert: cert.into(),
+            key: key.into(),
+        }
+    }
+
+    // les dates de modification : un changement déclenche le rechargement
+    fn modified(&self) -> Option<(SystemTime, SystemTime)> {
+        let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        Some((modified(&self.cert)?, modified(&self.key)?))
+    }
+}
+
+fn open(path: &Path) -> Result<BufReader<File>, MyError> {
+    File::open(path)
+        .map(BufReader::new)
+        .map_err(|e| MyError::Tls(format!("{}: {}", path.display(), e)))
+}
+
+///
+/// lit le certificat et la clé (PKCS#8 ou RSA)
+///
+pub fn load_certified_key(files: &TlsFiles) -> Result<CertifiedKey, MyError> {
+    let certs = pemfile::certs(&mut open(&files.cert)?)
+        .map_err(|_| MyError::Tls(format!("{}: invalid certificate", files.cert.display())))?;
+    if certs.is_empty() {
+        return Err(MyError::Tls(format!(
+            "{}: no certificate",
+            files.cert.display()
+        )));
+    }
+
+    let invalid_key = || MyError::Tls(format!("{}: invalid private key", files.key.display()));
+    let mut keys =
+        pemfile::pkcs8_private_keys(&mut open(&files.key)?).map_err(|_| invalid_key())?;
+    if keys.is_empty() {
+        keys = pemfile::rsa_private_keys(&mut open(&files.key)?).map_err(|_| invalid_key())?;
+    }
+    let key = keys.into_iter().next().ok_or_else(invalid_key)?;
+    let signing_key = sign::any_supported_type(&key).map_err(|_| invalid_key())?;
+
+    Ok(CertifiedKey::new(certs, Arc::new(signing_key)))
+}
+
+///
+/// donne le certificat du moment à chaque poignée de main TLS
+/// reload() remplace le certificat : les connexions ouvertes ne sont pas coupées,
+/// les nouvelles reçoivent le nouveau certificat
+///
+pub struct ReloadingCert {
+    files: TlsFiles,
+    current: RwLock<CertifiedKey>,
+    modified: Mutex<Option<(SystemTime, SystemTime)>>,
+}
+
+impl ReloadingCert {
+    pub fn load(files: TlsFiles) -> Result<Self, MyError> {
+        let current = load_certified_key(&files)?;
+        let modified = files.modified();
+        Ok(Self {
+            files,
+            current: RwLock::new(current),
+            modified: Mutex::new(modified),
+        })
+    }
+
+    ///
+    /// relit les fichiers ; en cas d'erreur, l'ancien certificat reste en place
+    ///
+    pub fn reload(&self) -> Result<(), MyError> {
+        let modified = self.files.modified();
+        let key = load_certified_key(&self.files)?;
+        *self.current.write().unwrap() = key;
+        *self.modified.lock().unwrap() = modified;
+        Ok(())
+    }
+
+    ///
+    /// recharge seulement si un des fichiers a changé depuis le dernier chargement
+    ///
+    pub fn reload_if_changed(&self) -> Result<bool, MyError> {
+        let modified = self.files.modified();
+        if modified.is_none() || modified == *self.modified.lock().unwrap() {
+            return Ok(false);
+        }
+        self.reload().map(|_| true)
+    }
+
+    ///
+    /// le certificat servi en ce moment
+    ///
+    pub fn current(&self) -> CertifiedKey {
+        self.current.read().unwrap().clone()
+    }
+}
+
+impl ResolvesServerCert for ReloadingCert {
+    fn resolve(&self, _client_hello: ClientHello) -> Option<CertifiedKey> {
+        Some(self.current())
+    }
+}
+
+///
+/// la configuration rustls pour HttpServer::bind_rustls
+///
+pub fn server_config(cert: Arc<ReloadingCert>) -> ServerConfig {
+    let mut config = ServerConfig::new(NoClientAuth::new());
+    config.cert_resolver = cert;
+    config
+}
+
+fn log_reload(result: Result<bool, MyError>, trigger: &str) {
+    match result {
+        Ok(true) => tracing::info!(trigger, "TLS certificate reloaded"),
+        Ok(false) => {}
+        Err(e) => tracing::error!(trigger, error = %e, "TLS certificate reload failed"),
+    }
+}
+
+///
+/// recharge le certificat sur SIGHUP et quand les fichiers changent
+/// à appeler dans le runtime actix (main)
+///
+pub fn watch(cert: Arc<ReloadingCert>) {
+    #[cfg(unix)]
+    {
+        use actix_rt::signal::unix::{signal, SignalKind};
+
+        let cert = cert.clone();
+        match signal(SignalKind::hangup()) {
+            Ok(mut hangup) => actix_rt::spawn(async move {
+                while hangup.recv().await.is_some() {
+                    log_reload(cert.reload().map(|_| true), "SIGHUP");
+                }
+            }),
+            Err(e) => tracing::warn!(error = %e, "SIGHUP is not available"),
+        }
+    }
+
+    actix_rt::spawn(async move {
+        let mut interval = actix_rt::time::interval(WATCH_INTERVAL);
+        loop {
+            interval.tick().await;
+            log_reload(cert.reload_if_changed(), "file change");
+        }
+    });
+}
+
+///
+/// --dev-tls : un certificat auto-signé pour localhost
+/// il est gardé dans le dossier et réutilisé aux démarrages suivants,
+/// le navigateur n'a besoin de l'accepter qu'une fois
+///
+pub fn dev_certificate(dir: &Path) -> Result<TlsFiles, MyError> {
+    let files = TlsFiles::new(dir.join("dev-cert.pem"), dir.join("dev-key.pem"));
+    if files.cert.exists() && files.key.exists() {
+        return Ok(files);
+    }
+
+    let generated = rcgen::generate_simple_self_signed(vec!["localhost".to_owned()])
+        .map_err(|e| MyError::Tls(e.to_string()))?;
+    let cert = generated
+        .serialize_pem()
+        .map_err(|e| MyError::Tls(e.to_string()))?;
+    std::fs::create_dir_all(dir)?;
+    std::fs::write(&files.cert, cert)?;
+    std::fs::write(&files.key, generated.serialize_private_key_pem())?;
+    Ok(files)
+}
+
+///
+/// l'adresse HTTPS qui remplace une adresse HTTP : même hôte, port HTTPS
+///
+pub fn https_location(host: &str, https_port: u16, path_and_query: &str) -> String {
+    // l'hôte sans son port ; [::1]:8080 garde ses crochets
+    let hostname = match host.rfind(':') {
+        Some(i) if !host[i..].contains(']') => &host[..i],
+        _ => host,
+    };
+    if https_port == 443 {
+        format!("https://{}{}", hostname, path_and_query)
+    } else {
+        format!("https://{}:{}{}", hostname, https_port, path_and_query)
+    }
+}
+
+///
+/// le port HTTPS, pour la redirection du port HTTP
+///
+#[derive(Clone, Copy, Debug)]
+pub struct HttpsPort(pub u16);
+
+///
+/// toutes les requêtes du port HTTP : redirection permanente vers HTTPS
+/// 308 garde la méthode et le corps des POST
+///
+pub async fn https_redirect_hdl(req: HttpRequest, port: web::Data<HttpsPort>) -> HttpResponse {
+    let path_and_query = req
+        .uri()
+        .path_and_query()
+        .map(|pq| pq.as_str())
+        .unwrap_or("/");
+    let location = https_location(req.connection_info().host(), port.0, path_and_query);
+    HttpResponse::PermanentRedirect()
+        .header(header::LOCATION, location)
+        .finish()
+}
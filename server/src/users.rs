@@ -0,0 +1,223 @@
+// server/src/users.rs
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use bson::{doc, from_bson, Bson, Document};
+use chrono::Utc;
+
+use crate::db_mongo;
+use crate::db_postgres::PostgresUsers;
+use crate::db_sqlite::{self, SqliteUsers};
+use crate::errors::MyError;
+use crate::store::{self, Backend};
+pub use shared::{Scope, User};
+
+///
+/// là où les utilisateurs sont gardés : le stockage des personnes (SEED_STORAGE)
+///
+pub trait UserStore: Send + Sync {
+    fn get(&self, id: &str) -> Result<Option<User>, MyError>;
+    fn find_by_subject(&self, subject: &str) -> Result<Option<User>, MyError>;
+    fn insert(&self, user: &User) -> Result<(), MyError>;
+    // renvoie false si l'utilisateur n'existe pas
+    fn update(&self, user: &User) -> Result<bool, MyError>;
+}
+
+///
+/// le stockage partagé par les routes de connexion (web::Data<SharedUserStore>)
+///
+pub type SharedUserStore = Arc<dyn UserStore>;
+
+///
+/// le stockage des utilisateurs avec le backend configuré
+///
+pub fn open_user_store() -> Result<Box<dyn UserStore>, MyError> {
+    Ok(match store::backend() {
+        Backend::Mongo => Box::new(MongoUsers),
+        Backend::Sqlite => Box::new(SqliteUsers::new(db_sqlite::path())),
+        Backend::Postgres => Box::new(PostgresUsers),
+    })
+}
+
+///
+/// la collection Users de MongoDB
+///
+pub struct MongoUsers;
+
+impl MongoUsers {
+    fn to_document(user: &User) -> Result<Document, MyError> {
+        let mut document = match bson::to_bson(user)? {
+            Bson::Document(document) => document,
+            _ => Document::new(),
+        };
+        document.remove("id");
+        document.insert("_id", user.id.clone());
+        Ok(document)
+    }
+
+    fn find_one(&self, filter: Document) -> Result<Option<User>, MyError> {
+        let coll = db_mongo::get_named_collection("Users")?;
+        match coll.find_one(Some(filter), None)? {
+            Some(document) => Ok(Some(from_bson(Bson::Document(document))?)),
+            None => Ok(None),
+        }
+    }
+}
+
+impl UserStore for MongoUsers {
+    fn get(&self, id: &str) -> Result<Option<User>, MyError> {
+        self.find_one(doc! {"_id": id})
+    }
+
+    fn find_by_subject(&self, subject: &str) -> Result<Option<User>, MyError> {
+        self.find_one(doc! {"subject": subject})
+    }
+
+    fn insert(&self, user: &User) -> Result<(), MyError> {
+        let coll = db_mongo::get_named_collection("Users")?;
+        coll.insert_one(Self::to_document(user)?, None)?;
+        Ok(())
+    }
+
+    fn update(&self, user: &User) -> Result<bool, MyError> {
+        let coll = db_mongo::get_named_collection("Users")?;
+        let result = coll.replace_one(
+            doc! {"_id": user.id.clone()},
+            Self::to_document(user)?,
+            None,
+        )?;
+        Ok(result.matched_count > 0)
+    }
+}
+
+impl UserStore for SqliteUsers {
+    fn get(&self, id: &str) -> Result<Option<User>, MyError> {
+        SqliteUsers::get(self, id)
+    }
+
+    fn find_by_subject(&self, subject: &str) -> Result<Option<User>, MyError> {
+        SqliteUsers::find_by_subject(self, subject)
+    }
+
+    fn insert(&self, user: &User) -> Result<(), MyError> {
+        SqliteUsers::insert(self, user)
+    }
+
+    fn update(&self, user: &User) -> Result<bool, MyError> {
+        SqliteUsers::update(self, user)
+    }
+}
+
+impl UserStore for PostgresUsers {
+    fn get(&self, id: &str) -> Result<Option<User>, MyError> {
+        PostgresUsers::get(self, id)
+    }
+
+    fn find_by_subject(&self, subject: &str) -> Result<Option<User>, MyError> {
+        PostgresUsers::find_by_subject(self, subject)
+    }
+
+    fn insert(&self, user: &User) -> Result<(), MyError> {
+        PostgresUsers::insert(self, user)
+    }
+
+    fn update(&self, user: &User) -> Result<bool, MyError> {
+        PostgresUsers::update(self, user)
+    }
+}
+
+///
+/// les utilisateurs en mémoire (tests)
+///
+#[derive(Default)]
+pub struct MemoryUserStore {
+    users: Mutex<BTreeMap<String, User>>,
+}
+
+impl UserStore for MemoryUserStore {
+    fn get(&self, id: &str) -> Result<Option<User>, MyError> {
+        Ok(self.users.lock().unwrap().get(id).cloned())
+    }
+
+    fn find_by_subject(&self, subject: &str) -> Result<Option<User>, MyError> {
+        Ok(self
+            .users
+            .lock()
+            .unwrap()
+            .values()
+            .find(|user| user.subject == subject)
+            .cloned())
+    }
+
+    fn insert(&self, user: &User) -> Result<(), MyError> {
+        self.users
+            .lock()
+            .unwrap()
+            .insert(user.id.clone(), user.clone());
+        Ok(())
+    }
+
+    fn update(&self, user: &User) -> Result<bool, MyError> {
+        match self.users.lock().unwrap().get_mut(&user.id) {
+            Some(stored) => {
+                *stored = user.clone();
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+///
+/// ce que le fournisseur d'identité dit de l'utilisateur, après validation de l'ID token
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Identity {
+    // <issuer>#<sub>
+    pub subject: String,
+    pub name: String,
+    pub email: Option<String>,
+    pub groups: Vec<String>,
+    pub tenant: Option<String>,
+}
+
+///
+/// l'utilisateur de l'identité : créé à la première connexion, mis à jour ensuite
+/// le fournisseur fait foi : nom, groupes, droits et locataire sont repris à chaque connexion
+///
+pub fn provision(
+    store: &dyn UserStore,
+    identity: Identity,
+    scopes: Vec<Scope>,
+) -> Result<User, MyError> {
+    let now = Utc::now().timestamp_millis();
+    match store.find_by_subject(&identity.subject)? {
+        Some(mut user) => {
+            user.name = identity.name;
+            user.email = identity.email;
+            user.groups = identity.groups;
+            user.scopes = scopes;
+            user.tenant = identity.tenant;
+            user.last_login_at = now;
+            store.update(&user)?;
+            Ok(user)
+        }
+        None => {
+            let user = User {
+                id: uuid::Uuid::new_v4().to_string(),
+                subject: identity.subject,
+                name: identity.name,
+                email: identity.email,
+                groups: identity.groups,
+                scopes,
+                tenant: identity.tenant,
+                created_at: now,
+                last_login_at: now,
+            };
+            store.insert(&user)?;
+            tracing::info!(user = %user.id, "user provisioned");
+            Ok(user)
+        }
+    }
+}
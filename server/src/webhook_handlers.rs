@@ -0,0 +1,101 @@
+// src/webhook_handlers.rs
+
+use actix_web::{web, HttpRequest, HttpResponse, Responder, ResponseError};
+
+use crate::admin::check_global_admin;
+use crate::config::Config;
+use crate::tenancy::Tenant;
+use crate::webhooks::{self, NewWebhook};
+use shared::ErrorEnvelope;
+
+fn webhook_not_found() -> HttpResponse {
+    HttpResponse::NotFound().json(ErrorEnvelope::new("not_found", "webhook not found"))
+}
+
+///
+/// crée un webhook pour le locataire de la requête : il ne recevra que ses événements
+///
+pub async fn add_webhook_hdl(
+    req: HttpRequest,
+    config: web::Data<Config>,
+    tenant: Tenant,
+    new_hook: web::Json<NewWebhook>,
+) -> impl Responder {
+    if let Err(resp) = check_global_admin(&req, &config) {
+        return resp;
+    }
+    let new_hook = NewWebhook {
+        tenant: tenant.id,
+        ..new_hook.into_inner()
+    };
+    if !new_hook.url.starts_with("http://") && !new_hook.url.starts_with("https://") {
+        return HttpResponse::BadRequest().json(ErrorEnvelope::new(
+            "invalid_webhook",
+            "webhook url must be http or https",
+        ));
+    }
+    if new_hook.secret.is_empty() {
+        return HttpResponse::BadRequest().json(ErrorEnvelope::new(
+            "invalid_webhook",
+            "webhook secret must not be empty",
+        ));
+    }
+    match webhooks::add_webhook(new_hook) {
+        Ok(hook) => HttpResponse::Ok().json(hook),
+        Err(e) => e.error_response(),
+    }
+}
+
+pub async fn list_webhooks_hdl(
+    req: HttpRequest,
+    config: web::Data<Config>,
+    tenant: Tenant,
+) -> impl Responder {
+    if let Err(resp) = check_global_admin(&req, &config) {
+        return resp;
+    }
+    match webhooks::get_list_webhooks(tenant.id.as_deref()) {
+        Ok(hooks) => HttpResponse::Ok().json(hooks),
+        Err(e) => e.error_response(),
+    }
+}
+
+pub async fn delete_webhook_hdl(
+    req: HttpRequest,
+    config: web::Data<Config>,
+    tenant: Tenant,
+    id: web::Path<String>,
+) -> impl Responder {
+    if let Err(resp) = check_global_admin(&req, &config) {
+        return resp;
+    }
+    match webhooks::delete_webhook(tenant.id.as_deref(), &id.into_inner()) {
+        Ok(Some(hook)) => HttpResponse::Ok().json(hook),
+        Ok(None) => webhook_not_found(),
+        Err(e) => e.error_response(),
+    }
+}
+
+///
+/// les livraisons d'un webhook du locataire de la requête
+///
+pub async fn list_deliveries_hdl(
+    req: HttpRequest,
+    config: web::Data<Config>,
+    tenant: Tenant,
+    id: web::Path<String>,
+) -> impl Responder {
+    if let Err(resp) = check_global_admin(&req, &config) {
+        return resp;
+    }
+    let id = id.into_inner();
+    match webhooks::get_webhook_by_id(&id) {
+        Ok(Some(hook)) if hook.tenant == tenant.id => {}
+        Ok(_) => return webhook_not_found(),
+        Err(e) => return e.error_response(),
+    }
+    match webhooks::get_deliveries(&id) {
+        Ok(deliveries) => HttpResponse::Ok().json(deliveries),
+        Err(e) => e.error_response(),
+    }
+}
@@ -0,0 +1,382 @@
+// server/src/webhooks.rs
+
+use std::collections::BTreeSet;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use actix_web::client::Client;
+use actix_web::error::BlockingError;
+use actix_web::web;
+use bson::oid::ObjectId;
+use bson::{doc, from_bson, Bson, Document};
+use chrono::Utc;
+use futures::StreamExt;
+use hmac::{Hmac, Mac};
+use mongodb::options::FindOptions;
+use once_cell::sync::Lazy;
+use serde::de::DeserializeOwned;
+use sha2::Sha256;
+
+use crate::broadcast::Broadcaster;
+use crate::db_mongo;
+use crate::errors::MyError;
+use crate::event_log::LoggedEvent;
+pub use shared::{Delivery, NewWebhook, Webhook};
+
+type HmacSha256 = Hmac<Sha256>;
+
+// nombre d'essais avant d'abandonner une livraison
+const MAX_ATTEMPTS: i32 = 8;
+// premier délai avant de réessayer, doublé à chaque essai
+const BASE_RETRY_DELAY_SECS: i64 = 10;
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub const STATUS_PENDING: &str = "pending";
+pub const STATUS_DELIVERED: &str = "delivered";
+pub const STATUS_FAILED: &str = "failed";
+
+// les événements effacés (droit à l'effacement) que la mise en file n'a peut-être pas encore vus
+// le verrou sépare la mise en file de l'effacement des livraisons
+static ERASED_EVENTS: Lazy<Mutex<BTreeSet<i64>>> = Lazy::new(|| Mutex::new(BTreeSet::new()));
+
+///
+/// décode un document des collections Webhooks et WebhookDeliveries
+/// les ObjectId deviennent leur forme hexadécimale : _id devient id, webhook_id une chaîne
+///
+fn from_document<T: DeserializeOwned>(mut doc: Document) -> Result<T, MyError> {
+    match doc.remove("_id") {
+        Some(Bson::ObjectId(oid)) => {
+            doc.insert("id", oid.to_hex());
+        }
+        Some(other) => {
+            doc.insert("id", other);
+        }
+        None => {}
+    }
+    if let Some(Bson::ObjectId(oid)) = doc.get("webhook_id") {
+        let webhook_id = oid.to_hex();
+        doc.insert("webhook_id", webhook_id);
+    }
+    Ok(from_bson(Bson::Document(doc))?)
+}
+
+// le locataire tel qu'il est rangé ; null (ou absent, avant les locataires) pour le mode single
+fn tenant_bson(tenant: Option<&str>) -> Bson {
+    tenant.map_or(Bson::Null, |tenant| Bson::String(tenant.to_owned()))
+}
+
+pub fn add_webhook(new_hook: NewWebhook) -> Result<Webhook, MyError> {
+    let coll = db_mongo::get_named_collection("Webhooks")?;
+    let result = coll.insert_one(
+        doc! {
+            "url": new_hook.url.clone(),
+            "events": new_hook.events.iter().map(|e| Bson::String(e.clone())).collect::<Vec<_>>(),
+            "secret": new_hook.secret.clone(),
+            "active": true,
+            "tenant": tenant_bson(new_hook.tenant.as_deref()),
+        },
+        None,
+    )?;
+    Ok(Webhook {
+        id: result.inserted_id.as_object_id().map(ObjectId::to_hex),
+        url: new_hook.url,
+        events: new_hook.events,
+        secret: new_hook.secret,
+        active: true,
+        tenant: new_hook.tenant,
+    })
+}
+
+///
+/// les webhooks d'un locataire
+///
+pub fn get_list_webhooks(tenant: Option<&str>) -> Result<Vec<Webhook>, MyError> {
+    let cursor = db_mongo::get_named_collection("Webhooks")?
+        .find(Some(doc! {"tenant": tenant_bson(tenant)}), None)?;
+    let res: Result<Vec<_>, MyError> = cursor.map(|row| from_document::<Webhook>(row?)).collect();
+    res
+}
+
+pub fn get_webhook_by_id(hook_id: &str) -> Result<Option<Webhook>, MyError> {
+    let coll = db_mongo::get_named_collection("Webhooks")?;
+    let cursor: Option<Document> =
+        coll.find_one(Some(doc! {"_id": ObjectId::with_string(hook_id)?}), None)?;
+    cursor
+        .map(from_document::<Webhook>)
+        .map_or(Ok(None), |v| v.map(Some))
+}
+
+///
+/// efface le webhook s'il est à ce locataire
+///
+pub fn delete_webhook(tenant: Option<&str>, hook_id: &str) -> Result<Option<Webhook>, MyError> {
+    let coll = db_mongo::get_named_collection("Webhooks")?;
+    let cursor: Option<Document> = coll.find_one_and_delete(
+        doc! {"_id": ObjectId::with_string(hook_id)?, "tenant": tenant_bson(tenant)},
+        Some(Default::default()),
+    )?;
+    cursor
+        .map(from_document::<Webhook>)
+        .map_or(Ok(None), |v| v.map(Some))
+}
+
+pub fn get_deliveries(hook_id: &str) -> Result<Vec<Delivery>, MyError> {
+    let coll = db_mongo::get_named_collection("WebhookDeliveries")?;
+    let options = FindOptions::builder().sort(doc! {"created_at": -1}).build();
+    let cursor = coll.find(
+        Some(doc! {"webhook_id": ObjectId::with_string(hook_id)?}),
+        Some(options),
+    )?;
+    let res: Result<Vec<_>, MyError> = cursor.map(|row| from_document::<Delivery>(row?)).collect();
+    res
+}
+
+fn event_bsons(event_ids: &[i64]) -> Vec<Bson> {
+    event_ids.iter().map(|id| Bson::I64(*id)).collect()
+}
+
+///
+/// les livraisons des événements donnés, tous webhooks confondus
+///
+pub fn deliveries_for_events(event_ids: &[i64]) -> Result<Vec<Delivery>, MyError> {
+    let coll = db_mongo::get_named_collection("WebhookDeliveries")?;
+    let options = FindOptions::builder().sort(doc! {"created_at": 1}).build();
+    let cursor = coll.find(
+        Some(doc! {"event_id": {"$in": event_bsons(event_ids)}}),
+        Some(options),
+    )?;
+    let res: Result<Vec<_>, MyError> = cursor.map(|row| from_document::<Delivery>(row?)).collect();
+    res
+}
+
+///
+/// efface les livraisons des événements donnés, même celles encore en file
+/// ces événements ne seront plus mis en file s'ils attendent encore la tâche de fond
+/// renvoie le nombre de livraisons effacées
+///
+pub fn delete_deliveries_for_events(event_ids: &[i64]) -> Result<i64, MyError> {
+    let mut erased = ERASED_EVENTS.lock().unwrap();
+    erased.extend(event_ids);
+    let coll = db_mongo::get_named_collection("WebhookDeliveries")?;
+    let result = coll.delete_many(doc! {"event_id": {"$in": event_bsons(event_ids)}}, None)?;
+    Ok(result.deleted_count)
+}
+
+///
+/// met en file une livraison pour chaque webhook du locataire intéressé par l'événement
+/// la file est dans la collection WebhookDeliveries, elle survit aux redémarrages
+/// un événement effacé entre-temps n'est pas mis en file
+///
+pub fn enqueue_deliveries(logged: &LoggedEvent) -> Result<(), MyError> {
+    // les événements arrivent dans l'ordre : les numéros déjà passés sont oubliés
+    let mut erased = ERASED_EVENTS.lock().unwrap();
+    *erased = erased.split_off(&logged.id);
+    if erased.remove(&logged.id) {
+        return Ok(());
+    }
+
+    let event_type = logged.event.kind();
+    let payload = serde_json::json!({
+        "id": logged.id,
+        "type": event_type,
+        "tenant": logged.tenant,
+        "person": logged.event.person(),
+    })
+    .to_string();
+    let now = Utc::now().timestamp_millis();
+
+    let coll = db_mongo::get_named_collection("WebhookDeliveries")?;
+    for hook in get_list_webhooks(logged.tenant.as_deref())?
+        .iter()
+        .filter(|h| h.accepts(event_type))
+    {
+        let hook_id = match &hook.id {
+            Some(id) => ObjectId::with_string(id)?,
+            None => continue,
+        };
+        coll.insert_one(
+            doc! {
+                "webhook_id": hook_id,
+                "event_id": logged.id,
+                "event_type": event_type,
+                "payload": payload.clone(),
+                "status": STATUS_PENDING,
+                "attempts": 0,
+                "next_attempt_at": now,
+                "last_status_code": Bson::Null,
+                "last_error": Bson::Null,
+                "created_at": now,
+                "delivered_at": Bson::Null,
+            },
+            None,
+        )?;
+    }
+    Ok(())
+}
+
+///
+/// la signature HMAC-SHA256 de "timestamp.body" avec le secret du webhook
+/// le destinataire la recalcule pour vérifier que l'envoi vient de nous
+///
+pub fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac = HmacSha256::new_varkey(secret.as_bytes()).expect("HMAC accepts any key size");
+    mac.input(format!("{}.{}", timestamp, body).as_bytes());
+    hex::encode(mac.result().code())
+}
+
+///
+/// envoie le corps signé au webhook
+/// renvoie le code HTTP reçu, ou l'erreur de connexion
+///
+pub async fn post_signed(
+    url: &str,
+    secret: &str,
+    delivery_id: &str,
+    event_type: &str,
+    body: String,
+) -> Result<u16, String> {
+    let timestamp = Utc::now().timestamp();
+    let signature = sign(secret, timestamp, &body);
+
+    let response = Client::default()
+        .post(url)
+        .timeout(DELIVERY_TIMEOUT)
+        .header("Content-Type", "application/json")
+        .header("X-Seed-Event", event_type)
+        .header("X-Seed-Delivery", delivery_id)
+        .header("X-Seed-Timestamp", timestamp.to_string())
+        .header("X-Seed-Signature", format!("sha256={}", signature))
+        .send_body(body)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(response.status().as_u16())
+}
+
+// délai avant le prochain essai : 10s, 20s, 40s, ...
+fn retry_delay_millis(attempts: i32) -> i64 {
+    BASE_RETRY_DELAY_SECS * 1000 * 2_i64.pow((attempts - 1).max(0) as u32)
+}
+
+///
+/// essaie une livraison et enregistre le résultat
+/// les appels à MongoDB, synchrones, passent par web::block : l'arbitre ne les attend pas
+///
+async fn attempt_delivery(delivery: Delivery) -> Result<(), BlockingError<MyError>> {
+    let delivery_id = match &delivery.id {
+        Some(id) => id.clone(),
+        None => return Ok(()),
+    };
+    let webhook_id = delivery.webhook_id.clone();
+    let hook = match web::block(move || get_webhook_by_id(&webhook_id)).await? {
+        Some(hook) => hook,
+        None => {
+            let update = doc! {"$set": {"status": STATUS_FAILED, "last_error": "webhook deleted"}};
+            return web::block(move || record_attempt(&delivery_id, update)).await;
+        }
+    };
+
+    let result = post_signed(
+        &hook.url,
+        &hook.secret,
+        &delivery_id,
+        &delivery.event_type,
+        delivery.payload.clone(),
+    )
+    .await;
+
+    let attempts = delivery.attempts + 1;
+    let now = Utc::now().timestamp_millis();
+    let update = match result {
+        Ok(code) if code >= 200 && code < 300 => doc! {"$set": {
+            "status": STATUS_DELIVERED,
+            "attempts": attempts,
+            "last_status_code": (code as i32),
+            "last_error": Bson::Null,
+            "delivered_at": now,
+        }},
+        other => {
+            let (code, error) = match other {
+                Ok(code) => (Bson::I32(code as i32), format!("HTTP status {}", code)),
+                Err(e) => (Bson::Null, e),
+            };
+            let status = if attempts >= MAX_ATTEMPTS {
+                STATUS_FAILED
+            } else {
+                STATUS_PENDING
+            };
+            doc! {"$set": {
+                "status": status,
+                "attempts": attempts,
+                "last_status_code": code,
+                "last_error": error,
+                "next_attempt_at": now + retry_delay_millis(attempts),
+            }}
+        }
+    };
+    web::block(move || record_attempt(&delivery_id, update)).await
+}
+
+fn record_attempt(delivery_id: &str, update: Document) -> Result<(), MyError> {
+    let coll = db_mongo::get_named_collection("WebhookDeliveries")?;
+    coll.update_one(
+        doc! {"_id": ObjectId::with_string(delivery_id)?},
+        update,
+        None,
+    )?;
+    Ok(())
+}
+
+fn get_due_deliveries() -> Result<Vec<Delivery>, MyError> {
+    let coll = db_mongo::get_named_collection("WebhookDeliveries")?;
+    let options = FindOptions::builder()
+        .sort(doc! {"next_attempt_at": 1})
+        .limit(50)
+        .build();
+    let cursor = coll.find(
+        Some(doc! {
+            "status": STATUS_PENDING,
+            "next_attempt_at": {"$lte": Utc::now().timestamp_millis()},
+        }),
+        Some(options),
+    )?;
+    let res: Result<Vec<_>, MyError> = cursor.map(|row| from_document::<Delivery>(row?)).collect();
+    res
+}
+
+///
+/// lance les deux tâches de fond des webhooks :
+/// - la mise en file des livraisons à chaque événement
+/// - l'envoi des livraisons en attente, avec les nouveaux essais
+/// MongoDB est synchrone : ses appels passent par web::block, hors de l'arbitre
+///
+pub fn start_webhook_workers(broadcaster: &Broadcaster) {
+    let mut events = broadcaster.new_client();
+    actix_rt::spawn(async move {
+        while let Some(logged) = events.next().await {
+            let event_id = logged.id;
+            if let Err(e) = web::block(move || enqueue_deliveries(&logged)).await {
+                tracing::error!(event_id, error = %e, "failed to enqueue webhook deliveries");
+            }
+        }
+    });
+
+    actix_rt::spawn(async move {
+        loop {
+            actix_rt::time::delay_for(POLL_INTERVAL).await;
+            let due = match web::block(get_due_deliveries).await {
+                Ok(due) => due,
+                Err(e) => {
+                    tracing::error!(error = %e, "failed to read webhook deliveries");
+                    continue;
+                }
+            };
+            for delivery in due {
+                if let Err(e) = attempt_delivery(delivery).await {
+                    tracing::error!(error = %e, "failed to record webhook delivery");
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,104 @@
+// server/src/ws.rs
+
+use std::time::{Duration, Instant};
+
+use actix::{Actor, ActorContext, AsyncContext, StreamHandler};
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use actix_web_actors::ws;
+use futures::{future, Stream, StreamExt};
+
+use crate::broadcast::Broadcaster;
+use crate::event_log::LoggedEvent;
+use crate::tenancy::Tenant;
+
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+///
+/// une session WebSocket par client
+/// reçoit les événements du Broadcaster et les pousse au client en JSON
+///
+pub struct PersonWs {
+    heartbeat: Instant,
+    events: Option<Box<dyn Stream<Item = LoggedEvent> + Unpin>>,
+}
+
+impl PersonWs {
+    fn new(events: Box<dyn Stream<Item = LoggedEvent> + Unpin>) -> Self {
+        Self {
+            heartbeat: Instant::now(),
+            events: Some(events),
+        }
+    }
+
+    // on envoie un ping régulièrement
+    // et on coupe la connexion si le client ne répond plus
+    fn heartbeat(&self, ctx: &mut ws::WebsocketContext<Self>) {
+        ctx.run_interval(HEARTBEAT_INTERVAL, |act, ctx| {
+            if Instant::now().duration_since(act.heartbeat) > CLIENT_TIMEOUT {
+                ctx.stop();
+                return;
+            }
+            ctx.ping(b"");
+        });
+    }
+}
+
+impl Actor for PersonWs {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        self.heartbeat(ctx);
+        if let Some(events) = self.events.take() {
+            ctx.add_stream(events);
+        }
+    }
+}
+
+impl StreamHandler<LoggedEvent> for PersonWs {
+    fn handle(&mut self, logged: LoggedEvent, ctx: &mut Self::Context) {
+        match serde_json::to_string(&logged.event) {
+            Ok(text) => ctx.text(text),
+            Err(e) => tracing::error!(error = %e, "failed to serialize event"),
+        }
+    }
+
+    // le flux des événements ne doit pas fermer la session
+    fn finished(&mut self, _ctx: &mut Self::Context) {}
+}
+
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for PersonWs {
+    fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        match msg {
+            Ok(ws::Message::Ping(msg)) => {
+                self.heartbeat = Instant::now();
+                ctx.pong(&msg);
+            }
+            Ok(ws::Message::Pong(_)) => {
+                self.heartbeat = Instant::now();
+            }
+            Ok(ws::Message::Close(reason)) => {
+                ctx.close(reason);
+                ctx.stop();
+            }
+            Ok(_) => (),
+            Err(_) => ctx.stop(),
+        }
+    }
+}
+
+///
+/// la route /ws
+/// ouvre la session et l'abonne aux événements de son locataire
+///
+pub async fn ws_index(
+    req: HttpRequest,
+    stream: web::Payload,
+    broadcaster: web::Data<Broadcaster>,
+    tenant: Tenant,
+) -> Result<HttpResponse, Error> {
+    let events = broadcaster
+        .new_client()
+        .filter(move |logged| future::ready(logged.tenant == tenant.id));
+    ws::start(PersonWs::new(Box::new(events)), &req, stream)
+}
@@ -2,12 +2,89 @@
 
 use core::fmt;
 use serde::export::Formatter;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
+
+///
+/// l'identifiant d'une personne, le même quel que soit le stockage
+/// en JSON, c'est une chaîne ; avec MongoDB, la forme hexadécimale de l'ObjectId
+///
+#[derive(Serialize, Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(transparent)]
+pub struct PersonId(String);
+
+impl PersonId {
+    pub fn new(id: impl Into<String>) -> Self {
+        Self(id.into())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for PersonId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<String> for PersonId {
+    fn from(id: String) -> Self {
+        Self(id)
+    }
+}
+
+impl From<&str> for PersonId {
+    fn from(id: &str) -> Self {
+        Self(id.to_owned())
+    }
+}
+
+#[cfg(feature = "mongo")]
+impl From<bson::oid::ObjectId> for PersonId {
+    fn from(id: bson::oid::ObjectId) -> Self {
+        Self(id.to_hex())
+    }
+}
+
+#[cfg(feature = "mongo")]
+impl PersonId {
+    ///
+    /// l'ObjectId MongoDB ; une erreur si l'id n'a pas sa forme hexadécimale
+    ///
+    pub fn to_object_id(&self) -> Result<bson::oid::ObjectId, bson::oid::Error> {
+        bson::oid::ObjectId::with_string(&self.0)
+    }
+}
+
+// on accepte aussi l'ancienne forme {"$oid": "..."}
+// celle des anciennes sauvegardes et des documents MongoDB
+impl<'de> Deserialize<'de> for PersonId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Plain(String),
+            ObjectId {
+                #[serde(rename = "$oid")]
+                oid: String,
+            },
+        }
+        Ok(match Repr::deserialize(deserializer)? {
+            Repr::Plain(id) => Self(id),
+            Repr::ObjectId { oid } => Self(oid),
+        })
+    }
+}
 
 #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Person {
-    #[serde(rename = "_id")] // Use MongoDB's special primary key field name when serializing
-    pub id: Option<bson::oid::ObjectId>,
+    // "id" en JSON ; db_mongo le range dans _id
+    // _id est encore accepté en entrée : anciennes sauvegardes et anciens clients
+    #[serde(alias = "_id")]
+    pub id: Option<PersonId>,
     pub nom: String,
     pub prenom: String,
 }
@@ -44,6 +121,7 @@ impl fmt::Display for InsertablePers {
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct ListPersons {
     pub list_persons: Vec<Person>,
 }
@@ -83,3 +161,392 @@ impl Default for ListPersons {
     }
 }
 
+///
+/// les événements envoyés aux clients lorsqu'une personne
+/// est ajoutée, modifiée ou effacée
+///
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(tag = "type", content = "person", rename_all = "lowercase")]
+pub enum PersonEvent {
+    Created(Person),
+    Updated(Person),
+    Deleted(Person),
+}
+
+impl PersonEvent {
+    ///
+    /// le nom du type d'événement, le même que dans le JSON
+    ///
+    pub fn kind(&self) -> &'static str {
+        match self {
+            PersonEvent::Created(_) => "created",
+            PersonEvent::Updated(_) => "updated",
+            PersonEvent::Deleted(_) => "deleted",
+        }
+    }
+
+    pub fn person(&self) -> &Person {
+        match self {
+            PersonEvent::Created(pers) | PersonEvent::Updated(pers) | PersonEvent::Deleted(pers) => {
+                pers
+            }
+        }
+    }
+
+    ///
+    /// applique l'événement à une liste de personnes
+    /// sans devoir recharger toute la liste
+    ///
+    pub fn apply_to(&self, list: &mut ListPersons) {
+        match self {
+            PersonEvent::Created(pers) => {
+                if !list.list_persons.iter().any(|p| p.id == pers.id) {
+                    list.list_persons.push(pers.clone());
+                }
+            }
+            PersonEvent::Updated(pers) => {
+                match list.list_persons.iter_mut().find(|p| p.id == pers.id) {
+                    Some(p) => *p = pers.clone(),
+                    None => list.list_persons.push(pers.clone()),
+                }
+            }
+            PersonEvent::Deleted(pers) => list.list_persons.retain(|p| p.id != pers.id),
+        }
+    }
+}
+
+///
+/// le corps JSON renvoyé par l'API en cas d'erreur
+/// error : un code stable, message : une explication lisible
+///
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ErrorEnvelope {
+    pub error: String,
+    pub message: String,
+}
+
+impl ErrorEnvelope {
+    pub fn new(error: &str, message: impl Into<String>) -> Self {
+        Self {
+            error: error.to_owned(),
+            message: message.into(),
+        }
+    }
+}
+
+///
+/// une opération d'un lot (POST /persons/batch)
+/// ref est choisi par le client et revient dans le résultat ;
+/// dans l'id des opérations suivantes, "$<ref>" désigne la personne ajoutée par cette opération
+///
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct BatchOperation {
+    #[serde(rename = "ref", default, skip_serializing_if = "Option::is_none")]
+    pub reference: Option<String>,
+    #[serde(flatten)]
+    pub action: BatchAction,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum BatchAction {
+    Add { person: Person },
+    Modify { id: String, person: Person },
+    Delete { id: String },
+}
+
+///
+/// le corps de POST /persons/batch : les opérations, exécutées dans l'ordre
+/// partial à false (par défaut) : tout ou rien ; à true : chaque opération réussit ou échoue seule
+///
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct BatchRequest {
+    pub operations: Vec<BatchOperation>,
+    #[serde(default)]
+    pub partial: bool,
+}
+
+///
+/// le résultat d'une opération, au même rang que l'opération
+/// status est un code HTTP : 200, 404 pour une personne absente,
+/// 424 pour une opération annulée ou pas exécutée parce qu'une autre a échoué
+///
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct BatchResult {
+    #[serde(rename = "ref", default, skip_serializing_if = "Option::is_none")]
+    pub reference: Option<String>,
+    pub status: u16,
+    // la personne après l'opération ; pour delete, la personne effacée
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub person: Option<Person>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorEnvelope>,
+}
+
+///
+/// la réponse de POST /persons/batch
+/// committed à false : un lot tout ou rien a échoué et rien n'a été gardé
+///
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct BatchResponse {
+    pub committed: bool,
+    pub results: Vec<BatchResult>,
+}
+
+///
+/// un abonnement webhook
+/// events vide veut dire tous les événements
+///
+#[cfg(feature = "mongo")]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Webhook {
+    // "id" en JSON, comme Person ; webhooks le range dans _id, un ObjectId
+    #[serde(alias = "_id")]
+    pub id: Option<String>,
+    pub url: String,
+    pub events: Vec<String>,
+    // le secret n'est jamais renvoyé par l'API
+    #[serde(skip_serializing, default)]
+    pub secret: String,
+    pub active: bool,
+    // le locataire du webhook : il ne reçoit que ses événements ; None pour le mode single
+    #[serde(default)]
+    pub tenant: Option<String>,
+}
+
+#[cfg(feature = "mongo")]
+impl Webhook {
+    pub fn accepts(&self, event_type: &str) -> bool {
+        self.active && (self.events.is_empty() || self.events.iter().any(|e| e == event_type))
+    }
+}
+
+///
+/// ce que l'administrateur envoie pour créer un webhook
+///
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NewWebhook {
+    pub url: String,
+    #[serde(default)]
+    pub events: Vec<String>,
+    pub secret: String,
+    // par l'API, c'est toujours le locataire de la requête
+    #[serde(default)]
+    pub tenant: Option<String>,
+}
+
+///
+/// une livraison d'un événement à un webhook
+/// les dates sont en millisecondes depuis epoch
+///
+#[cfg(feature = "mongo")]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Delivery {
+    #[serde(alias = "_id")]
+    pub id: Option<String>,
+    pub webhook_id: String,
+    pub event_id: i64,
+    pub event_type: String,
+    pub payload: String,
+    pub status: String,
+    pub attempts: i32,
+    pub next_attempt_at: i64,
+    pub last_status_code: Option<i32>,
+    pub last_error: Option<String>,
+    pub created_at: i64,
+    pub delivered_at: Option<i64>,
+}
+
+///
+/// ce qu'une clé d'API ou un utilisateur a le droit de faire
+/// read : lire les personnes ; write : les modifier aussi ; admin : tout, webhooks et clés compris
+///
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    Read,
+    Write,
+    Admin,
+}
+
+impl Scope {
+    pub fn name(self) -> &'static str {
+        match self {
+            Scope::Read => "read",
+            Scope::Write => "write",
+            Scope::Admin => "admin",
+        }
+    }
+}
+
+impl std::str::FromStr for Scope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "read" => Ok(Scope::Read),
+            "write" => Ok(Scope::Write),
+            "admin" => Ok(Scope::Admin),
+            other => Err(format!("unknown scope '{}' (read, write or admin)", other)),
+        }
+    }
+}
+
+///
+/// une clé d'API pour les clients machines (tâches cron, intégrations)
+/// prefix identifie la clé ; seule l'empreinte du secret est gardée
+/// les dates sont en millisecondes depuis epoch
+///
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ApiKey {
+    // _id dans MongoDB
+    #[serde(alias = "_id")]
+    pub id: String,
+    pub name: String,
+    pub prefix: String,
+    // l'empreinte n'est jamais renvoyée par l'API
+    #[serde(skip_serializing, default)]
+    pub hash: String,
+    pub scopes: Vec<Scope>,
+    // sans locataire, la clé vaut pour tous
+    pub tenant: Option<String>,
+    pub created_at: i64,
+    pub expires_at: Option<i64>,
+    pub last_used_at: Option<i64>,
+    pub revoked_at: Option<i64>,
+}
+
+impl ApiKey {
+    ///
+    /// vrai si la clé donne ce droit ; chaque droit comprend ceux d'en dessous
+    ///
+    pub fn allows(&self, scope: Scope) -> bool {
+        self.scopes.iter().any(|s| *s >= scope)
+    }
+}
+
+///
+/// ce que l'administrateur envoie pour créer une clé
+///
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct NewApiKey {
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    #[serde(default)]
+    pub tenant: Option<String>,
+    #[serde(default)]
+    pub expires_at: Option<i64>,
+}
+
+///
+/// une clé qui vient d'être créée ou renouvelée
+/// secret est la clé complète à mettre dans Authorization: Bearer ; elle n'est montrée qu'une fois
+///
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct IssuedApiKey {
+    pub key: ApiKey,
+    pub secret: String,
+}
+
+///
+/// un utilisateur connecté par le fournisseur d'identité (OpenID Connect)
+/// créé à la première connexion ; groups et scopes sont repris à chaque connexion
+/// subject est l'identifiant chez le fournisseur : <issuer>#<sub>
+///
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct User {
+    // _id dans MongoDB
+    #[serde(alias = "_id")]
+    pub id: String,
+    pub subject: String,
+    pub name: String,
+    pub email: Option<String>,
+    pub groups: Vec<String>,
+    pub scopes: Vec<Scope>,
+    pub tenant: Option<String>,
+    pub created_at: i64,
+    pub last_login_at: i64,
+}
+
+///
+/// une étape d'une demande RGPD sur une personne (droit d'accès, droit à l'effacement)
+/// le journal garde seulement l'id de la personne : il survit à l'effacement
+/// l'entrée erased est la pierre tombale qui prouve l'effacement
+///
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ComplianceEntry {
+    // _id dans MongoDB
+    #[serde(alias = "_id")]
+    pub id: String,
+    pub tenant: Option<String>,
+    pub person_id: String,
+    pub action: ComplianceAction,
+    // key:<id>, user:<id> ou admin-token
+    pub actor: String,
+    // millisecondes depuis epoch
+    pub at: i64,
+    pub details: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ComplianceAction {
+    Export,
+    ErasureStarted,
+    Erased,
+    ErasureFailed,
+}
+
+impl ComplianceAction {
+    pub fn name(self) -> &'static str {
+        match self {
+            ComplianceAction::Export => "export",
+            ComplianceAction::ErasureStarted => "erasure_started",
+            ComplianceAction::Erased => "erased",
+            ComplianceAction::ErasureFailed => "erasure_failed",
+        }
+    }
+}
+
+impl std::str::FromStr for ComplianceAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "export" => Ok(ComplianceAction::Export),
+            "erasure_started" => Ok(ComplianceAction::ErasureStarted),
+            "erased" => Ok(ComplianceAction::Erased),
+            "erasure_failed" => Ok(ComplianceAction::ErasureFailed),
+            other => Err(format!("unknown compliance action '{}'", other)),
+        }
+    }
+}
+
+///
+/// ce que l'effacement d'une personne a retiré, avec la pierre tombale
+///
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ErasureReport {
+    pub tombstone: ComplianceEntry,
+    // false si la fiche avait déjà été effacée par une tentative précédente
+    pub person_deleted: bool,
+    pub events: usize,
+    pub webhook_deliveries: usize,
+    pub cached_responses: usize,
+}